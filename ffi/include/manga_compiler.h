@@ -0,0 +1,44 @@
+/* C ABI of manga-compiler. Regenerate with
+ * `cbindgen --config cbindgen.toml --output include/manga_compiler.h` after changing src/lib.rs. */
+
+#ifndef MANGA_COMPILER_H
+#define MANGA_COMPILER_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Statistics of a successful mc_compile. */
+typedef struct McSummary {
+  uint64_t pages;
+  uint64_t skipped;
+  uint64_t warnings;
+  uint64_t bytes_in;
+  uint64_t bytes_out;
+} McSummary;
+
+/* Compiles folder into an archive and returns an exit code (0 on success).
+ *
+ * output is the exact archive path, or NULL to derive it like the CLI does. config is NULL or
+ * a TOML document with the keys of .manga-compiler.toml, taking precedence over the config
+ * files. summary may be NULL. */
+int32_t mc_compile(const char *folder, const char *output, const char *config, McSummary *summary);
+
+/* Checks an archive like `manga-compiler verify`. Returns 6 if problems were found, with one
+ * problem per line in mc_last_error(). */
+int32_t mc_verify(const char *archive);
+
+/* Why the last call on this thread failed, or NULL if it succeeded. Valid until the next call
+ * into this library from the same thread. */
+const char *mc_last_error(void);
+
+/* The library version as a static string. */
+const char *mc_version(void);
+
+#ifdef __cplusplus
+}  /* extern "C" */
+#endif
+
+#endif  /* MANGA_COMPILER_H */
@@ -0,0 +1,129 @@
+//! C ABI over the manga-compiler library; see `include/manga_compiler.h`.
+//!
+//! Every function returns one of the CLI exit codes (0 on success). The message of the last
+//! failure on the calling thread is available from [`mc_last_error`].
+
+use manga_compiler::exit::{self, Code};
+use manga_compiler::{Compiler, Config, archive};
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString, c_char},
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    ptr,
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Statistics of a successful [`mc_compile`].
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct McSummary {
+    pub pages: u64,
+    pub skipped: u64,
+    pub warnings: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+}
+
+/// Compiles `folder` into an archive.
+///
+/// `output` is the exact archive path, or NULL to derive it like the CLI does. `config` is
+/// NULL or a TOML document with the keys of `.manga-compiler.toml`, taking precedence over the
+/// config files. `summary` may be NULL.
+///
+/// # Safety
+///
+/// `folder` must be a valid NUL-terminated string, `output` and `config` NULL or valid
+/// NUL-terminated strings, and `summary` NULL or valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mc_compile(
+    folder: *const c_char,
+    output: *const c_char,
+    config: *const c_char,
+    summary: *mut McSummary,
+) -> i32 {
+    guard(|| {
+        let folder = unsafe { string(folder) }?.ok_or_else(|| exit::fail(Code::Usage, "folder is NULL"))?;
+        let config = match unsafe { string(config) }? {
+            Some(text) => Config::parse(&text)?,
+            None => Config::default(),
+        };
+        let mut compiler = Compiler::new(folder).config(config);
+        if let Some(output) = unsafe { string(output) }? {
+            compiler = compiler.output(PathBuf::from(output));
+        }
+        let stats = compiler.run()?.stats;
+        if !summary.is_null() {
+            let out = McSummary {
+                pages: stats.pages as u64,
+                skipped: stats.skipped as u64,
+                warnings: stats.warnings as u64,
+                bytes_in: stats.bytes_in,
+                bytes_out: stats.bytes_out,
+            };
+            unsafe { summary.write(out) };
+        }
+        Ok(())
+    })
+}
+
+/// Checks an archive the way `manga-compiler verify` does. Returns 6 if problems were found,
+/// with one problem per line in [`mc_last_error`].
+///
+/// # Safety
+///
+/// `archive` must be a valid NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mc_verify(archive: *const c_char) -> i32 {
+    guard(|| {
+        let path = unsafe { string(archive) }?.ok_or_else(|| exit::fail(Code::Usage, "archive is NULL"))?;
+        let problems = archive::verify(path.as_ref())?;
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(exit::fail(Code::Validation, problems.join("\n")))
+    })
+}
+
+/// Why the last call on this thread failed, or NULL if it succeeded. The pointer stays valid until
+/// the next call into this library from the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn mc_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// The library version as a static string.
+#[unsafe(no_mangle)]
+pub extern "C" fn mc_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// Runs `f`, turning errors and panics into an exit code plus the thread's last error.
+fn guard(f: impl FnOnce() -> anyhow::Result<()>) -> i32 {
+    let result = panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|_| Err(anyhow::anyhow!("panic inside manga-compiler")));
+    let (code, message) = match result {
+        Ok(()) => (0, None),
+        Err(e) => (exit::code_of(&e) as i32, Some(format!("{:#}", e))),
+    };
+    let message = message.map(|m| CString::new(m.replace('\0', " ")).unwrap());
+    LAST_ERROR.with(|e| *e.borrow_mut() = message);
+    code
+}
+
+/// # Safety
+///
+/// `s` must be NULL or a valid NUL-terminated string.
+unsafe fn string(s: *const c_char) -> anyhow::Result<Option<String>> {
+    if s.is_null() {
+        return Ok(None);
+    }
+    let s = unsafe { CStr::from_ptr(s) };
+    match s.to_str() {
+        Ok(s) => Ok(Some(s.to_string())),
+        Err(_) => Err(exit::fail(Code::Usage, "argument is not valid UTF-8")),
+    }
+}
@@ -0,0 +1,197 @@
+//! Python bindings: `manga_compiler.discover_pages` and `manga_compiler.compile`.
+
+use ::manga_compiler::discover::describe_gaps;
+use ::manga_compiler::progress::{ProgressObserver, Warning};
+use ::manga_compiler::{self as mc, Compiler, Config, Options};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyInt, PyString, PyTuple};
+use std::path::{Path, PathBuf};
+
+create_exception!(
+    manga_compiler,
+    CompileError,
+    PyException,
+    "Compiling failed; `args` is `(message, exit_code)` with the CLI's exit code."
+);
+
+fn compile_error(err: mc::CompileError) -> PyErr {
+    let code = err.code() as i32;
+    CompileError::new_err((format!("{:#}", anyhow::Error::from(err)), code))
+}
+
+/// The pages found in a folder.
+#[pyclass(frozen, get_all, module = "manga_compiler")]
+struct PageSet {
+    /// The pattern after `{folder}` expansion.
+    pattern: String,
+    /// `(number, path)` tuples ordered by page number.
+    pages: Vec<(u32, String)>,
+    noise: Vec<String>,
+    missing: Vec<u32>,
+}
+
+/// What a successful `compile` wrote.
+#[pyclass(frozen, get_all, module = "manga_compiler")]
+struct Summary {
+    source: String,
+    output: String,
+    pages: usize,
+    skipped: usize,
+    warnings: usize,
+    bytes_in: u64,
+    bytes_out: u64,
+    elapsed_secs: f64,
+    ignored: Vec<String>,
+    missing: Vec<u32>,
+}
+
+fn path_str(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+/// Classifies the files of `folder` into pages and noise without writing anything.
+#[pyfunction]
+#[pyo3(signature = (folder, pattern = None))]
+fn discover_pages(folder: PathBuf, pattern: Option<String>) -> PyResult<PageSet> {
+    let set = mc::discover_pages(&folder, &Options { pattern, ..Options::default() }).map_err(compile_error)?;
+    Ok(PageSet {
+        pattern: set.pattern,
+        pages: set.pages.iter().map(|p| (p.number, path_str(&p.path))).collect(),
+        noise: set.noise.iter().map(|p| path_str(p)).collect(),
+        missing: set.missing,
+    })
+}
+
+/// Compiles `folder` into an archive.
+///
+/// Keyword options are the config file keys with `_` for `-`, e.g. `output_dir="/library"` or
+/// `missing="warn"`. `progress` is called as `progress(event, *args)` with the events
+/// `("started", output, entries)`, `("page", index, name)`, `("bytes", written)` and
+/// `("warning", message)`; an exception it raises is re-raised once the archive is written.
+#[pyfunction]
+#[pyo3(signature = (folder, *, output = None, progress = None, use_config_files = true, **options))]
+fn compile(
+    py: Python<'_>,
+    folder: PathBuf,
+    output: Option<PathBuf>,
+    progress: Option<PyObject>,
+    use_config_files: bool,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<Summary> {
+    let config = match options {
+        Some(options) => config_from(options)?,
+        None => Config::default(),
+    };
+    let mut compiler = Compiler::new(folder).config(config).use_config_files(use_config_files);
+    if let Some(output) = output {
+        compiler = compiler.output(output);
+    }
+    let mut observer = PyProgress { callback: progress, error: None };
+    let result = py.allow_threads(|| compiler.run_with(&mut observer));
+    if let Some(err) = observer.error {
+        return Err(err);
+    }
+    let summary = result.map_err(compile_error)?;
+    Ok(Summary {
+        source: path_str(&summary.source),
+        output: path_str(&summary.output),
+        pages: summary.stats.pages,
+        skipped: summary.stats.skipped,
+        warnings: summary.stats.warnings,
+        bytes_in: summary.stats.bytes_in,
+        bytes_out: summary.stats.bytes_out,
+        elapsed_secs: summary.stats.elapsed_secs,
+        ignored: summary.ignored,
+        missing: summary.missing,
+    })
+}
+
+/// Builds a [`Config`] the same way a config file would, so every key and check is shared.
+fn config_from(options: &Bound<'_, PyDict>) -> PyResult<Config> {
+    let mut table = toml::Table::new();
+    for (key, value) in options {
+        let key = key.extract::<String>()?.replace('_', "-");
+        let value = if value.is_instance_of::<PyBool>() {
+            toml::Value::Boolean(value.extract()?)
+        } else if value.is_instance_of::<PyInt>() {
+            toml::Value::Integer(value.extract()?)
+        } else if value.is_instance_of::<PyString>() {
+            toml::Value::String(value.extract()?)
+        } else if let Ok(path) = value.extract::<PathBuf>() {
+            toml::Value::String(path_str(&path))
+        } else {
+            return Err(PyTypeError::new_err(format!("unsupported value for {}", key)));
+        };
+        table.insert(key, value);
+    }
+    toml::Value::Table(table)
+        .try_into()
+        .map_err(|e| PyValueError::new_err(format!("invalid option: {}", e.to_string().trim_end())))
+}
+
+struct PyProgress {
+    callback: Option<PyObject>,
+    error: Option<PyErr>,
+}
+
+impl PyProgress {
+    fn emit(&mut self, args: impl for<'py> IntoPyObject<'py, Target = PyTuple>) {
+        let Some(callback) = &self.callback else { return };
+        if self.error.is_some() {
+            return;
+        }
+        let result = Python::with_gil(|py| callback.call1(py, args).map(drop));
+        self.error = result.err();
+    }
+}
+
+impl ProgressObserver for PyProgress {
+    fn started(&mut self, output: &Path, entries: usize) {
+        let output = path_str(output);
+        self.emit(("started", output, entries));
+    }
+
+    fn page_finished(&mut self, index: usize, name: &str) {
+        self.emit(("page", index, name.to_string()));
+    }
+
+    fn bytes_written(&mut self, bytes: u64) {
+        self.emit(("bytes", bytes));
+    }
+
+    fn warning(&mut self, warning: &Warning) {
+        let message = match warning {
+            Warning::IgnoredFiles(names) => format!("ignored files not matching pattern: {}", names.join(", ")),
+            Warning::DroppedDuplicates(names) => format!("left out duplicate copies of pages: {}", names.join(", ")),
+            Warning::MissingPages(numbers) => format!("missing page numbers: {:?}", numbers),
+            Warning::MissingChapterPages(gaps) => format!("missing page numbers: {}", describe_gaps(gaps)),
+            Warning::IdenticalPages(pairs) => {
+                let pairs: Vec<String> = pairs.iter().map(|(copy, page)| format!("{} = {}", copy, page)).collect();
+                format!("pages identical to an earlier page: {}", pairs.join(", "))
+            }
+            Warning::OverMemory(names) => {
+                format!("stored as they are, preparing them would take more than max-memory: {}", names.join(", "))
+            }
+            Warning::Unfiltered(pages) => {
+                let pages: Vec<String> = pages.iter().map(|(name, reason)| format!("{} ({})", name, reason)).collect();
+                format!("stored unfiltered, the filter failed on: {}", pages.join(", "))
+            }
+            other => format!("{:?}", other),
+        };
+        self.emit(("warning", message));
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "manga_compiler")]
+fn manga_compiler_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    m.add("CompileError", m.py().get_type::<CompileError>())?;
+    m.add_class::<PageSet>()?;
+    m.add_class::<Summary>()?;
+    m.add_function(wrap_pyfunction!(discover_pages, m)?)?;
+    m.add_function(wrap_pyfunction!(compile, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,258 @@
+use crate::compiler;
+use crate::paths;
+use crate::exit::{Code, WithCode};
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    fs::File,
+    io::{self, Write},
+    iter::Peekable,
+    path::Path,
+    str::Chars,
+    sync::LazyLock,
+    time::SystemTime,
+};
+use zip::{ZipArchive, ZipWriter, write::FileOptions};
+
+pub const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png"];
+
+static TRAILING_NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d+)\.[^./]+$").unwrap());
+static VOLUME: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)\b(?:vol(?:ume)?\.?|v)\s*(\d+)").unwrap());
+static CHAPTER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(?:ch(?:apter)?\.?|c)\s*(\d+(?:\.\d+)?)").unwrap());
+static CHAPTER_TITLE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(?:ch(?:apter)?\.?|c)\s*\d+(?:\.\d+)?\s*[-:]\s*(\S.*?)\s*$").unwrap());
+static SPECIAL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(?:sp\d*|specials?|omake|extras?|one-?shot)\b").unwrap());
+static LAST_VALUE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d+(?:\.\d+)?)\D*$").unwrap());
+static LAST_NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d+)\D*$").unwrap());
+
+pub fn open(path: &Path) -> Result<ZipArchive<File>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    ZipArchive::new(file).with_context(|| format!("{} is not a valid zip archive", path.display()))
+}
+
+pub fn is_image(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| IMAGE_EXTENSIONS.iter().any(|x| e.eq_ignore_ascii_case(x)))
+}
+
+pub fn page_number(name: &str) -> Option<u32> {
+    TRAILING_NUMBER.captures(name)?[1].parse().ok()
+}
+
+/// The volume a folder name refers to: the number after `Vol`, `Volume` or `v`, else its last
+/// number unless that belongs to a chapter token.
+pub fn volume_number(name: &str) -> Option<u32> {
+    let caps = match VOLUME.captures(name) {
+        Some(caps) => caps,
+        None if CHAPTER.is_match(name) => return None,
+        None => LAST_NUMBER.captures(name)?,
+    };
+    caps[1].parse().ok()
+}
+
+/// The number after `Ch`, `Chapter` or `c` in a folder name, decimals such as `Ch 15.5` kept.
+pub fn chapter_number(name: &str) -> Option<f64> {
+    CHAPTER.captures(name)?[1].parse().ok()
+}
+
+/// The chapter a folder holds: the number after a chapter token, else the last number in the
+/// name.
+pub fn chapter_value(name: &str) -> Option<f64> {
+    let caps = CHAPTER.captures(name).or_else(|| LAST_VALUE.captures(name))?;
+    caps[1].parse().ok()
+}
+
+/// What follows the chapter number after a dash or colon, as in `Chapter 21.5 - The Hawk`.
+pub fn chapter_title(name: &str) -> Option<String> {
+    Some(CHAPTER_TITLE.captures(name)?[1].to_string())
+}
+
+/// Whether a folder name marks a special rather than a numbered volume.
+pub fn is_special(name: &str) -> bool {
+    SPECIAL.is_match(name)
+}
+
+/// Page numbers between 1 and the highest numbered page that never occur.
+pub fn missing_pages(numbers: &[u32]) -> Vec<u32> {
+    let present: HashSet<u32> = numbers.iter().copied().collect();
+    let max = numbers.iter().copied().max().unwrap_or(0);
+    (1..=max).filter(|n| !present.contains(n)).collect()
+}
+
+/// Rewrites `path` in place: entries accepted by `keep` are raw-copied, then `extend` may add new ones.
+pub fn rewrite(
+    path: &Path,
+    keep: impl Fn(&str) -> bool,
+    extend: impl FnOnce(&mut ZipWriter<File>) -> Result<()>,
+) -> Result<()> {
+    rewrite_with(path, |src, dst| {
+        for i in 0..src.len() {
+            let entry = src.by_index_raw(i)?;
+            if keep(entry.name()) {
+                dst.raw_copy_file(entry)?;
+            }
+        }
+        extend(dst)
+    })
+}
+
+/// New contents for an entry of an archive.
+#[derive(Debug, Clone)]
+pub struct Replacement {
+    /// Name of the entry replaced.
+    pub entry: String,
+    /// Name of the new entry, which differs when the format does.
+    pub name: String,
+    pub data: Vec<u8>,
+    pub modified: Option<SystemTime>,
+}
+
+/// Rewrites `path` in place with each replacement written where its entry was, compressed the
+/// same way; every other entry is raw-copied, so it stays exactly as it was.
+pub fn replace(path: &Path, replacements: &[Replacement]) -> Result<()> {
+    rewrite_with(path, |src, dst| {
+        for i in 0..src.len() {
+            let entry = src.by_index_raw(i)?;
+            let Some(new) = replacements.iter().find(|r| r.entry == entry.name()) else {
+                dst.raw_copy_file(entry)?;
+                continue;
+            };
+            let mut options = FileOptions::default().compression_method(entry.compression());
+            if let Some(time) = new.modified.and_then(compiler::dos_time) {
+                options = options.last_modified_time(time);
+            }
+            dst.start_file(new.name.as_str(), options)?;
+            dst.write_all(&new.data)?;
+        }
+        Ok(())
+    })
+}
+
+/// Writes the archive `body` copies from the current `path` through [`paths::write_atomic`].
+fn rewrite_with(
+    path: &Path,
+    body: impl FnOnce(&mut ZipArchive<File>, &mut ZipWriter<File>) -> Result<()>,
+) -> Result<()> {
+    let mut src = open(path)?;
+    paths::write_atomic(path, |part| {
+        let file = File::create(part)
+            .with_context(|| format!("Failed to create {}", part.display()))
+            .code(Code::Write)?;
+        let mut dst = ZipWriter::new(file);
+        body(&mut src, &mut dst)?;
+        dst.finish()?;
+        Ok(())
+    })
+}
+
+/// Compares names so that embedded numbers sort by value (`p2` before `p10`).
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        let ordering = match (a.peek(), b.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (x, y) = (take_number(&mut a), take_number(&mut b));
+                let (tx, ty) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                tx.len().cmp(&ty.len()).then(tx.cmp(ty)).then(x.len().cmp(&y.len()))
+            }
+            (Some(&x), Some(&y)) => {
+                a.next();
+                b.next();
+                x.to_lowercase().cmp(y.to_lowercase())
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn take_number(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits
+}
+
+/// Canonical page file name, `title-007.jpg`, padded to at least three digits.
+pub fn page_file_name(title: &str, number: u32, total: usize, ext: &str) -> String {
+    let width = total.to_string().len().max(3);
+    format!("{}-{:0width$}.{}", title, number, ext.to_ascii_lowercase())
+}
+
+/// Names of the image entries of an archive in reading order, which is how readers number pages.
+pub fn page_names(zip: &ZipArchive<File>) -> Vec<String> {
+    let mut names: Vec<String> =
+        zip.file_names().filter(|n| !n.ends_with('/') && !is_junk(n) && is_image(n)).map(String::from).collect();
+    names.sort_by(|a, b| natural_cmp(a, b));
+    names
+}
+
+pub fn is_junk(name: &str) -> bool {
+    name.split('/').any(|part| part == "__MACOSX" || part.starts_with('.'))
+        || name.rsplit('/').next().is_some_and(|f| f.eq_ignore_ascii_case("Thumbs.db"))
+}
+
+/// Reads every entry (which checks its CRC) of an archive just written for a book and returns
+/// the problems found, including each of `names`, the book's entries, that it lacks. Unlike
+/// [`verify`] it does not judge the page numbering, which the book's pages, its extras, a page
+/// selection or its missing-page policy decide.
+pub fn verify_entries(path: &Path, names: &[String]) -> Result<Vec<String>> {
+    let mut zip = open(path)?;
+    let mut problems = Vec::new();
+    let mut found = HashSet::new();
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        let name = entry.name().to_string();
+        if let Err(e) = io::copy(&mut entry, &mut io::sink()) {
+            problems.push(format!("{}: {}", name, e));
+        }
+        found.insert(name);
+    }
+    problems.extend(names.iter().filter(|n| !found.contains(n.as_str())).map(|n| format!("{}: missing", n)));
+    Ok(problems)
+}
+
+/// Reads every entry (which checks its CRC) and returns the problems found.
+pub fn verify(path: &Path) -> Result<Vec<String>> {
+    let mut zip = open(path)?;
+    let mut problems = Vec::new();
+    let mut numbers = Vec::new();
+    let mut seen = HashSet::new();
+
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        let name = entry.name().to_string();
+        if let Err(e) = io::copy(&mut entry, &mut io::sink()) {
+            problems.push(format!("{}: {}", name, e));
+            continue;
+        }
+        if entry.is_dir() || !is_image(&name) {
+            continue;
+        }
+        match page_number(&name) {
+            Some(n) if !seen.insert(n) => problems.push(format!("{}: duplicate page number {}", name, n)),
+            Some(n) => numbers.push(n),
+            None => problems.push(format!("{}: no page number", name)),
+        }
+    }
+
+    if numbers.is_empty() {
+        problems.push("no pages".to_string());
+    }
+    let missing = missing_pages(&numbers);
+    if !missing.is_empty() {
+        problems.push(format!("missing page numbers: {:?}", missing));
+    }
+    Ok(problems)
+}
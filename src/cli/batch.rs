@@ -0,0 +1,326 @@
+use super::build::{self, CompileOpts};
+use super::i18n::{self, t};
+use super::index::{self, Index, Record};
+use super::log;
+use super::resume::{self, BatchState};
+use super::style::{self, Tone};
+use super::upload;
+use super::watch::{self, WatchOpts};
+use manga_compiler::{Book, Config, archive};
+use manga_compiler::exit::{self, Code};
+use manga_compiler::summary::{BatchSummary, Existing, Summary};
+use anyhow::{Context, Result};
+use std::{
+    cell::Cell,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+use walkdir::WalkDir;
+
+#[derive(clap::Args, Debug)]
+pub struct BatchArgs {
+    /// Folders whose immediate subfolders are each compiled into an archive
+    #[arg(required = true)]
+    pub roots: Vec<PathBuf>,
+    /// Treat each root as a library of series folders holding volume folders, with each series
+    /// folder's config shared by its volumes
+    #[arg(long)]
+    pub tree: bool,
+    /// Print the run summary as JSON instead of text
+    #[arg(long)]
+    pub json: bool,
+    /// Skip books whose archives were already compiled and verified by an earlier run, or with
+    /// --index, books unchanged since they were indexed
+    #[arg(long)]
+    pub resume: bool,
+    /// Where verified books are recorded, defaults to `.manga-compiler-batch.json` in the first root
+    #[arg(long, value_name = "FILE")]
+    pub state_file: Option<PathBuf>,
+    /// What to do with a book whose archive already exists
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = OnExists::Overwrite)]
+    pub on_exists: OnExists,
+    #[command(flatten)]
+    pub opts: CompileOpts,
+    #[command(flatten)]
+    pub watch: WatchOpts,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnExists {
+    /// Replace the archive
+    Overwrite,
+    /// Leave the archive and the book alone
+    Skip,
+    /// Keep the archive and write the book next to it as `<name> (1).cbz`, ...
+    Rename,
+    /// Ask for each book, or once for all the rest
+    Prompt,
+}
+
+/// A book written, or left alone since its archive exists.
+enum Outcome {
+    Compiled(Box<Summary>),
+    Skipped(PathBuf),
+}
+
+pub fn run(args: &BatchArgs) -> Result<()> {
+    let started = Instant::now();
+    let books = find_books(&args.roots, args.tree)?;
+    let state_path = args
+        .state_file
+        .clone()
+        .unwrap_or_else(|| args.roots[0].join(resume::STATE_FILE));
+    let mut state = if args.resume { BatchState::load(&state_path)? } else { BatchState::default() };
+    let indexed = match &args.opts.index {
+        Some(path) if args.resume => Some(Index::open(path)?.records()?),
+        _ => None,
+    };
+
+    if args.on_exists == OnExists::Prompt && !io::stdin().is_terminal() {
+        return Err(exit::fail(Code::Usage, "--on-exists prompt needs a terminal to ask on"));
+    }
+    let on_exists = Cell::new(args.on_exists);
+    let mut summary = BatchSummary::default();
+    for folder in &books {
+        let record = indexed.as_ref().and_then(|records| records.get(&index::absolute(folder)));
+        let unchanged = |r: &&Record| {
+            let settings = inherited(folder, args).and_then(|config| index::settings(folder, &args.opts, config));
+            settings.is_ok_and(|s| r.unchanged(folder, &s))
+        };
+        if let Some(record) = record.filter(unchanged) {
+            if !args.json {
+                let output = record.output.display();
+                println!("{}", t!("Skipping {0}, unchanged since {1} was compiled", folder.display(), output));
+            }
+            summary.push_resumed(folder.clone(), record.output.clone());
+            continue;
+        }
+        if let Some(output) = (args.resume && indexed.is_none()).then(|| state.finished(folder)).flatten() {
+            if !args.json {
+                println!("{}", t!("Skipping {0}, {1} is already verified", folder.display(), output.display()));
+            }
+            summary.push_resumed(folder.clone(), output.to_path_buf());
+            continue;
+        }
+        let result = match compile(folder, args, &on_exists) {
+            Ok(Outcome::Skipped(output)) => {
+                if !args.json {
+                    println!("{}", t!("Skipping {0}, {1} already exists", folder.display(), output.display()));
+                }
+                log::line(format_args!("Skipped {}, {} already exists", folder.display(), output.display()));
+                summary.push_skipped(folder.clone(), output);
+                continue;
+            }
+            Ok(Outcome::Compiled(s)) => Ok(*s),
+            Err(e) => Err(e),
+        };
+        match &result {
+            Ok(s) => state.mark_verified(folder, &s.output),
+            Err(_) => state.forget(folder),
+        }
+        state.save(&state_path)?;
+        summary.push(folder.clone(), &result);
+    }
+    summary.total.set_elapsed(started.elapsed());
+
+    if args.json {
+        println!("{}", serde_json::to_string(&summary).unwrap());
+    } else {
+        if args.tree {
+            report_series(&summary, &args.roots);
+        }
+        println!("{}", t!("{0} of {1} books compiled", summary.compiled, books.len()));
+        if summary.resumed > 0 {
+            println!("  {}", t!("{0} skipped as already verified", summary.resumed));
+        }
+        if summary.skipped_existing > 0 {
+            println!("  {}", t!("{0} skipped as their archives exist", summary.skipped_existing));
+        }
+        println!("  {}", i18n::stats(&summary.total));
+    }
+
+    if args.watch.watch {
+        // A changed book replaces the archive this run wrote.
+        let overwrite = Cell::new(OnExists::Overwrite);
+        return watch::run(&args.watch, || find_books(&args.roots, args.tree), |folder| {
+            if let Ok(Outcome::Compiled(summary)) = compile(folder, args, &overwrite)
+                && args.json
+            {
+                build::report(&summary, true);
+            }
+        });
+    }
+    if summary.failed > 0 {
+        return Err(exit::fail(Code::Batch, format!("{} of {} books failed", summary.failed, books.len())));
+    }
+    Ok(())
+}
+
+fn find_books(roots: &[PathBuf], tree: bool) -> Result<Vec<PathBuf>> {
+    let mut books = Vec::new();
+    for root in roots {
+        for folder in subfolders(root)? {
+            if !tree {
+                books.push(folder);
+                continue;
+            }
+            // A series folder holding its pages directly is a series of one book.
+            let volumes: Vec<PathBuf> = subfolders(&folder)?.into_iter().filter(|v| has_pages(v)).collect();
+            if volumes.is_empty() && has_pages(&folder) {
+                books.push(folder);
+            } else {
+                books.extend(volumes);
+            }
+        }
+    }
+    Ok(books)
+}
+
+fn subfolders(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut folders = Vec::new();
+    for entry in WalkDir::new(dir).min_depth(1).max_depth(1).sort_by_file_name() {
+        let entry = entry.with_context(|| format!("Failed to read {}", dir.display()))?;
+        if entry.file_type().is_dir() {
+            folders.push(entry.into_path());
+        }
+    }
+    Ok(folders)
+}
+
+fn has_pages(dir: &Path) -> bool {
+    WalkDir::new(dir)
+        .min_depth(1)
+        .max_depth(1)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .any(|e| e.file_type().is_file() && archive::is_image(&e.file_name().to_string_lossy()))
+}
+
+/// The series folder of a book in a library: the folder right below its root.
+fn series_folder<'a>(book: &'a Path, roots: &[PathBuf]) -> Option<&'a Path> {
+    let root = roots.iter().find(|r| book.starts_with(r))?;
+    book.ancestors().find(|a| a.parent() == Some(root.as_path()))
+}
+
+/// One line per series with how many of its books were compiled.
+fn report_series(summary: &BatchSummary, roots: &[PathBuf]) {
+    let mut series: Vec<(String, usize, usize)> = Vec::new();
+    for book in &summary.books {
+        let name = series_folder(&book.source, roots)
+            .and_then(|s| s.file_name())
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let index = match series.iter().position(|(n, _, _)| *n == name) {
+            Some(index) => index,
+            None => {
+                series.push((name, 0, 0));
+                series.len() - 1
+            }
+        };
+        series[index].1 += usize::from(book.error.is_none());
+        series[index].2 += 1;
+    }
+    for (name, compiled, total) in &series {
+        let line = t!("{0}: {1} of {2} books", name, compiled, total);
+        let tone = if compiled == total { Tone::Success } else { Tone::Error };
+        println!("{}", style::out(tone, line));
+    }
+}
+
+/// Verifies a freshly written archive against the entries of `book` so that `--resume` can
+/// trust it later.
+fn check(summary: Summary, book: &Book) -> Result<Summary> {
+    let names: Vec<String> = book.entries().into_iter().map(|(name, _)| name).collect();
+    let problems = archive::verify_entries(&summary.output, &names)?;
+    if problems.is_empty() {
+        return Ok(summary);
+    }
+    let message = format!("{} failed verification: {}", summary.output.display(), problems.join(", "));
+    Err(exit::fail(Code::Validation, message))
+}
+
+/// What the book of `folder` inherits: in a library the series folder's config applies below the
+/// book's own, and its name is the series unless that config names one.
+fn inherited(folder: &Path, args: &BatchArgs) -> Result<Config> {
+    let mut inherited = Config::default();
+    if let Some(series) = series_folder(folder, &args.roots).filter(|_| args.tree) {
+        inherited = Config::load_folder(series)?;
+        let name = series.file_name().map(|n| n.to_string_lossy().into_owned());
+        inherited.series = inherited.series.or(name);
+        // As many books as volume or chapter folders, one for a series folder holding its pages.
+        let books = subfolders(series)?.into_iter().filter(|v| has_pages(v)).count() as u32;
+        inherited.count = inherited.count.or(Some(books.max(1)));
+    }
+    Ok(inherited)
+}
+
+/// Compiles one book, printing its outcome unless the whole run is reported as JSON at the end.
+/// `on_exists` becomes the answer given for all books at a prompt.
+fn compile(folder: &Path, args: &BatchArgs, on_exists: &Cell<OnExists>) -> Result<Outcome> {
+    let opts = &args.opts;
+    let result = (|| {
+        let inherited = inherited(folder, args)?;
+        let (mut compiler, book) = build::discover(folder, None, opts, inherited.clone())?;
+        let out_path = book.out_path(None)?;
+        let mut existing = None;
+        // Appending needs the archive that is there.
+        if out_path.exists() && !opts.append {
+            let action = match on_exists.get() {
+                OnExists::Prompt => prompt(&out_path, on_exists)?,
+                action => action,
+            };
+            existing = Some(match action {
+                OnExists::Skip => return Ok(Outcome::Skipped(out_path)),
+                OnExists::Rename => {
+                    compiler = compiler.output(free_name(&out_path));
+                    Existing::Renamed
+                }
+                _ => Existing::Overwritten,
+            });
+        }
+        let summary = Summary { existing, ..build::compile_book(&compiler, &book)? };
+        let summary = check(summary, &book)?;
+        if !args.json {
+            build::report(&summary, false);
+        }
+        index::record(opts, &summary, inherited)?;
+        upload::run(&opts.upload, &summary.output)?;
+        Ok(Outcome::Compiled(Box::new(summary)))
+    })();
+    if let Err(e) = &result {
+        log::line(format_args!("Failed {}: {:#}", folder.display(), e));
+        eprintln!("{}", style::err(Tone::Error, t!("Failed {0}: {1}", folder.display(), format!("{:#}", e))));
+    }
+    result
+}
+
+/// `<name> (1).cbz`, or the first higher number not taken.
+fn free_name(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    (1..).map(|n| path.with_file_name(format!("{} ({}){}", stem, n, ext))).find(|p| !p.exists()).unwrap()
+}
+
+/// Asks on the terminal what to do with `path`; an upper case answer also stores it in
+/// `on_exists` for the remaining books.
+fn prompt(path: &Path, on_exists: &Cell<OnExists>) -> Result<OnExists> {
+    loop {
+        eprint!("{}", t!("{0} exists. [o]verwrite, [s]kip or [r]ename? (O, S or R for all books) ", path.display()));
+        io::stderr().flush()?;
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer)? == 0 {
+            return Err(exit::fail(Code::Usage, format!("No answer for {}", path.display())));
+        }
+        let action = match answer.trim().to_lowercase().as_str() {
+            "o" | "overwrite" => OnExists::Overwrite,
+            "s" | "skip" => OnExists::Skip,
+            "r" | "rename" => OnExists::Rename,
+            _ => continue,
+        };
+        if matches!(answer.trim(), "O" | "S" | "R") {
+            on_exists.set(action);
+        }
+        return Ok(action);
+    }
+}
@@ -0,0 +1,347 @@
+use super::download::{self, DownloadOpts};
+use super::i18n::{self, t};
+use super::index;
+use super::log;
+use super::mangadex::{self, MangaDexOpts};
+use super::progress::Bar;
+use super::style::{self, Tone};
+use super::upload::{self, UploadOpts};
+use super::watch::{self, WatchOpts};
+use manga_compiler::discover::PageRanges;
+use manga_compiler::exit::{self, Code};
+use manga_compiler::quality::{self, QualityReport};
+use manga_compiler::sign::Signer;
+use manga_compiler::summary::Existing;
+use manga_compiler::{
+    Book, Compiler, Compression, Config, DedupeExact, Direction, Format, Layout, MemorySize, MissingPolicy,
+    OnFilterError, Preset, Sort, Summary,
+};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Args, Debug)]
+pub struct BuildArgs {
+    #[arg(required_unless_present = "from_urls")]
+    pub folder: Option<PathBuf>,
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Print the run summary as JSON instead of text
+    #[arg(long)]
+    pub json: bool,
+    #[command(flatten)]
+    pub opts: CompileOpts,
+    #[command(flatten)]
+    pub watch: WatchOpts,
+    #[command(flatten)]
+    pub download: DownloadOpts,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct CompileOpts {
+    #[arg(short, long, value_enum)]
+    pub format: Option<Format>,
+    #[arg(short, long, value_enum)]
+    pub compression: Option<Compression>,
+    /// Whether gaps in the page numbering fail the build or only warn
+    #[arg(long, value_enum, value_name = "POLICY")]
+    pub missing: Option<MissingPolicy>,
+    /// Number the pages found without gaps instead of failing on missing ones, same as `--missing compact`
+    #[arg(long, conflicts_with = "missing")]
+    pub compact: bool,
+    /// Write archives into this folder instead of next to each source folder
+    #[arg(short = 'd', long)]
+    pub output_dir: Option<PathBuf>,
+    /// Output file name, e.g. `'{series} - Vol {volume:02}.cbz'`; variables are title, series,
+    /// volume, chapter, chapter_title, folder, parent and pages
+    #[arg(long, value_name = "TEMPLATE")]
+    pub output_template: Option<String>,
+    /// Name outputs for a media server library rooted at the output directory
+    #[arg(long, value_enum)]
+    pub layout: Option<Layout>,
+    #[arg(short, long)]
+    pub title: Option<String>,
+    #[arg(long)]
+    pub series: Option<String>,
+    #[arg(long)]
+    pub volume: Option<u32>,
+    #[arg(long)]
+    pub chapter: Option<f64>,
+    /// Chapter title for ComicInfo.xml and the `{chapter_title}` template variable
+    #[arg(long, value_name = "TITLE")]
+    pub chapter_title: Option<String>,
+    /// Books in the whole series for ComicInfo.xml, so readers can tell when it is complete
+    #[arg(long, value_name = "N")]
+    pub count: Option<u32>,
+    /// Do not read volume, chapter and chapter title from folder names
+    #[arg(long)]
+    pub no_infer: bool,
+    /// Authors, separated by commas
+    #[arg(long)]
+    pub author: Option<String>,
+    /// Filename regex; `{folder}` expands to the folder name, group 1 or `(?P<page>...)` is the page
+    /// number and a `(?P<chapter>...)` group orders pages by chapter first
+    #[arg(long)]
+    pub pattern: Option<String>,
+    /// Order pages by modification or EXIF time instead of their number, for names without one
+    #[arg(long, value_enum)]
+    pub sort: Option<Sort>,
+    /// Leave out pages byte for byte identical to an earlier one, or with `warn` only report them
+    #[arg(long, value_enum, value_name = "MODE", num_args = 0..=1, default_missing_value = "drop")]
+    pub dedupe_exact: Option<DedupeExact>,
+    /// Compile only these page numbers, e.g. `1-20,35,40-`; gaps outside them are not checked
+    #[arg(long, value_name = "RANGES")]
+    pub pages: Option<PageRanges>,
+    #[arg(long, value_enum)]
+    pub direction: Option<Direction>,
+    /// Format, screen fit, encoding and compression for a purpose, below each of them given on its own
+    #[arg(long, value_enum)]
+    pub preset: Option<Preset>,
+    /// Scale and reduce the pages for a device profile, e.g. a KCC id such as KPW or KoC
+    #[arg(long, value_name = "PROFILE")]
+    pub device: Option<String>,
+    /// Most memory preparing one page for the device may take, e.g. `512M`; pages that would
+    /// need more are stored as they are
+    #[arg(long, value_name = "SIZE")]
+    pub max_memory: Option<MemorySize>,
+    /// Pipe every page through this shell command, which reads it on stdin and prints it in the
+    /// same format on stdout, e.g. `magick - -deskew 40% -`
+    #[arg(long, value_name = "COMMAND")]
+    pub filter_cmd: Option<String>,
+    /// Pages the filter command works on at the same time, one per CPU by default
+    #[arg(long, value_name = "N")]
+    pub filter_jobs: Option<usize>,
+    /// What to do with a page the filter command fails on
+    #[arg(long, value_enum, value_name = "MODE")]
+    pub on_filter_error: Option<OnFilterError>,
+    /// Bring the black and white points of every chapter to the volume's, for volumes assembled
+    /// from chapters of different scans
+    #[arg(long)]
+    pub normalize_levels: bool,
+    /// Also write a Calibre `metadata.opf` next to the archive
+    #[arg(long)]
+    pub opf: bool,
+    /// Score each page for blur, JPEG artifacts, resolution and skew and list the worst ones
+    #[arg(long)]
+    pub quality_report: bool,
+    /// Also write the cover (a `cover.jpg` among the files, or else the first page) next to the
+    /// archive under this name, e.g. `folder.jpg` or `'{archive}.jpg'`
+    #[arg(long, value_name = "NAME")]
+    pub cover: Option<String>,
+    /// Longest side of that cover in pixels [default: 1500]
+    #[arg(long, value_name = "PX", requires = "cover")]
+    pub cover_size: Option<u32>,
+    /// Add pages missing from an existing archive to it instead of rebuilding it
+    #[arg(long)]
+    pub append: bool,
+    /// Date every entry 1980-01-01 instead of its file's modification time, so archives of the
+    /// same pages are byte for byte identical
+    #[arg(long)]
+    pub reproducible: bool,
+    /// Transliterate entry names to ASCII (romaji for kana, accents dropped) for old readers
+    #[arg(long)]
+    pub ascii_names: bool,
+    /// Folder (relative to the source folder) whose files are appended after the pages
+    #[arg(long)]
+    pub extras_dir: Option<PathBuf>,
+    /// Shell command run before writing each archive, see MANGA_COMPILER_* variables
+    #[arg(long, value_name = "COMMAND")]
+    pub pre_hook: Option<String>,
+    /// Shell command run after each archive was written
+    #[arg(long, value_name = "COMMAND")]
+    pub post_hook: Option<String>,
+    /// Write a detached signature next to each archive with minisign or gpg
+    #[arg(long, value_enum, value_name = "TOOL")]
+    pub sign: Option<Signer>,
+    /// minisign secret key file or gpg key id, instead of the tool's default key
+    #[arg(long, value_name = "KEY", requires = "sign")]
+    pub sign_key: Option<String>,
+    /// Record every compiled book in this SQLite database, created as needed; needs the
+    /// sqlite3 command line shell, 3.33 or later, on PATH
+    #[arg(long, value_name = "PATH")]
+    pub index: Option<PathBuf>,
+    #[command(flatten)]
+    pub mangadex: MangaDexOpts,
+    #[command(flatten)]
+    pub upload: UploadOpts,
+}
+
+impl CompileOpts {
+    pub fn to_config(&self) -> Config {
+        Config {
+            preset: self.preset,
+            format: self.format,
+            compression: self.compression,
+            missing: self.missing.or(self.compact.then_some(MissingPolicy::Compact)),
+            output_dir: self.output_dir.clone(),
+            output_template: self.output_template.clone(),
+            layout: self.layout,
+            title: self.title.clone(),
+            series: self.series.clone(),
+            volume: self.volume,
+            chapter: self.chapter,
+            chapter_title: self.chapter_title.clone(),
+            count: self.count,
+            infer: self.no_infer.then_some(false),
+            author: self.author.clone(),
+            pattern: self.pattern.clone(),
+            sort: self.sort,
+            pages: self.pages.clone(),
+            dedupe_exact: self.dedupe_exact,
+            page: None,
+            direction: self.direction,
+            device: self.device.clone(),
+            max_memory: self.max_memory,
+            filter_cmd: self.filter_cmd.clone(),
+            filter_jobs: self.filter_jobs,
+            on_filter_error: self.on_filter_error,
+            normalize_levels: self.normalize_levels.then_some(true),
+            opf: self.opf.then_some(true),
+            quality_report: self.quality_report.then_some(true),
+            cover: self.cover.clone(),
+            cover_size: self.cover_size,
+            reproducible: self.reproducible.then_some(true),
+            ascii_names: self.ascii_names.then_some(true),
+            extras_dir: self.extras_dir.clone(),
+            pre_hook: self.pre_hook.clone(),
+            post_hook: self.post_hook.clone(),
+            sign: self.sign,
+            sign_key: self.sign_key.clone(),
+        }
+    }
+}
+
+pub fn run(args: &BuildArgs) -> Result<()> {
+    if let Some(list) = &args.download.from_urls {
+        let summary = download::compile(list, args.output.as_deref(), &args.opts, &args.download)?;
+        report(&summary, args.json);
+        index::record(&args.opts, &summary, Config::default())?;
+        return upload::run(&args.opts.upload, &summary.output);
+    }
+    let folder = args.folder.as_ref().ok_or_else(|| exit::fail(Code::Usage, "No source folder given"))?;
+    if !args.watch.watch {
+        let summary = compile(folder, args.output.as_deref(), &args.opts)?;
+        report(&summary, args.json);
+        index::record(&args.opts, &summary, Config::default())?;
+        return upload::run(&args.opts.upload, &summary.output);
+    }
+
+    let compile_and_report = |folder: &Path| {
+        let result = compile(folder, args.output.as_deref(), &args.opts).and_then(|summary| {
+            report(&summary, args.json);
+            index::record(&args.opts, &summary, Config::default())?;
+            upload::run(&args.opts.upload, &summary.output)
+        });
+        if let Err(e) = result {
+            log::line(format_args!("Failed {}: {:#}", folder.display(), e));
+            eprintln!("{}", style::err(Tone::Error, t!("Failed {0}: {1}", folder.display(), format!("{:#}", e))));
+        }
+    };
+    compile_and_report(folder);
+    watch::run(&args.watch, || Ok(vec![folder.clone()]), compile_and_report)
+}
+
+pub fn report(summary: &Summary, json: bool) {
+    if json {
+        println!("{}", serde_json::to_string(summary).unwrap());
+    } else {
+        let created = match summary.appended {
+            Some(0) => t!("{0} already holds every page", summary.output.display()),
+            Some(added) => t!("Appended {0} pages to {1}", added, summary.output.display()),
+            None => t!("Successfully created {0}", summary.output.display()),
+        };
+        println!("{}", style::out(Tone::Success, created));
+        if let Some(signature) = &summary.signature {
+            println!("  {}", t!("Signed as {0}", signature.display()));
+        }
+        if let Some(cover) = &summary.cover {
+            println!("  {}", t!("Cover written as {0}", cover.display()));
+        }
+        match summary.existing {
+            Some(Existing::Overwritten) => println!("  {}", t!("Replaced the archive that was there")),
+            Some(Existing::Renamed) => println!("  {}", t!("Kept the archive that was there and picked a new name")),
+            _ => {}
+        }
+        println!("  {}", i18n::stats(&summary.stats));
+        if let Some(quality) = &summary.quality {
+            report_quality(quality);
+        }
+    }
+}
+
+/// The book's mean score and its worst pages with what is wrong with them.
+fn report_quality(quality: &QualityReport) {
+    let worst = quality.worst(quality::WORST);
+    if worst.is_empty() {
+        println!("  {}", t!("Quality {0}/100, no page with issues", quality.score()));
+        return;
+    }
+    println!("  {}", t!("Quality {0}/100, worst pages:", quality.score()));
+    for page in worst {
+        let issues: Vec<String> = page.issues.iter().map(|issue| i18n::text(issue, &[])).collect();
+        let tone = if page.score < 50 { Tone::Error } else { Tone::Warning };
+        let measures = t!(
+            "sharpness {0}, blockiness {1}, skew {2}°, {3}x{4}",
+            format!("{:.0}", page.sharpness),
+            format!("{:.2}", page.blockiness),
+            page.skew,
+            page.width,
+            page.height
+        );
+        let line = format!("{:>3}  {}: {}", page.score, page.name, issues.join(", "));
+        println!("    {} {}", style::out(tone, line), style::out(Tone::Dim, format!("({})", measures)));
+    }
+}
+
+/// Compiles one folder with the command line settings, showing warnings and progress on stderr.
+pub fn compile(folder: &Path, output: Option<&Path>, opts: &CompileOpts) -> Result<Summary> {
+    compile_inheriting(folder, output, opts, Config::default())
+}
+
+/// Like [`compile`], with `inherited` settings below those of the folder's config.
+pub fn compile_inheriting(
+    folder: &Path,
+    output: Option<&Path>,
+    opts: &CompileOpts,
+    inherited: Config,
+) -> Result<Summary> {
+    let (compiler, book) = discover(folder, output, opts, inherited)?;
+    compile_book(&compiler, &book)
+}
+
+/// The first half of [`compile_inheriting`]: finds the pages, printing their order when sorted by time.
+pub fn discover(
+    folder: &Path,
+    output: Option<&Path>,
+    opts: &CompileOpts,
+    inherited: Config,
+) -> Result<(Compiler, Book)> {
+    let mut compiler = Compiler::new(folder).config(opts.to_config()).inherit(inherited).append(opts.append);
+    if opts.mangadex.mangadex.is_some() {
+        let mut config = compiler.effective_config()?;
+        mangadex::apply(&opts.mangadex, folder, &mut config)?;
+        compiler = compiler.config(config);
+    }
+    if let Some(output) = output {
+        compiler = compiler.output(output);
+    }
+    let book = compiler.discover()?;
+    if book.config.sort.is_some_and(|s| s != Sort::Number) {
+        preview_order(&book);
+    }
+    log::book(&book);
+    Ok((compiler, book))
+}
+
+pub fn compile_book(compiler: &Compiler, book: &Book) -> Result<Summary> {
+    Ok(compiler.compile_with(book, &mut Bar::new())?)
+}
+
+/// Lists the pages with the time that put them in order, on stderr to keep `--json` intact.
+fn preview_order(book: &Book) {
+    eprintln!("{}", style::err(Tone::Dim, t!("Page order of {0}:", book.folder.display())));
+    for page in &book.pages {
+        let name = page.path.file_name().unwrap_or_default().to_string_lossy();
+        let time = page.time.as_deref().unwrap_or("unknown time");
+        eprintln!("{:>5}  {:<19}  {}", page.number, time, name);
+    }
+}
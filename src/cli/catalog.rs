@@ -0,0 +1,281 @@
+use super::style::{self, Tone};
+use manga_compiler::{archive, compiler};
+use manga_compiler::comicinfo::{self, ComicInfo, escape};
+use anyhow::{Context, Result, anyhow};
+use image::ImageFormat;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    io::{Cursor, Read},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use walkdir::WalkDir;
+
+const ROOT_FEED: &str = "catalog.xml";
+const FEED_DIR: &str = "opds";
+const ACQUISITION: &str = "application/atom+xml;profile=opds-catalog;kind=acquisition";
+const NAVIGATION: &str = "application/atom+xml;profile=opds-catalog;kind=navigation";
+
+#[derive(clap::Args, Debug)]
+pub struct CatalogArgs {
+    /// Library folder holding the compiled archives, searched recursively
+    pub dir: PathBuf,
+    /// Title of the catalog, defaults to the folder name
+    #[arg(long)]
+    pub title: Option<String>,
+    /// Longest side of the cover thumbnails in pixels
+    #[arg(long, default_value_t = 300, value_name = "PX")]
+    pub thumbnail_size: u32,
+}
+
+struct Entry {
+    /// Path relative to the library root, with `/` separators.
+    href: String,
+    title: String,
+    author: Option<String>,
+    updated: SystemTime,
+    cover: Option<String>,
+    thumbnail: Option<String>,
+}
+
+/// Writes `catalog.xml` (a navigation feed with one entry per series folder) into the library,
+/// and an acquisition feed plus the extracted covers per series into `opds/`. Every link is
+/// relative, so the folder can be served as is by any static file server.
+pub fn run(args: &CatalogArgs) -> Result<()> {
+    let root = &args.dir;
+    let title = match &args.title {
+        Some(t) => t.clone(),
+        None => folder_name(root)?,
+    };
+    let covers = root.join(FEED_DIR).join("covers");
+    fs::create_dir_all(&covers).with_context(|| format!("Failed to create {}", covers.display()))?;
+
+    let mut series: BTreeMap<String, Vec<Entry>> = BTreeMap::new();
+    let walk = WalkDir::new(root)
+        .sort_by(|a, b| archive::natural_cmp(&a.file_name().to_string_lossy(), &b.file_name().to_string_lossy()));
+    for item in walk {
+        let item = item.with_context(|| format!("Failed to read {}", root.display()))?;
+        let path = item.path();
+        if !item.file_type().is_file() || !path.extension().is_some_and(|e| e.eq_ignore_ascii_case("cbz")) {
+            continue;
+        }
+        let rel = path.strip_prefix(root).unwrap();
+        let group = rel.parent().map(slashed).unwrap_or_default();
+        match read_entry(path, rel, &covers, args.thumbnail_size) {
+            Ok(entry) => series.entry(group).or_default().push(entry),
+            Err(e) => eprintln!("{} skipping {}: {:#}", style::err(Tone::Warning, "Warning:"), path.display(), e),
+        }
+    }
+
+    let now = SystemTime::now();
+    let mut slugs = HashSet::new();
+    let mut nav = feed_head("urn:manga-compiler:catalog", &title, now, ROOT_FEED, NAVIGATION, ROOT_FEED);
+    for (group, entries) in &series {
+        let name = if group.is_empty() { title.clone() } else { group.clone() };
+        let slug = unique_slug(&name, &mut slugs);
+        let href = format!("{}/{}.xml", FEED_DIR, slug);
+        let updated = entries.iter().map(|e| e.updated).max().unwrap_or(now);
+
+        let start = format!("../{}", ROOT_FEED);
+        let mut feed = feed_head(&urn("series", group), &name, updated, &format!("{}.xml", slug), ACQUISITION, &start);
+        feed.push_str(&format!("  <link rel=\"up\" href=\"{}\" type=\"{}\"/>\n", start, NAVIGATION));
+        for entry in entries {
+            feed.push_str(&book_entry(entry));
+        }
+        feed.push_str("</feed>\n");
+        let path = root.join(&href);
+        fs::write(&path, feed).with_context(|| format!("Failed to write {}", path.display()))?;
+
+        nav.push_str(&format!(
+            "  <entry>\n    <title>{}</title>\n    <id>{}</id>\n    <updated>{}</updated>\n    \
+             <content type=\"text\">{} book{}</content>\n    \
+             <link rel=\"subsection\" href=\"{}\" type=\"{}\"/>\n  </entry>\n",
+            escape(&name),
+            urn("series", group),
+            timestamp(updated),
+            entries.len(),
+            if entries.len() == 1 { "" } else { "s" },
+            href_escape(&href),
+            ACQUISITION,
+        ));
+    }
+    nav.push_str("</feed>\n");
+    let path = root.join(ROOT_FEED);
+    fs::write(&path, nav).with_context(|| format!("Failed to write {}", path.display()))?;
+
+    let books: usize = series.values().map(Vec::len).sum();
+    println!(
+        "{} {} with {} books in {} series",
+        style::out(Tone::Success, "Wrote"),
+        path.display(),
+        books,
+        series.len()
+    );
+    Ok(())
+}
+
+fn read_entry(path: &Path, rel: &Path, covers: &Path, size: u32) -> Result<Entry> {
+    let href = slashed(rel);
+    let updated = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut zip = archive::open(path)?;
+
+    let info = match zip.by_name("ComicInfo.xml") {
+        Ok(mut file) => {
+            let mut xml = String::new();
+            file.read_to_string(&mut xml).context("Failed to read ComicInfo.xml")?;
+            ComicInfo::parse(&xml)
+        }
+        Err(_) => ComicInfo::default(),
+    };
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+
+    let mut pages: Vec<String> = zip
+        .file_names()
+        .filter(|n| archive::is_image(n) && !archive::is_junk(n))
+        .map(String::from)
+        .collect();
+    pages.sort_by(|a, b| archive::natural_cmp(a, b));
+    let (mut cover, mut thumbnail) = (None, None);
+    if let Some(first) = pages.first() {
+        let ext = Path::new(first).extension().unwrap_or_default().to_string_lossy().to_lowercase();
+        let name = slug(&slashed(&rel.with_extension("")));
+        let (full, thumb) = (format!("{}.{}", name, ext), format!("{}.thumb.jpg", name));
+        let fresh = |file: &str| {
+            fs::metadata(covers.join(file)).and_then(|m| m.modified()).is_ok_and(|t| t >= updated)
+        };
+        if !fresh(&full) {
+            let mut data = Vec::new();
+            zip.by_name(first)?.read_to_end(&mut data).with_context(|| format!("Failed to read {}", first))?;
+            let full_path = covers.join(&full);
+            fs::write(&full_path, &data).with_context(|| format!("Failed to write {}", full_path.display()))?;
+            let _ = fs::remove_file(covers.join(&thumb));
+            if let Err(e) = write_thumbnail(&data, &covers.join(&thumb), size) {
+                eprintln!("{} no thumbnail for {}: {:#}", style::err(Tone::Warning, "Warning:"), href, e);
+            }
+        }
+        if fresh(&thumb) {
+            thumbnail = Some(format!("covers/{}", thumb));
+        }
+        cover = Some(format!("covers/{}", full));
+    }
+
+    Ok(Entry { title: info.title.unwrap_or(stem), author: info.writer, updated, cover, thumbnail, href })
+}
+
+fn write_thumbnail(data: &[u8], path: &Path, size: u32) -> Result<()> {
+    let mut jpeg = Cursor::new(Vec::new());
+    image::load_from_memory(data)
+        .map_err(|e| anyhow!("Failed to decode the cover: {}", e.to_string().trim_end()))?
+        .thumbnail(size, size)
+        .to_rgb8()
+        .write_to(&mut jpeg, ImageFormat::Jpeg)?;
+    fs::write(path, jpeg.into_inner()).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn feed_head(id: &str, title: &str, updated: SystemTime, slf: &str, kind: &str, start: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <feed xmlns=\"http://www.w3.org/2005/Atom\" xmlns:opds=\"http://opds-spec.org/2010/catalog\">\n  \
+         <id>{}</id>\n  <title>{}</title>\n  <updated>{}</updated>\n  \
+         <author><name>manga-compiler</name></author>\n  \
+         <link rel=\"self\" href=\"{}\" type=\"{}\"/>\n  \
+         <link rel=\"start\" href=\"{}\" type=\"{}\"/>\n",
+        id,
+        escape(title),
+        timestamp(updated),
+        href_escape(slf),
+        kind,
+        href_escape(start),
+        NAVIGATION,
+    )
+}
+
+fn book_entry(entry: &Entry) -> String {
+    let mut xml = format!(
+        "  <entry>\n    <title>{}</title>\n    <id>{}</id>\n    <updated>{}</updated>\n",
+        escape(&entry.title),
+        urn("book", &entry.href),
+        timestamp(entry.updated),
+    );
+    for author in entry.author.as_deref().into_iter().flat_map(comicinfo::authors) {
+        xml.push_str(&format!("    <author><name>{}</name></author>\n", escape(author)));
+    }
+    if let Some(cover) = &entry.cover {
+        let kind = if cover.ends_with(".png") { "image/png" } else { "image/jpeg" };
+        xml.push_str(&format!(
+            "    <link rel=\"http://opds-spec.org/image\" href=\"{}\" type=\"{}\"/>\n",
+            href_escape(cover),
+            kind
+        ));
+    }
+    if let Some(thumb) = &entry.thumbnail {
+        xml.push_str(&format!(
+            "    <link rel=\"http://opds-spec.org/image/thumbnail\" href=\"{}\" type=\"image/jpeg\"/>\n",
+            href_escape(thumb)
+        ));
+    }
+    xml.push_str(&format!(
+        "    <link rel=\"http://opds-spec.org/acquisition\" href=\"../{}\" type=\"application/vnd.comicbook+zip\"/>\n  \
+         </entry>\n",
+        href_escape(&entry.href),
+    ));
+    xml
+}
+
+fn folder_name(dir: &Path) -> Result<String> {
+    let dir = dir.canonicalize().with_context(|| format!("Failed to read {}", dir.display()))?;
+    Ok(dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| "Library".to_string()))
+}
+
+fn slashed(path: &Path) -> String {
+    path.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")
+}
+
+fn urn(kind: &str, rel: &str) -> String {
+    format!("urn:manga-compiler:{}:/{}", kind, href_escape(rel))
+}
+
+fn slug(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() { "library".to_string() } else { slug.to_string() }
+}
+
+fn unique_slug(name: &str, taken: &mut HashSet<String>) -> String {
+    let base = slug(name);
+    let mut candidate = base.clone();
+    let mut n = 2;
+    while !taken.insert(candidate.clone()) {
+        candidate = format!("{}-{}", base, n);
+        n += 1;
+    }
+    candidate
+}
+
+/// Percent-encodes everything but unreserved characters and `/`, then escapes for XML.
+fn href_escape(path: &str) -> String {
+    let mut out = String::new();
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => out.push(b as char),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+/// RFC 3339 in UTC, as Atom requires.
+fn timestamp(time: SystemTime) -> String {
+    let time = compiler::timestamp(time).unwrap_or_else(|| "1970-01-01 00:00:00".to_string());
+    time.replace(' ', "T") + "Z"
+}
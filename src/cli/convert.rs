@@ -0,0 +1,145 @@
+use super::i18n::t;
+use super::log;
+use super::meta;
+use super::style::{self, Tone};
+use manga_compiler::comicinfo::ComicInfo;
+use manga_compiler::compiler::timestamp;
+use manga_compiler::exit::{self, Code, WithCode};
+use manga_compiler::ocr::{Recognizer, Tesseract};
+use manga_compiler::{archive, epub, paths, pdf};
+use anyhow::{Context, Result, anyhow};
+use clap::ValueEnum;
+use std::{
+    fs::{self, File},
+    io::{BufWriter, ErrorKind, Read},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    /// Fixed-layout EPUB 3
+    Epub,
+    /// EPUB with Kobo's markup, named `.kepub.epub`
+    Kepub,
+    /// One image per page
+    Pdf,
+}
+
+impl Target {
+    fn extension(self) -> &'static str {
+        match self {
+            Target::Epub => "epub",
+            Target::Kepub => "kepub.epub",
+            Target::Pdf => "pdf",
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ConvertArgs {
+    /// Compiled archives to convert
+    #[arg(required = true)]
+    pub archives: Vec<PathBuf>,
+    /// Format to write
+    #[arg(long, value_enum)]
+    pub to: Target,
+    /// Folder to write the books into instead of next to each archive
+    #[arg(short = 'd', long, value_name = "DIR")]
+    pub output_dir: Option<PathBuf>,
+    /// Recognize the text of each page with tesseract, which must be installed, and lay it
+    /// invisibly over the page so the PDF can be searched
+    #[arg(long)]
+    pub ocr: bool,
+    /// Tesseract languages to recognize, e.g. `jpn+eng`
+    #[arg(long, value_name = "LANGS", requires = "ocr")]
+    pub ocr_lang: Option<String>,
+}
+
+pub fn run(args: &ConvertArgs) -> Result<()> {
+    if args.ocr && args.to != Target::Pdf {
+        return Err(exit::fail(Code::Usage, "--ocr needs --to pdf"));
+    }
+    if let [archive] = args.archives.as_slice() {
+        let output = convert(archive, args)?;
+        println!("{}", style::out(Tone::Success, t!("Converted {0} into {1}", archive.display(), output.display())));
+        return Ok(());
+    }
+    let mut failed = 0;
+    for archive in &args.archives {
+        match convert(archive, args) {
+            Ok(output) => {
+                let converted = t!("Converted {0} into {1}", archive.display(), output.display());
+                println!("{}", style::out(Tone::Success, converted));
+            }
+            Err(e) => {
+                failed += 1;
+                log::line(format_args!("Failed {}: {:#}", archive.display(), e));
+                println!("{}", style::out(Tone::Error, t!("Failed {0}: {1}", archive.display(), format!("{:#}", e))));
+            }
+        }
+    }
+    if failed > 0 {
+        let message = format!("{} of {} archives failed to convert", failed, args.archives.len());
+        return Err(exit::fail(Code::Batch, message));
+    }
+    Ok(())
+}
+
+/// Writes the pages of `archive` in reading order with the metadata of its ComicInfo.xml, the
+/// title falling back to the archive's name, and returns the path written.
+fn convert(archive: &Path, args: &ConvertArgs) -> Result<PathBuf> {
+    let mut zip = archive::open(archive)?;
+    let mut pages = Vec::new();
+    for name in archive::page_names(&zip) {
+        let mut data = Vec::new();
+        zip.by_name(&name)?
+            .read_to_end(&mut data)
+            .with_context(|| format!("Failed to read {} in {}", name, archive.display()))?;
+        pages.push((name, data));
+    }
+    drop(zip);
+    if pages.is_empty() {
+        return Err(exit::fail(Code::NoPages, format!("{} contains no pages", archive.display())));
+    }
+    let stem = archive.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+    let mut info = meta::read_comicinfo(archive)?.map(|xml| ComicInfo::parse(&xml)).unwrap_or_default();
+    info.title = info.title.or_else(|| Some(stem.clone()));
+
+    let mut text = Vec::new();
+    if args.ocr {
+        let tesseract = Tesseract { languages: args.ocr_lang.clone() };
+        for (name, data) in &pages {
+            let words = tesseract.words(name, data).with_context(|| format!("Failed to OCR {}", archive.display()))?;
+            text.push(words);
+        }
+    }
+
+    let dir = args.output_dir.as_deref().or(archive.parent()).unwrap_or(Path::new(""));
+    let output = dir.join(paths::file_name(&format!("{}.{}", stem, args.to.extension())));
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display())).code(Code::Write)?;
+    paths::write_atomic(&output, |part| {
+        let file = File::create(paths::extended(part))
+            .with_context(|| format!("Failed to create {}", part.display()))
+            .code(Code::Write)?;
+        let sink = BufWriter::new(file);
+        let written = match args.to {
+            Target::Epub | Target::Kepub => {
+                let modified = timestamp(SystemTime::now()).unwrap_or_default().replace(' ', "T") + "Z";
+                epub::write(sink, &pages, &info, &modified, args.to == Target::Kepub)
+            }
+            Target::Pdf => pdf::write(sink, &pages, &info, &text),
+        };
+        let file = match written {
+            // A page the format cannot take.
+            Err(e) if e.kind() == ErrorKind::InvalidData => {
+                return Err(anyhow!(e).context(format!("Failed to convert {}", archive.display())));
+            }
+            written => written.and_then(|w| w.into_inner().map_err(|e| e.into_error())),
+        };
+        let synced = file.and_then(|f| f.sync_all());
+        synced.with_context(|| format!("Failed to write {}", part.display())).code(Code::Write)
+    })?;
+    log::line(format_args!("Converted {} into {}", archive.display(), output.display()));
+    Ok(output)
+}
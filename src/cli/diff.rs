@@ -0,0 +1,219 @@
+use super::index::Index;
+use super::style::{self, Tone};
+use manga_compiler::archive;
+use manga_compiler::exit::{self, Code};
+use anyhow::{Context, Result, anyhow};
+use image::imageops::FilterType;
+use serde::Serialize;
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+#[derive(clap::Args, Debug)]
+pub struct DiffArgs {
+    /// The earlier archive, or with --index the archive compared with what the index recorded of it
+    pub old: PathBuf,
+    /// The archive compared with it
+    #[arg(required_unless_present = "index")]
+    pub new: Option<PathBuf>,
+    /// Compare the archive with its pages as this SQLite index recorded them; needs the sqlite3
+    /// command line shell, 3.33 or later, on PATH
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["new", "perceptual"])]
+    pub index: Option<PathBuf>,
+    /// Compare what the pages look like instead of their bytes, so re-encoded pages still match
+    #[arg(long)]
+    pub perceptual: bool,
+    /// Most bits two perceptual hashes may differ in for the pages to count as the same
+    #[arg(long, value_name = "BITS", default_value_t = 6, requires = "perceptual")]
+    pub threshold: u32,
+    /// Print the differences as JSON instead of text
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// A page of one archive, by entry name and 1-based position.
+#[derive(Serialize, Debug, Clone)]
+struct PageRef {
+    name: String,
+    page: usize,
+}
+
+#[derive(Serialize, Debug, Default)]
+struct Diff {
+    unchanged: usize,
+    /// Pages of the same name, or else at the same position, whose content differs.
+    changed: Vec<(PageRef, PageRef)>,
+    /// Pages found in both archives whose place in the reading order differs.
+    moved: Vec<(PageRef, PageRef)>,
+    added: Vec<PageRef>,
+    removed: Vec<PageRef>,
+}
+
+struct Page {
+    name: String,
+    /// CRC-32 and size, or the perceptual hash.
+    key: u64,
+}
+
+pub fn run(args: &DiffArgs) -> Result<()> {
+    let (old, new, old_label, new_label) = match (&args.index, &args.new) {
+        (Some(db), _) => (
+            indexed(db, &args.old)?,
+            pages(&args.old, false)?,
+            format!("{} in {}", args.old.display(), db.display()),
+            args.old.display().to_string(),
+        ),
+        (None, Some(new)) => (
+            pages(&args.old, args.perceptual)?,
+            pages(new, args.perceptual)?,
+            args.old.display().to_string(),
+            new.display().to_string(),
+        ),
+        (None, None) => return Err(exit::fail(Code::Usage, "No archive to compare with given")),
+    };
+    let same = |a: &Page, b: &Page| {
+        if args.perceptual { (a.key ^ b.key).count_ones() <= args.threshold } else { a.key == b.key }
+    };
+    let page_ref = |pages: &[Page], i: usize| PageRef { name: pages[i].name.clone(), page: i + 1 };
+
+    // Each new page takes the first old page left with the same content; the others were changed
+    // if an old page left has their name, or else their position.
+    let mut old_of: Vec<Option<usize>> = vec![None; new.len()];
+    let mut taken = vec![false; old.len()];
+    for (j, page) in new.iter().enumerate() {
+        if let Some(i) = (0..old.len()).find(|&i| !taken[i] && same(&old[i], page)) {
+            taken[i] = true;
+            old_of[j] = Some(i);
+        }
+    }
+    let mut diff = Diff::default();
+    let mut unmatched_new: Vec<usize> = (0..new.len()).filter(|&j| old_of[j].is_none()).collect();
+    for by_name in [true, false] {
+        unmatched_new.retain(|&j| {
+            let counterpart = |i: usize| if by_name { old[i].name == new[j].name } else { i == j };
+            let found = (0..old.len()).find(|&i| !taken[i] && counterpart(i));
+            if let Some(i) = found {
+                taken[i] = true;
+                diff.changed.push((page_ref(&old, i), page_ref(&new, j)));
+            }
+            found.is_none()
+        });
+    }
+    diff.changed.sort_by_key(|(_, b)| b.page);
+    diff.added = unmatched_new.into_iter().map(|j| page_ref(&new, j)).collect();
+    diff.removed = (0..old.len()).filter(|&i| !taken[i]).map(|i| page_ref(&old, i)).collect();
+
+    // Pages outside the longest run that kept its order are the ones that moved.
+    let matched: Vec<(usize, usize)> = old_of.iter().enumerate().filter_map(|(j, i)| Some(((*i)?, j))).collect();
+    let kept = longest_increasing(&matched.iter().map(|&(i, _)| i).collect::<Vec<_>>());
+    for (index, &(i, j)) in matched.iter().enumerate() {
+        if kept.contains(&index) {
+            diff.unchanged += 1;
+        } else {
+            diff.moved.push((page_ref(&old, i), page_ref(&new, j)));
+        }
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string(&diff).unwrap());
+        return Ok(());
+    }
+    println!("{}: {} pages, {}: {} pages", old_label, old.len(), new_label, new.len());
+    for (a, b) in &diff.changed {
+        println!("{}", style::out(Tone::Warning, format!("~ changed  {}", pair(a, b))));
+    }
+    for (a, b) in &diff.moved {
+        println!("{}", style::out(Tone::Dim, format!("> moved    {}", pair(a, b))));
+    }
+    for page in &diff.added {
+        println!("{}", style::out(Tone::Success, format!("+ added    {} (page {})", page.name, page.page)));
+    }
+    for page in &diff.removed {
+        println!("{}", style::out(Tone::Error, format!("- removed  {} (page {})", page.name, page.page)));
+    }
+    println!(
+        "{} unchanged, {} changed, {} moved, {} added, {} removed",
+        diff.unchanged,
+        diff.changed.len(),
+        diff.moved.len(),
+        diff.added.len(),
+        diff.removed.len()
+    );
+    Ok(())
+}
+
+/// `a.jpg -> b.jpg (page 3 -> 4)`, leaving out whichever stayed the same.
+fn pair(a: &PageRef, b: &PageRef) -> String {
+    let name = if a.name == b.name { a.name.clone() } else { format!("{} -> {}", a.name, b.name) };
+    if a.page == b.page {
+        format!("{} (page {})", name, a.page)
+    } else {
+        format!("{} (page {} -> {})", name, a.page, b.page)
+    }
+}
+
+/// The image entries of an archive in reading order, keyed by their content.
+fn pages(path: &Path, perceptual: bool) -> Result<Vec<Page>> {
+    let mut zip = archive::open(path)?;
+    let mut pages = Vec::new();
+    for name in archive::page_names(&zip) {
+        let mut entry = zip.by_name(&name)?;
+        let key = if perceptual {
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).with_context(|| format!("Failed to read {} in {}", name, path.display()))?;
+            let image = image::load_from_memory(&data)
+                .with_context(|| format!("{} in {} could not be decoded", name, path.display()))?;
+            dhash(&image)
+        } else {
+            (u64::from(entry.crc32()) << 32) ^ entry.size()
+        };
+        pages.push(Page { name, key });
+    }
+    Ok(pages)
+}
+
+/// The pages of the archive at `path` as the index `db` recorded them.
+fn indexed(db: &Path, path: &Path) -> Result<Vec<Page>> {
+    let index = Index::open(db)?;
+    let record = index.book(path)?.ok_or_else(|| anyhow!("{} is not in {}", path.display(), db.display()))?;
+    let pages = index.pages(&record)?;
+    Ok(pages.into_iter().map(|p| Page { name: p.name, key: (u64::from(p.crc32) << 32) ^ p.size }).collect())
+}
+
+/// Difference hash: one bit per neighbouring pair of a 9 by 8 grayscale thumbnail, set where
+/// the left one is brighter.
+fn dhash(image: &image::DynamicImage) -> u64 {
+    let small = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash = hash << 1 | u64::from(small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0]);
+        }
+    }
+    hash
+}
+
+/// Indices into `values` of a longest strictly increasing subsequence.
+fn longest_increasing(values: &[usize]) -> Vec<usize> {
+    // `tails[k]` is the index ending the best run of length k + 1 found so far.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous = vec![None; values.len()];
+    for (index, &value) in values.iter().enumerate() {
+        let k = tails.partition_point(|&t| values[t] < value);
+        previous[index] = k.checked_sub(1).map(|k| tails[k]);
+        if k == tails.len() {
+            tails.push(index);
+        } else {
+            tails[k] = index;
+        }
+    }
+    let mut run = Vec::new();
+    let mut next = tails.last().copied();
+    while let Some(index) = next {
+        run.push(index);
+        next = previous[index];
+    }
+    run.reverse();
+    run
+}
@@ -0,0 +1,136 @@
+use super::build::{self, CompileOpts};
+use super::i18n::t;
+use super::log;
+use super::scratch::Scratch;
+use super::style::{self, Tone};
+use manga_compiler::exit::{self, Code};
+use manga_compiler::{Config, Summary};
+use anyhow::{Context, Result, anyhow};
+use image::ImageFormat;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+use ureq::Agent;
+
+/// Pages far above this are no scan but something else.
+const MAX_PAGE_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct DownloadOpts {
+    /// Download the pages listed in FILE, one image URL per line in reading order, and compile
+    /// them as a book named after FILE
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["folder", "watch"])]
+    pub from_urls: Option<PathBuf>,
+    /// Pages downloaded at the same time
+    #[arg(long, value_name = "N", default_value_t = 4, requires = "from_urls")]
+    pub jobs: usize,
+    /// Further attempts at a page that failed to download, a second apart and then longer
+    #[arg(long, value_name = "N", default_value_t = 3, requires = "from_urls")]
+    pub retries: u32,
+}
+
+/// URLs of a list file: blank lines and lines starting with `#` are left out.
+fn read_list(path: &Path) -> Result<Vec<String>> {
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let urls: Vec<String> = text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(String::from)
+        .collect();
+    if urls.is_empty() {
+        return Err(exit::fail(Code::NoPages, format!("{} lists no URLs", path.display())));
+    }
+    Ok(urls)
+}
+
+/// Downloads the pages into a temporary folder named like the list, `<name>-001.jpg` and so on,
+/// and compiles that folder. A page that could not be downloaded, or whose body is no JPEG or PNG
+/// image, leaves a gap in the numbering, which fails the build unless `--missing` allows it.
+/// Unless told otherwise the archive goes next to the list.
+pub fn compile(list: &Path, output: Option<&Path>, opts: &CompileOpts, download: &DownloadOpts) -> Result<Summary> {
+    let urls = read_list(list)?;
+    let name = list.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+    let scratch = Scratch::new("download")?;
+    let folder = scratch.path().join(&name);
+    fs::create_dir_all(&folder).with_context(|| format!("Failed to create {}", folder.display()))?;
+
+    let jobs = download.jobs.clamp(1, urls.len());
+    eprintln!("{}", t!("Downloading {0} pages from {1}, {2} at a time", urls.len(), list.display(), jobs));
+    log::line(format_args!("Downloading {} pages from {}", urls.len(), list.display()));
+    let agent = Agent::new_with_defaults();
+    let next = AtomicUsize::new(0);
+    let failed = Mutex::new(Vec::new());
+    let width = urls.len().to_string().len().max(3);
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(url) = urls.get(index) else { break };
+                    let number = index + 1;
+                    match fetch(&agent, url, download.retries) {
+                        Ok(data) => {
+                            let ext = match image::guess_format(&data) {
+                                Ok(ImageFormat::Png) => "png",
+                                Ok(ImageFormat::Jpeg) => "jpg",
+                                _ => {
+                                    let e = anyhow!("the server sent no JPEG or PNG image");
+                                    failed.lock().unwrap().push((number, url, e));
+                                    continue;
+                                }
+                            };
+                            let path = folder.join(format!("{}-{:0width$}.{}", name, number, ext));
+                            if let Err(e) = fs::write(&path, data) {
+                                failed.lock().unwrap().push((number, url, anyhow!(e)));
+                            } else {
+                                log::line(format_args!("Downloaded page {} from {}", number, url));
+                            }
+                        }
+                        Err(e) => failed.lock().unwrap().push((number, url, e)),
+                    }
+                }
+            });
+        }
+    });
+    let mut failed = failed.into_inner().unwrap();
+    failed.sort_by_key(|(number, _, _)| *number);
+    for (number, url, e) in &failed {
+        log::line(format_args!("Failed to download page {} from {}: {:#}", number, url, e));
+        let message = t!("Warning: failed to download page {0} from {1}: {2}", number, url, format!("{:#}", e));
+        eprintln!("{}", style::err(Tone::Warning, message));
+    }
+
+    let (compiler, mut book) = build::discover(&folder, output, opts, Config::default())?;
+    if book.config.output_dir.is_none() {
+        book.config.output_dir = Some(list.parent().map(Path::to_path_buf).unwrap_or_default());
+    }
+    build::compile_book(&compiler, &book)
+}
+
+/// The body of `url`, trying `retries` more times after a failure the server may get over.
+fn fetch(agent: &Agent, url: &str, retries: u32) -> Result<Vec<u8>> {
+    let get = || agent.get(url).call()?.body_mut().with_config().limit(MAX_PAGE_BYTES).read_to_vec();
+    let mut attempt = 0;
+    loop {
+        match get() {
+            Ok(data) => return Ok(data),
+            // Asking again for a page that is not there or not allowed gives the same answer.
+            Err(ureq::Error::StatusCode(status)) if (400..500).contains(&status) && status != 429 => {
+                return Err(anyhow!("the server answered {}", status));
+            }
+            Err(e) if attempt >= retries => return Err(e.into()),
+            Err(_) => {
+                attempt += 1;
+                thread::sleep(Duration::from_secs(1 << (attempt - 1).min(5)));
+            }
+        }
+    }
+}
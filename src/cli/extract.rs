@@ -0,0 +1,83 @@
+use super::i18n::t;
+use super::style::{self, Tone};
+use manga_compiler::archive;
+use manga_compiler::exit::{Code, WithCode};
+use anyhow::{Context, Result};
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+use zip::ZipArchive;
+
+#[derive(clap::Args, Debug)]
+pub struct ExtractArgs {
+    pub archive: PathBuf,
+    /// Destination folder, defaults to the archive path without its extension
+    pub dir: Option<PathBuf>,
+    /// Unpack every entry under its original name instead of renumbering the pages
+    #[arg(long)]
+    pub raw: bool,
+}
+
+pub fn run(args: &ExtractArgs) -> Result<()> {
+    let dir = args.dir.clone().unwrap_or_else(|| args.archive.with_extension(""));
+    let mut zip = archive::open(&args.archive)?;
+    if args.raw {
+        zip.extract(&dir)
+            .with_context(|| format!("Failed to extract into {}", dir.display()))?;
+        println!("{}", t!("Extracted {0} entries into {1}", zip.len(), dir.display()));
+        return Ok(());
+    }
+
+    let (pages, skipped) = unpack_pages(&args.archive, &mut zip, &dir)?;
+    if !skipped.is_empty() {
+        eprintln!("{}", style::err(Tone::Warning, t!("Warning: skipped non-page entries:")));
+        for s in &skipped {
+            eprintln!("  - {}", style::err(Tone::Dim, s));
+        }
+    }
+    println!("{}", style::out(Tone::Success, t!("Extracted {0} pages into {1}", pages.len(), dir.display())));
+    Ok(())
+}
+
+/// Writes the pages of `zip` in natural order into `dir` as `<dir>-001.jpg`, ..., returning
+/// their entry names in that order and the names of the entries that are not pages.
+pub fn unpack_pages(path: &Path, zip: &mut ZipArchive<File>, dir: &Path) -> Result<(Vec<String>, Vec<String>)> {
+    let title = dir
+        .file_name()
+        .and_then(|s| s.to_str())
+        .context("Could not determine folder name as title")?
+        .to_string();
+
+    let mut pages = Vec::new();
+    let mut skipped = Vec::new();
+    for i in 0..zip.len() {
+        let entry = zip.by_index(i)?;
+        let name = entry.name().to_string();
+        if entry.is_dir() {
+            continue;
+        }
+        if archive::is_junk(&name) || !archive::is_image(&name) {
+            skipped.push(name);
+        } else {
+            pages.push((name, i));
+        }
+    }
+    if pages.is_empty() {
+        anyhow::bail!("{} contains no pages", path.display());
+    }
+    pages.sort_by(|(a, _), (b, _)| archive::natural_cmp(a, b));
+
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    for (number, (name, index)) in (1..).zip(&pages) {
+        let ext = Path::new(name).extension().and_then(|e| e.to_str()).unwrap_or("jpg");
+        let target = dir.join(archive::page_file_name(&title, number, pages.len(), ext));
+        let mut entry = zip.by_index(*index)?;
+        let mut out = File::create(&target)
+            .with_context(|| format!("Failed to create {}", target.display()))
+            .code(Code::Write)?;
+        io::copy(&mut entry, &mut out).with_context(|| format!("Failed to extract {}", name))?;
+    }
+    Ok((pages.into_iter().map(|(name, _)| name).collect(), skipped))
+}
@@ -0,0 +1,160 @@
+//! Translations of the messages the commands print. Each message is looked up by its English
+//! text, which is also what is printed when the catalog of the language lacks it. Error messages
+//! and the log file stay in English so they can be searched for and reported as they are.
+
+use manga_compiler::summary::{Stats, human_bytes};
+use clap::ValueEnum;
+use std::{fmt::Display, sync::OnceLock};
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lang {
+    /// English
+    En,
+    /// Japanese
+    Ja,
+}
+
+/// Picks `lang`, or else the language of the locale in `LC_ALL`, `LC_MESSAGES` or `LANG`.
+pub fn init(lang: Option<Lang>) {
+    let detected = || {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|key| std::env::var(key).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        if locale.starts_with("ja") { Lang::Ja } else { Lang::En }
+    };
+    let _ = LANG.set(lang.unwrap_or_else(detected));
+}
+
+/// `message` in the chosen language with `{0}`, `{1}` and so on replaced by `args`.
+pub fn text(message: &'static str, args: &[&dyn Display]) -> String {
+    let catalog = match LANG.get().copied().unwrap_or(Lang::En) {
+        Lang::En => &[][..],
+        Lang::Ja => JA,
+    };
+    let template = catalog.iter().find(|(en, _)| *en == message).map_or(message, |(_, translated)| translated);
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let argument = rest[start + 1..]
+            .split_once('}')
+            .and_then(|(n, after)| Some((args.get(n.parse::<usize>().ok()?)?, after)));
+        match argument {
+            Some((arg, after)) => {
+                out.push_str(&arg.to_string());
+                rest = after;
+            }
+            None => {
+                out.push('{');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// [`text`] with the arguments given inline, as `format!` takes them.
+macro_rules! t {
+    ($message:literal $(, $arg:expr)* $(,)?) => {
+        $crate::cli::i18n::text($message, &[$(&$arg),*])
+    };
+}
+pub(crate) use t;
+
+/// [`Stats::line`] in the chosen language.
+pub fn stats(stats: &Stats) -> String {
+    t!(
+        "{0} pages, {1} in, {2} out ({3}%), {4} skipped, {5} warnings, {6}s",
+        stats.pages,
+        human_bytes(stats.bytes_in),
+        human_bytes(stats.bytes_out),
+        format!("{:.1}", stats.ratio * 100.0),
+        stats.skipped,
+        stats.warnings,
+        format!("{:.2}", stats.elapsed_secs)
+    )
+}
+
+const JA: &[(&str, &str)] = &[
+    ("Error: {0}", "エラー: {0}"),
+    ("Warning: {0}", "警告: {0}"),
+    ("Warning: ignored files not matching pattern:", "警告: パターンに一致しないファイルを無視しました:"),
+    ("Warning: left out duplicate copies of pages:", "警告: 重複したページのコピーを除外しました:"),
+    ("Warning: pages identical to an earlier page:", "警告: 前のページと同一のページがあります:"),
+    ("{0} repeats {1}", "{0} は {1} と同じです"),
+    (
+        "Warning: stored as they are, preparing them would take more than --max-memory:",
+        "警告: 処理に --max-memory を超えるメモリが必要なため、そのまま格納しました:",
+    ),
+    ("Warning: stored unfiltered, the filter failed on:", "警告: フィルターが失敗したため、そのまま格納しました:"),
+    ("Warning: missing page numbers: {0}", "警告: 欠けているページ番号: {0}"),
+    ("Warning: skipped non-page entries:", "警告: ページ以外のエントリーをスキップしました:"),
+    ("{0}/{1} pages, {2}", "{0}/{1} ページ, {2}"),
+    (
+        "{0} pages, {1} in, {2} out ({3}%), {4} skipped, {5} warnings, {6}s",
+        "{0} ページ, 入力 {1}, 出力 {2} ({3}%), スキップ {4}, 警告 {5}, {6} 秒",
+    ),
+    ("Successfully created {0}", "{0} を作成しました"),
+    ("Appended {0} pages to {1}", "{1} に {0} ページを追加しました"),
+    ("{0} already holds every page", "{0} にはすべてのページが揃っています"),
+    ("Signed as {0}", "署名: {0}"),
+    ("Cover written as {0}", "表紙: {0}"),
+    ("Replaced the archive that was there", "既存のアーカイブを置き換えました"),
+    ("Kept the archive that was there and picked a new name", "既存のアーカイブを残し、別の名前を付けました"),
+    ("Quality {0}/100, no page with issues", "品質 {0}/100、問題のあるページはありません"),
+    ("Quality {0}/100, worst pages:", "品質 {0}/100、品質の低いページ:"),
+    ("sharpness {0}, blockiness {1}, skew {2}°, {3}x{4}", "鮮明度 {0}、ブロックノイズ {1}、傾き {2}°、{3}x{4}"),
+    ("blurry", "ぼやけ"),
+    ("JPEG artifacts", "JPEG ノイズ"),
+    ("low resolution", "低解像度"),
+    ("skewed", "傾き"),
+    ("Failed {0}: {1}", "{0} に失敗しました: {1}"),
+    ("Page order of {0}:", "{0} のページ順:"),
+    ("Skipping {0}, {1} is already verified", "{0} をスキップします。{1} は検証済みです"),
+    ("Skipping {0}, unchanged since {1} was compiled", "{0} をスキップします。{1} の作成後に変更はありません"),
+    ("Skipping {0}, {1} already exists", "{0} をスキップします。{1} は既に存在します"),
+    ("{0} of {1} books compiled", "{1} 冊中 {0} 冊を作成しました"),
+    ("{0} skipped as already verified", "検証済みのため {0} 冊をスキップしました"),
+    ("{0} skipped as their archives exist", "アーカイブが存在するため {0} 冊をスキップしました"),
+    ("{0}: {1} of {2} books", "{0}: {2} 冊中 {1} 冊"),
+    (
+        "{0} exists. [o]verwrite, [s]kip or [r]ename? (O, S or R for all books) ",
+        "{0} は既に存在します。[o] 上書き、[s] スキップ、[r] 名前を変更 (O、S、R で以降のすべての本に適用) ",
+    ),
+    ("{0}: {1} - {2} ({3} pages)", "{0}: {1} - {2} ({3} ページ)"),
+    ("{0} of {1} chapters compiled", "{1} 話中 {0} 話を作成しました"),
+    ("Folders outside every range of {0}:", "{0} のどの範囲にも含まれないフォルダー:"),
+    ("Volume {0}: {1}", "第 {0} 巻: {1}"),
+    ("Failed volume {0}: {1}", "第 {0} 巻に失敗しました: {1}"),
+    ("{0} of {1} volumes compiled", "{1} 巻中 {0} 巻を作成しました"),
+    ("Watching {0} folders for changes, press Ctrl-C to stop", "{0} 個のフォルダーを監視しています。Ctrl-C で終了します"),
+    ("Downloading {0} pages from {1}, {2} at a time", "{1} から {0} ページをダウンロードしています (同時に {2} 件)"),
+    ("Converted {0} into {1}", "{0} を {1} に変換しました"),
+    ("Extracted {0} entries into {1}", "{0} 個のエントリーを {1} に展開しました"),
+    ("Extracted {0} pages into {1}", "{0} ページを {1} に展開しました"),
+    ("{0} is already named canonically", "{0} は既に正規の名前です"),
+    ("{0} files would be renamed", "{0} 個のファイルの名前が変更されます"),
+    ("Renamed {0} files", "{0} 個のファイルの名前を変更しました"),
+    ("Updated {0} pages in {1}", "{1} の {0} ページを更新しました"),
+    ("Page {0} ({1}): {2}", "ページ {0} ({1}): {2}"),
+    ("Page {0} ({1}): {2} as {3}", "ページ {0} ({1}): {2} ({3} として)"),
+    ("Updated {0} in {1}", "{1} の {0} を更新しました"),
+    ("Verified {0}", "{0} を検証しました"),
+    ("{0} failed verification:", "{0} の検証に失敗しました:"),
+    ("{0} is available in Komga library {1}", "{0} は Komga ライブラリ {1} で利用できます"),
+    ("Syncing to {0} at {1}", "{0} ({1}) に同期しています"),
+    ("Would copy {0}", "コピー予定: {0}"),
+    ("Copied {0}", "{0} をコピーしました"),
+    ("{0} cannot open these files, skipped:", "{0} はこれらのファイルを開けないため、スキップしました:"),
+    ("Copied {0} books, {1} already on the device", "{0} 冊をコピーしました。{1} 冊は既にデバイスにあります"),
+    ("Would copy {0} books, {1} already on the device", "{0} 冊をコピーします。{1} 冊は既にデバイスにあります"),
+    (
+        "Warning: failed to download page {0} from {1}: {2}",
+        "警告: {1} からページ {0} をダウンロードできませんでした: {2}",
+    ),
+];
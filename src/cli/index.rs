@@ -0,0 +1,337 @@
+//! The library index: an SQLite database recording every book compiled with `--index`, kept
+//! through the `sqlite3` command line shell so that other tools can query it as well.
+
+use super::build::CompileOpts;
+use super::meta;
+use manga_compiler::archive;
+use manga_compiler::comicinfo::ComicInfo;
+use manga_compiler::compiler::timestamp;
+use manga_compiler::exit::{Code, WithCode};
+use manga_compiler::summary::Summary;
+use manga_compiler::{Compiler, Config};
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    env,
+    fmt::Write as _,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use walkdir::WalkDir;
+
+const SCHEMA: &str = "\
+CREATE TABLE IF NOT EXISTS books (
+    source TEXT PRIMARY KEY,
+    output TEXT NOT NULL,
+    pages INTEGER NOT NULL,
+    bytes INTEGER NOT NULL,
+    sha256 TEXT NOT NULL,
+    source_state TEXT NOT NULL,
+    title TEXT,
+    series TEXT,
+    volume INTEGER,
+    number REAL,
+    count INTEGER,
+    writer TEXT,
+    settings TEXT NOT NULL,
+    command TEXT NOT NULL,
+    source_modified TEXT,
+    output_modified INTEGER NOT NULL,
+    compiled_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS books_output ON books (output);
+CREATE TABLE IF NOT EXISTS pages (
+    source TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    crc32 INTEGER NOT NULL,
+    sha256 TEXT NOT NULL,
+    PRIMARY KEY (source, position)
+);
+";
+
+/// Flags whose value is a credential, left out of the recorded command line.
+const SECRET_FLAGS: &[&str] = &["--token"];
+
+/// The columns of a [`Record`].
+const RECORD: &str = "source, output, source_state, settings, bytes, output_modified, sha256, compiled_at";
+
+pub struct Index {
+    path: PathBuf,
+}
+
+/// What the index holds about a book for deciding whether it needs compiling again.
+#[derive(Deserialize, Debug)]
+pub struct Record {
+    pub source: PathBuf,
+    pub output: PathBuf,
+    source_state: String,
+    settings: String,
+    bytes: u64,
+    output_modified: i64,
+    pub sha256: String,
+    pub compiled_at: String,
+}
+
+/// A page as the index recorded it.
+#[derive(Deserialize, Debug)]
+pub struct IndexedPage {
+    pub name: String,
+    pub size: u64,
+    pub crc32: u32,
+}
+
+impl Index {
+    /// Opens the database at `path`, creating it and its tables as needed, once `sqlite3` is
+    /// found to be recent enough for `-json`.
+    pub fn open(path: &Path) -> Result<Index> {
+        check_sqlite()?;
+        let index = Index { path: path.to_path_buf() };
+        index.run(SCHEMA, false)?;
+        Ok(index)
+    }
+
+    /// Records the book `summary` describes, compiled with `settings`, in place of what was
+    /// recorded for its source or its archive before.
+    pub fn record(&self, summary: &Summary, settings: &str) -> Result<()> {
+        let source = absolute(&summary.source);
+        let output = absolute(&summary.output);
+        let info = meta::read_comicinfo(&summary.output)?.map(|xml| ComicInfo::parse(&xml)).unwrap_or_default();
+        let (sha256, bytes, modified) = file_state(&summary.output)
+            .with_context(|| format!("Failed to read {}", summary.output.display()))?;
+        let (source_state, source_modified) = source_state(&summary.source);
+        let (source, output) = (text(&source.to_string_lossy()), text(&output.to_string_lossy()));
+
+        let mut sql = String::from("BEGIN;\n");
+        let _ = writeln!(
+            sql,
+            "DELETE FROM pages WHERE source = {0} OR source IN (SELECT source FROM books WHERE output = {1});",
+            source, output
+        );
+        let _ = writeln!(sql, "DELETE FROM books WHERE source = {} OR output = {};", source, output);
+        let _ = writeln!(
+            sql,
+            "INSERT INTO books VALUES ({}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {});",
+            source,
+            output,
+            summary.stats.pages,
+            bytes,
+            text(&sha256),
+            text(&source_state),
+            optional(info.title.as_deref().map(text)),
+            optional(info.series.as_deref().map(text)),
+            optional(info.volume.map(|v| v.to_string())),
+            optional(info.number.map(|n| n.to_string())),
+            optional(info.count.map(|c| c.to_string())),
+            optional(info.writer.as_deref().map(text)),
+            text(settings),
+            text(&command()),
+            optional(source_modified.map(|m| text(&m))),
+            modified,
+            text(&timestamp(SystemTime::now()).unwrap_or_default()),
+        );
+        let mut zip = archive::open(&summary.output)?;
+        for (position, name) in (1..).zip(archive::page_names(&zip)) {
+            let mut entry = zip.by_name(&name)?;
+            let (size, crc32) = (entry.size(), entry.crc32());
+            let mut hasher = Sha256::new();
+            io::copy(&mut entry, &mut hasher)
+                .with_context(|| format!("Failed to read {} in {}", name, summary.output.display()))?;
+            let _ = writeln!(
+                sql,
+                "INSERT INTO pages VALUES ({}, {}, {}, {}, {}, {});",
+                source,
+                position,
+                text(&name),
+                size,
+                crc32,
+                text(&hex(&hasher.finalize()))
+            );
+        }
+        sql.push_str("COMMIT;\n");
+        self.run(&sql, false)?;
+        Ok(())
+    }
+
+    /// Every book recorded, by source folder.
+    pub fn records(&self) -> Result<HashMap<PathBuf, Record>> {
+        let records: Vec<Record> = self.query(&format!("SELECT {} FROM books;", RECORD))?;
+        Ok(records.into_iter().map(|r| (r.source.clone(), r)).collect())
+    }
+
+    /// The record of the book compiled into `output`, if any.
+    pub fn book(&self, output: &Path) -> Result<Option<Record>> {
+        let output = text(&absolute(output).to_string_lossy());
+        Ok(self.query::<Record>(&format!("SELECT {} FROM books WHERE output = {};", RECORD, output))?.pop())
+    }
+
+    /// The pages recorded for the book of `record`, in reading order.
+    pub fn pages(&self, record: &Record) -> Result<Vec<IndexedPage>> {
+        let source = text(&record.source.to_string_lossy());
+        self.query(&format!("SELECT name, size, crc32 FROM pages WHERE source = {} ORDER BY position;", source))
+    }
+
+    fn query<T: for<'de> Deserialize<'de>>(&self, sql: &str) -> Result<Vec<T>> {
+        let out = self.run(sql, true)?;
+        // No rows print nothing at all.
+        if out.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        let unexpected = || format!("Unexpected answer from sqlite3 for {}", self.path.display());
+        serde_json::from_str(&out).with_context(unexpected)
+    }
+
+    /// Runs `sql` with `sqlite3`, with `json` printing the rows as a JSON array, and returns
+    /// what it printed.
+    fn run(&self, sql: &str, json: bool) -> Result<String> {
+        let mut cmd = Command::new("sqlite3");
+        cmd.arg("-bail");
+        if json {
+            cmd.arg("-json");
+        }
+        cmd.arg(&self.path).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        let mut child = cmd.spawn().context("Failed to run sqlite3, which --index needs").code(Code::Write)?;
+        let written = child.stdin.take().map(|mut stdin| stdin.write_all(sql.as_bytes()));
+        let output = child.wait_with_output()?;
+        let failed = !output.status.success() || written.is_some_and(|w| w.is_err());
+        if failed {
+            let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(anyhow!("sqlite3 failed on {}: {}", self.path.display(), message)).code(Code::Write);
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Fails unless `sqlite3` runs and is 3.33 or later.
+fn check_sqlite() -> Result<()> {
+    let missing = "--index needs the sqlite3 command line shell, 3.33 or later, on PATH";
+    let output = Command::new("sqlite3").arg("-version").output().context(missing).code(Code::Write)?;
+    let version = String::from_utf8_lossy(&output.stdout);
+    let number = version.split_whitespace().next().unwrap_or_default();
+    let parts: Vec<u32> = number.split('.').map_while(|p| p.parse().ok()).collect();
+    match parts.as_slice() {
+        [major, minor, ..] if output.status.success() && (*major, *minor) >= (3, 33) => Ok(()),
+        _ => Err(anyhow!("{}, found {:?}", missing, version.trim())).code(Code::Write),
+    }
+}
+
+impl Record {
+    /// Whether compiling `source` with `settings` again would give the same book as recorded:
+    /// its files and the settings are as they were, and its archive is still the one written.
+    pub fn unchanged(&self, source: &Path, settings: &str) -> bool {
+        let current = fs::metadata(&self.output).ok().map(|m| (m.len(), modified_nanos(&m)));
+        self.source_state == source_state(source).0
+            && self.settings == settings
+            && current == Some((self.bytes, self.output_modified))
+    }
+}
+
+/// Records the book of `summary`, compiled with `opts` over `inherited`, in the index `opts`
+/// name, if any.
+pub fn record(opts: &CompileOpts, summary: &Summary, inherited: Config) -> Result<()> {
+    match &opts.index {
+        Some(path) => Index::open(path)?.record(summary, &settings(&summary.source, opts, inherited)?),
+        None => Ok(()),
+    }
+}
+
+/// `path` made absolute without resolving links, so that the same book gets the same key from
+/// any working directory.
+pub fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// The settings that decide what the book of `source` becomes, as JSON: `opts` layered over its
+/// config files, `inherited`, the global config and the environment.
+pub fn settings(source: &Path, opts: &CompileOpts, inherited: Config) -> Result<String> {
+    let config = Compiler::new(source).config(opts.to_config()).inherit(inherited).effective_config()?;
+    let mut value = serde_json::to_value(&config)?;
+    // Only what is set, keyed as in a config file.
+    if let Some(keys) = value.as_object_mut() {
+        keys.retain(|_, v| !v.is_null());
+    }
+    Ok(value.to_string())
+}
+
+/// The command line, with credentials replaced by `***`; so are the values of the
+/// `MANGA_COMPILER_*_TOKEN` variables wherever they were passed.
+fn command() -> String {
+    let secrets: Vec<String> = env::vars()
+        .filter(|(key, value)| key.starts_with("MANGA_COMPILER_") && key.ends_with("_TOKEN") && !value.is_empty())
+        .map(|(_, value)| value)
+        .collect();
+    let mut args = Vec::new();
+    let mut hide = false;
+    for arg in env::args() {
+        let flag = SECRET_FLAGS.iter().find(|flag| arg.strip_prefix(**flag).is_some_and(|rest| rest.starts_with('=')));
+        args.push(match flag {
+            Some(flag) => format!("{}=***", flag),
+            None if hide || secrets.iter().any(|secret| arg.contains(secret.as_str())) => "***".to_string(),
+            None => arg.clone(),
+        });
+        hide = SECRET_FLAGS.contains(&arg.as_str());
+    }
+    args.join(" ")
+}
+
+/// A digest of the names, sizes and modification times of the files below `source`, config
+/// files included, and the newest of those times.
+fn source_state(source: &Path) -> (String, Option<String>) {
+    let mut files: Vec<(String, u64, i64)> = WalkDir::new(source)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let meta = e.metadata().ok()?;
+            let name = e.path().strip_prefix(source).ok()?.to_string_lossy().into_owned();
+            Some((name, meta.len(), modified_nanos(&meta)))
+        })
+        .collect();
+    files.sort();
+    let mut hasher = Sha256::new();
+    for (name, size, modified) in &files {
+        hasher.update(format!("{}\0{}\0{}\n", name, size, modified).as_bytes());
+    }
+    let newest = files.iter().map(|f| f.2).max().and_then(|nanos| {
+        let time = UNIX_EPOCH + std::time::Duration::from_nanos(u64::try_from(nanos).ok()?);
+        timestamp(time)
+    });
+    (hex(&hasher.finalize()), newest)
+}
+
+/// SHA-256, size and modification time of a file.
+fn file_state(path: &Path) -> io::Result<(String, u64, i64)> {
+    let mut file = File::open(path)?;
+    let meta = file.metadata()?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok((hex(&hasher.finalize()), meta.len(), modified_nanos(&meta)))
+}
+
+pub fn sha256_of(path: &Path) -> io::Result<String> {
+    file_state(path).map(|(hash, _, _)| hash)
+}
+
+fn modified_nanos(meta: &fs::Metadata) -> i64 {
+    let since = meta.modified().ok().and_then(|m| m.duration_since(UNIX_EPOCH).ok());
+    since.map_or(0, |d| d.as_nanos() as i64)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// An SQL string literal.
+fn text(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn optional(value: Option<String>) -> String {
+    value.unwrap_or_else(|| "NULL".to_string())
+}
@@ -0,0 +1,185 @@
+use super::meta;
+use super::style::{self, Tone};
+use manga_compiler::archive;
+use manga_compiler::comicinfo::ComicInfo;
+use manga_compiler::summary::human_bytes;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{
+    io::{Cursor, Read},
+    path::PathBuf,
+};
+
+#[derive(clap::Args, Debug)]
+pub struct InspectArgs {
+    pub archive: PathBuf,
+    /// Print the report as JSON instead of text
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct Report {
+    /// Image entries in reading order.
+    pages: Vec<PageEntry>,
+    /// Every other entry but ComicInfo.xml, in stored order.
+    others: Vec<Entry>,
+    comicinfo: Option<ComicInfo>,
+    problems: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct Entry {
+    name: String,
+    size: u64,
+    compressed: u64,
+    method: String,
+}
+
+#[derive(Serialize, Debug)]
+struct PageEntry {
+    #[serde(flatten)]
+    entry: Entry,
+    /// What the contents are, which need not match the extension.
+    format: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+pub fn run(args: &InspectArgs) -> Result<()> {
+    let report = inspect(args)?;
+    if args.json {
+        println!("{}", serde_json::to_string(&report).unwrap());
+        return Ok(());
+    }
+
+    let total: u64 = report.pages.iter().map(|p| p.entry.size).chain(report.others.iter().map(|e| e.size)).sum();
+    println!("{}: {} pages, {}", args.archive.display(), report.pages.len(), human_bytes(total));
+    for (number, page) in (1..).zip(&report.pages) {
+        let size = match (page.width, page.height) {
+            (Some(w), Some(h)) => format!("{}x{}", w, h),
+            _ => "?".to_string(),
+        };
+        println!(
+            "{:>5} {:<5} {:>11} {:>10} {:<8} {}",
+            number,
+            page.format.as_deref().unwrap_or("?"),
+            size,
+            page.entry.size,
+            page.entry.method,
+            page.entry.name
+        );
+    }
+    if !report.others.is_empty() {
+        println!("Other entries:");
+        for entry in &report.others {
+            println!("{:>22} {:>10} {:<8} {}", "", entry.size, entry.method, entry.name);
+        }
+    }
+    match &report.comicinfo {
+        Some(info) => print_comicinfo(info),
+        None => println!("No ComicInfo.xml"),
+    }
+    if report.problems.is_empty() {
+        println!("{}", style::out(Tone::Success, "No problems found"));
+    } else {
+        println!("{}", style::out(Tone::Error, format!("{} problems:", report.problems.len())));
+        for problem in &report.problems {
+            println!("  - {}", problem);
+        }
+    }
+    Ok(())
+}
+
+fn inspect(args: &InspectArgs) -> Result<Report> {
+    let path = &args.archive;
+    // Reads every entry, which checks the CRCs, and looks for numbering gaps and duplicates.
+    let mut problems = archive::verify(path)?;
+    let mut zip = archive::open(path)?;
+    let names = archive::page_names(&zip);
+
+    let mut pages = Vec::new();
+    for name in &names {
+        let mut file = zip.by_name(name)?;
+        let entry = entry(&file);
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).with_context(|| format!("Failed to read {} in {}", name, path.display()))?;
+        let format = image::guess_format(&data).ok();
+        let dimensions =
+            format.and_then(|f| image::ImageReader::with_format(Cursor::new(&data), f).into_dimensions().ok());
+        match format {
+            None => problems.push(format!("{}: not a JPEG or PNG image", name)),
+            Some(format) if !format.extensions_str().iter().any(|e| name.to_ascii_lowercase().ends_with(e)) => {
+                problems.push(format!("{}: holds a {:?} image", name, format));
+            }
+            Some(_) if dimensions.is_none() => problems.push(format!("{}: could not be decoded", name)),
+            Some(_) => {}
+        }
+        pages.push(PageEntry {
+            entry,
+            format: format.map(|f| format!("{:?}", f).to_uppercase()),
+            width: dimensions.map(|d| d.0),
+            height: dimensions.map(|d| d.1),
+        });
+    }
+
+    let mut stored = Vec::new();
+    let mut others = Vec::new();
+    for i in 0..zip.len() {
+        let file = zip.by_index_raw(i)?;
+        if names.iter().any(|n| n == file.name()) {
+            stored.push(file.name().to_string());
+        } else if file.name() != "ComicInfo.xml" && !file.is_dir() {
+            others.push(entry(&file));
+        }
+    }
+    drop(zip);
+    // Some readers show pages in stored order instead of by name.
+    if let Some((stored, expected)) = stored.iter().zip(&names).find(|(s, e)| s != e) {
+        problems.push(format!("{} is stored where {} belongs in reading order", stored, expected));
+    }
+
+    let comicinfo = meta::read_comicinfo(path)?.map(|xml| ComicInfo::parse(&xml));
+    if let Some(info) = &comicinfo {
+        if let Some(count) = info.page_count.filter(|&c| c != pages.len()) {
+            problems.push(format!("ComicInfo.xml counts {} pages, the archive has {}", count, pages.len()));
+        }
+        for (index, text) in info.bookmarks.iter().filter(|(i, _)| *i >= pages.len()) {
+            problems.push(format!("Bookmark {:?} points at page {}, past the last page", text, index + 1));
+        }
+    }
+    Ok(Report { pages, others, comicinfo, problems })
+}
+
+fn entry(file: &zip::read::ZipFile) -> Entry {
+    Entry {
+        name: file.name().to_string(),
+        size: file.size(),
+        compressed: file.compressed_size(),
+        method: file.compression().to_string(),
+    }
+}
+
+fn print_comicinfo(info: &ComicInfo) {
+    println!("ComicInfo.xml:");
+    let fields = [
+        ("Title", info.title.clone()),
+        ("Series", info.series.clone()),
+        ("Writer", info.writer.clone()),
+        ("Volume", info.volume.map(|v| v.to_string())),
+        ("Number", info.number.map(|n| n.to_string())),
+        ("Count", info.count.map(|c| c.to_string())),
+        ("PageCount", info.page_count.map(|c| c.to_string())),
+        ("Direction", info.direction.map(|d| format!("{:?}", d).to_lowercase())),
+        ("Notes", info.notes.clone()),
+    ];
+    for (key, value) in fields.iter().filter_map(|(k, v)| Some((k, v.as_ref()?))) {
+        println!("  {:<10} {}", key, value);
+    }
+    for (index, text) in &info.bookmarks {
+        println!("  {:<10} page {}: {}", "Bookmark", index + 1, text);
+    }
+    for (index, kind) in &info.page_types {
+        println!("  {:<10} page {}: {}", "Type", index + 1, kind);
+    }
+}
@@ -0,0 +1,98 @@
+//! The `--log-file` record of what every run decided, whatever is shown on the console.
+
+use manga_compiler::compiler::timestamp;
+use manga_compiler::device::PageSettings;
+use manga_compiler::discover::describe_gaps;
+use manga_compiler::{Book, Device};
+use anyhow::{Context, Result};
+use std::{
+    fmt::Display,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::Mutex,
+    time::SystemTime,
+};
+
+static LOG: Mutex<Option<File>> = Mutex::new(None);
+
+/// Appends to `path` from now on, so the log of a long batch or watch run keeps every run.
+pub fn open(path: &Path) -> Result<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open the log file {}", path.display()))?;
+    *LOG.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+/// Writes one line stamped with the UTC time, when there is a log file.
+pub fn line(message: impl Display) {
+    let mut log = LOG.lock().unwrap();
+    if let Some(file) = log.as_mut() {
+        let time = timestamp(SystemTime::now()).unwrap_or_default();
+        // A full disk should not fail the build the log is about.
+        let _ = writeln!(file, "{} {}", time, message);
+    }
+}
+
+pub fn enabled() -> bool {
+    LOG.lock().unwrap().is_some()
+}
+
+/// Which files became pages under which names, which were skipped and how each page is processed.
+pub fn book(book: &Book) {
+    if !enabled() {
+        return;
+    }
+    let folder = book.folder.display();
+    line(format_args!("{}: {} pages of {:?} matching {}", folder, book.pages.len(), book.title, book.pattern));
+    let settings = book.packing(book.pages.len()).page_settings;
+    for ((name, path), page) in book.entries().iter().zip(&book.pages) {
+        let mut details = vec![format!("number {}", page.number)];
+        details.extend(page.chapter.map(|c| format!("chapter {}", c)));
+        details.extend(page.time.as_ref().map(|t| format!("time {}", t)));
+        let settings = settings.get(name).copied().unwrap_or_default();
+        details.push(processing(book.device.as_ref(), settings));
+        line(format_args!("{}: page {} as {} ({})", folder, path.display(), name, details.join(", ")));
+    }
+    for (name, path) in book.entries().iter().skip(book.pages.len()) {
+        line(format_args!("{}: extra {} as {}", folder, path.display(), name));
+    }
+    for path in &book.noise {
+        line(format_args!("{}: skipped {}, not matching the pattern", folder, path.display()));
+    }
+    for path in &book.duplicates {
+        line(format_args!("{}: skipped {}, a copy of another page", folder, path.display()));
+    }
+    if !book.missing.is_empty() {
+        line(format_args!("{}: missing page numbers {:?}", folder, book.missing));
+    }
+    if !book.chapter_gaps.is_empty() {
+        line(format_args!("{}: missing page numbers {}", folder, describe_gaps(&book.chapter_gaps)));
+    }
+}
+
+/// What [`Device::prepare`] does to a page, `stored as is` without a device.
+fn processing(device: Option<&Device>, settings: PageSettings) -> String {
+    let Some(device) = device else { return "stored as is".to_string() };
+    let mut steps = Vec::new();
+    if let Some((width, height)) = device.resolution.filter(|_| settings.scale != Some(false)) {
+        steps.push(format!("fit to {}x{}", width, height));
+    }
+    if let Some(levels) = device.palette.filter(|_| settings.keep_color != Some(true)) {
+        steps.push(format!("{} grays", levels));
+    }
+    if let Some(gamma) = settings.gamma.or(device.gamma) {
+        steps.push(format!("gamma {}", gamma));
+    }
+    if steps.is_empty() {
+        return format!("stored as is for {}", device.name);
+    }
+    if device.palette.is_some() && settings.keep_color == Some(true) {
+        steps.push("colour kept".to_string());
+    }
+    steps.push(format!("JPEG quality {}", settings.quality.unwrap_or(90)));
+    format!("{} for {}", steps.join(", "), device.name)
+}
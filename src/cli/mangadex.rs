@@ -0,0 +1,129 @@
+use super::style::{self, Tone};
+use manga_compiler::archive;
+use manga_compiler::config::Config;
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{LazyLock, Mutex},
+};
+
+const API: &str = "https://api.mangadex.org";
+/// The most chapters the feed endpoint returns per request.
+const PAGE_SIZE: usize = 500;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct MangaDexOpts {
+    /// MangaDex series id whose chapter titles and volumes name chapter folders
+    #[arg(long, value_name = "UUID", value_parser = parse_uuid)]
+    pub mangadex: Option<String>,
+    /// Language of the chapter titles looked up on MangaDex
+    #[arg(long, value_name = "LANG", default_value = "en")]
+    pub mangadex_lang: String,
+}
+
+#[derive(Debug, Clone)]
+struct Manga {
+    title: Option<String>,
+    /// Chapter number (`15`, `15.5`) to volume and title, from the first upload of each chapter.
+    chapters: HashMap<String, (Option<u32>, Option<String>)>,
+}
+
+/// One lookup per series and language, however many chapter folders a batch compiles.
+static CACHE: LazyLock<Mutex<HashMap<(String, String), Manga>>> = LazyLock::new(Default::default);
+
+fn parse_uuid(s: &str) -> Result<String, String> {
+    let groups: Vec<&str> = s.split('-').collect();
+    let lengths: Vec<usize> = groups.iter().map(|g| g.len()).collect();
+    if lengths != [8, 4, 4, 4, 12] || !groups.iter().all(|g| g.chars().all(|c| c.is_ascii_hexdigit())) {
+        return Err("expected a UUID such as a1c7c817-4e59-43b7-9365-09675a149a6f".to_string());
+    }
+    Ok(s.to_ascii_lowercase())
+}
+
+/// Fills series, volume and chapter title of a chapter folder from MangaDex into its effective
+/// config; values given on the command line or in a config keep precedence.
+pub fn apply(opts: &MangaDexOpts, folder: &Path, config: &mut Config) -> Result<()> {
+    let Some(id) = &opts.mangadex else { return Ok(()) };
+    let manga = lookup(id, &opts.mangadex_lang)?;
+    config.series = config.series.take().or(manga.title);
+    let name = folder.file_name().unwrap_or_default().to_string_lossy();
+    let Some(chapter) = config.chapter.or_else(|| archive::chapter_number(&name)) else {
+        eprintln!(
+            "{}",
+            style::err(Tone::Warning, format!("Could not tell the chapter of {}, pass --chapter", folder.display()))
+        );
+        return Ok(());
+    };
+    config.chapter = Some(chapter);
+    match manga.chapters.get(&chapter.to_string()) {
+        Some((volume, title)) => {
+            config.volume = config.volume.or(*volume);
+            config.chapter_title = config.chapter_title.take().or_else(|| title.clone());
+        }
+        None => eprintln!(
+            "{}",
+            style::err(Tone::Warning, format!("MangaDex lists no chapter {} in {}", chapter, opts.mangadex_lang))
+        ),
+    }
+    Ok(())
+}
+
+fn lookup(id: &str, lang: &str) -> Result<Manga> {
+    let key = (id.to_string(), lang.to_string());
+    if let Some(manga) = CACHE.lock().unwrap().get(&key) {
+        return Ok(manga.clone());
+    }
+    let manga = fetch(id, lang).with_context(|| format!("Failed to look up {} on MangaDex", id))?;
+    CACHE.lock().unwrap().insert(key, manga.clone());
+    Ok(manga)
+}
+
+fn fetch(id: &str, lang: &str) -> Result<Manga> {
+    let agent = ureq::Agent::new_with_defaults();
+    let get = |url: String, query: &[(&str, String)]| -> Result<Value> {
+        let mut request = agent.get(&url);
+        for (key, value) in query {
+            request = request.query(key, value);
+        }
+        let body = request.call()?.body_mut().read_to_string()?;
+        serde_json::from_str(&body).context("MangaDex sent invalid JSON")
+    };
+
+    let details = get(format!("{}/manga/{}", API, id), &[])?;
+    let titles = &details["data"]["attributes"]["title"];
+    let title = titles[lang]
+        .as_str()
+        .or_else(|| titles["en"].as_str())
+        .or_else(|| titles.as_object()?.values().next()?.as_str())
+        .map(String::from);
+
+    let mut chapters = HashMap::new();
+    let mut offset = 0;
+    loop {
+        let query = [
+            ("translatedLanguage[]", lang.to_string()),
+            ("limit", PAGE_SIZE.to_string()),
+            ("offset", offset.to_string()),
+            ("order[chapter]", "asc".to_string()),
+        ];
+        let feed = get(format!("{}/manga/{}/feed", API, id), &query)?;
+        let data = feed["data"].as_array().context("MangaDex sent no chapter list")?;
+        for item in data {
+            let attributes = &item["attributes"];
+            let Some(number) = attributes["chapter"].as_str().and_then(|c| c.parse::<f64>().ok()) else { continue };
+            let volume = attributes["volume"].as_str().and_then(|v| v.parse().ok());
+            let title = attributes["title"].as_str().filter(|t| !t.is_empty()).map(String::from);
+            let entry = chapters.entry(number.to_string()).or_insert((volume, title.clone()));
+            // Another group's upload may carry the title the first one left out.
+            entry.0 = entry.0.or(volume);
+            entry.1 = entry.1.take().or(title);
+        }
+        offset += data.len();
+        if data.is_empty() || offset >= feed["total"].as_u64().unwrap_or(0) as usize {
+            break;
+        }
+    }
+    Ok(Manga { title, chapters })
+}
@@ -0,0 +1,136 @@
+use super::build::{self, CompileOpts};
+use super::extract;
+use super::index;
+use super::log;
+use super::meta;
+use super::progress::Bar;
+use super::scratch::Scratch;
+use super::upload;
+use manga_compiler::comicinfo::{self, ComicInfo};
+use manga_compiler::titlepage::{self, TitleStyle};
+use manga_compiler::{Book, Compiler, Config, archive};
+use anyhow::Result;
+use image::Rgb;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Args, Debug)]
+pub struct MergeArgs {
+    /// Archives to join, in reading order
+    #[arg(required = true, num_args = 2..)]
+    pub archives: Vec<PathBuf>,
+    /// The merged archive
+    #[arg(short, long)]
+    pub output: PathBuf,
+    #[command(flatten)]
+    pub titles: TitlePageOpts,
+    /// Print the run summary as JSON instead of text
+    #[arg(long)]
+    pub json: bool,
+    #[command(flatten)]
+    pub opts: CompileOpts,
+}
+
+pub fn run(args: &MergeArgs) -> Result<()> {
+    let compiler =
+        Compiler::new(args.output.with_extension("")).config(args.opts.to_config()).output(&args.output);
+    let mut config = compiler.effective_config()?;
+    // The parts only need what decides how their pages are found and prepared.
+    let part_config = Config { infer: config.infer, device: config.device.clone(), ..Config::default() };
+    let scratch = Scratch::new("merge")?;
+    let mut parts = Vec::new();
+    for (index, path) in (1..).zip(&args.archives) {
+        let dir = scratch.path().join(format!("{:03}", index));
+        parts.push(unpack(path, &dir, part_config.clone())?.book);
+    }
+
+    config.series = config.series.or_else(|| parts.iter().find_map(|p| p.config.series.clone()));
+    config.author = config.author.or_else(|| merged_authors(&parts));
+    let volumes: Vec<Option<u32>> = parts.iter().map(|p| p.config.volume).collect();
+    if volumes.iter().all(|v| *v == volumes[0]) {
+        config.volume = config.volume.or(volumes[0]);
+    }
+    config.direction = config.direction.or_else(|| parts.iter().find_map(|p| p.config.direction));
+
+    let mut book = Book::merge(compiler.folder().to_path_buf(), config, parts);
+    args.titles.insert(&mut book, scratch.path())?;
+    log::book(&book);
+    let summary = compiler.compile_with(&book, &mut Bar::new())?;
+    build::report(&summary, args.json);
+    index::record(&args.opts, &summary, Config::default())?;
+    upload::run(&args.opts.upload, &summary.output)
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct TitlePageOpts {
+    /// Put a generated page with the chapter number and title before each chapter
+    #[arg(long)]
+    pub title_pages: bool,
+    /// TrueType or OpenType font of the title pages instead of the built-in bitmap font
+    #[arg(long, value_name = "FILE", requires = "title_pages")]
+    pub title_font: Option<PathBuf>,
+    /// Background of the title pages, `#rrggbb`, `white` or `black`
+    #[arg(long, value_name = "COLOR", requires = "title_pages", value_parser = parse_color)]
+    pub title_background: Option<Rgb<u8>>,
+}
+
+fn parse_color(s: &str) -> Result<Rgb<u8>, String> {
+    titlepage::parse_color(s).ok_or_else(|| "expected #rrggbb, white or black".to_string())
+}
+
+impl TitlePageOpts {
+    /// Writes the title pages below `scratch` and inserts them into `book`, if asked for.
+    pub fn insert(&self, book: &mut Book, scratch: &Path) -> Result<()> {
+        if !self.title_pages {
+            return Ok(());
+        }
+        let mut style = TitleStyle::default();
+        if let Some(path) = &self.title_font {
+            style.font = Some(TitleStyle::load_font(path)?);
+        }
+        style.background = self.title_background.unwrap_or(style.background);
+        Ok(book.insert_title_pages(&scratch.join("titles"), &style)?)
+    }
+}
+
+/// An archive opened as if it were a chapter folder.
+pub struct Unpacked {
+    pub book: Book,
+    /// Entry names of the pages, in page order.
+    pub entries: Vec<String>,
+    pub info: ComicInfo,
+}
+
+/// Unpacks the pages of `path` into a folder below `dir` named after the archive and discovers
+/// it with `config` layered over the archive's ComicInfo.xml; config files are not read, their
+/// page pattern would not match the unpacked names.
+pub fn unpack(path: &Path, dir: &Path, config: Config) -> Result<Unpacked> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+    let info = meta::read_comicinfo(path)?.map(|xml| ComicInfo::parse(&xml)).unwrap_or_default();
+    let folder = dir.join(&stem);
+    let (entries, _) = extract::unpack_pages(path, &mut archive::open(path)?, &folder)?;
+    let stored = Config {
+        series: info.series.clone(),
+        author: info.writer.clone(),
+        volume: info.volume,
+        chapter: info.number,
+        // Archives written without a chapter title carry their file name as Title.
+        chapter_title: info.title.clone().filter(|t| *t != stem),
+        direction: info.direction,
+        ..Config::default()
+    };
+    let book = Compiler::new(&folder).config(config.or(stored)).use_config_files(false).discover()?;
+    Ok(Unpacked { book, entries, info })
+}
+
+/// Every writer of the parts once, in order of appearance.
+fn merged_authors(parts: &[Book]) -> Option<String> {
+    let mut authors: Vec<&str> = Vec::new();
+    for writer in parts.iter().filter_map(|p| p.config.author.as_deref()) {
+        for author in comicinfo::authors(writer) {
+            if !authors.contains(&author) {
+                authors.push(author);
+            }
+        }
+    }
+    (!authors.is_empty()).then(|| authors.join(", "))
+}
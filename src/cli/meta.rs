@@ -0,0 +1,70 @@
+use super::i18n::t;
+use manga_compiler::archive;
+use manga_compiler::comicinfo::{self, ComicInfo};
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::{io::{Read, Write}, path::PathBuf};
+use zip::write::FileOptions;
+
+const COMICINFO: &str = "ComicInfo.xml";
+
+#[derive(clap::Args, Debug)]
+pub struct MetaArgs {
+    pub archive: PathBuf,
+    /// Set a ComicInfo element, e.g. `--set Series=Berserk`; may be repeated
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_pair)]
+    pub set: Vec<(String, String)>,
+}
+
+fn parse_pair(s: &str) -> Result<(String, String), String> {
+    let (key, value) = s.split_once('=').ok_or("expected KEY=VALUE")?;
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("invalid element name {:?}", key));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+pub fn run(args: &MetaArgs) -> Result<()> {
+    let current = read_comicinfo(&args.archive)?;
+    if args.set.is_empty() {
+        match current {
+            Some(xml) => print!("{}", xml),
+            None => println!("{} has no {}", args.archive.display(), COMICINFO),
+        }
+        return Ok(());
+    }
+
+    let mut xml = current.unwrap_or_else(|| ComicInfo::default().to_xml());
+    for (key, value) in &args.set {
+        xml = set_element(&xml, key, value);
+    }
+    archive::rewrite(&args.archive, |name| name != COMICINFO, |zip| {
+        zip.start_file(COMICINFO, FileOptions::default())?;
+        zip.write_all(xml.as_bytes())?;
+        Ok(())
+    })?;
+    println!("{}", t!("Updated {0} in {1}", COMICINFO, args.archive.display()));
+    Ok(())
+}
+
+pub fn read_comicinfo(path: &std::path::Path) -> Result<Option<String>> {
+    let mut zip = archive::open(path)?;
+    let Ok(mut entry) = zip.by_name(COMICINFO) else {
+        return Ok(None);
+    };
+    let mut xml = String::new();
+    entry
+        .read_to_string(&mut xml)
+        .with_context(|| format!("Failed to read {} from {}", COMICINFO, path.display()))?;
+    Ok(Some(xml))
+}
+
+fn set_element(xml: &str, key: &str, value: &str) -> String {
+    let element = format!("<{key}>{}</{key}>", comicinfo::escape(value));
+    let existing = Regex::new(&format!(r"(?s)<{key}>.*?</{key}>|<{key}\s*/>")).unwrap();
+    if existing.is_match(xml) {
+        existing.replace(xml, regex::NoExpand(&element)).into_owned()
+    } else {
+        xml.replacen("</ComicInfo>", &format!("  {}\n</ComicInfo>", element), 1)
+    }
+}
@@ -0,0 +1,28 @@
+pub mod batch;
+pub mod build;
+pub mod catalog;
+pub mod convert;
+pub mod diff;
+pub mod download;
+pub mod extract;
+pub mod i18n;
+pub mod index;
+pub mod inspect;
+pub mod log;
+pub mod mangadex;
+pub mod merge;
+pub mod meta;
+pub mod progress;
+pub mod rename;
+pub mod resume;
+#[cfg(feature = "tui")]
+pub mod review;
+pub mod scratch;
+pub mod split;
+pub mod style;
+pub mod sync;
+pub mod update;
+pub mod upload;
+pub mod verify;
+pub mod volumes;
+pub mod watch;
@@ -0,0 +1,130 @@
+use super::i18n::t;
+use super::log;
+use super::style::{self, Tone};
+use manga_compiler::discover::describe_gaps;
+use manga_compiler::summary::{Summary, human_bytes};
+use manga_compiler::{ProgressObserver, Warning};
+use std::{
+    io::{self, IsTerminal, Write},
+    path::Path,
+};
+
+const WIDTH: usize = 30;
+
+/// Prints warnings and, when stderr is a terminal, a one-line progress bar that is cleared
+/// again once the archive is written.
+pub struct Bar {
+    enabled: bool,
+    drawn: bool,
+    total: usize,
+    done: usize,
+    bytes: u64,
+}
+
+impl Bar {
+    pub fn new() -> Self {
+        Bar { enabled: io::stderr().is_terminal(), drawn: false, total: 0, done: 0, bytes: 0 }
+    }
+
+    fn draw(&mut self) {
+        if !self.enabled || self.total == 0 {
+            return;
+        }
+        let filled = self.done * WIDTH / self.total;
+        let bar = format!("{}{}", "#".repeat(filled), " ".repeat(WIDTH - filled));
+        eprint!("\r[{}] {}", bar, t!("{0}/{1} pages, {2}", self.done, self.total, human_bytes(self.bytes)));
+        let _ = io::stderr().flush();
+        self.drawn = true;
+    }
+
+    fn clear(&mut self) {
+        if self.drawn {
+            eprint!("\r\x1b[2K");
+            self.drawn = false;
+        }
+    }
+}
+
+impl ProgressObserver for Bar {
+    fn started(&mut self, output: &Path, entries: usize) {
+        log::line(format_args!("Writing {} entries to {}", entries, output.display()));
+        self.total = entries;
+        self.draw();
+    }
+
+    fn page_finished(&mut self, index: usize, name: &str) {
+        log::line(format_args!("Packed {}", name));
+        self.done = index + 1;
+    }
+
+    fn bytes_written(&mut self, bytes: u64) {
+        self.bytes = bytes;
+        self.draw();
+    }
+
+    fn warning(&mut self, warning: &Warning) {
+        log::line(format_args!("Warning: {:?}", warning));
+        self.clear();
+        match warning {
+            Warning::IgnoredFiles(names) => {
+                eprintln!("{}", style::err(Tone::Warning, t!("Warning: ignored files not matching pattern:")));
+                for name in names {
+                    eprintln!("  - {}", style::err(Tone::Dim, name));
+                }
+            }
+            Warning::DroppedDuplicates(names) => {
+                eprintln!("{}", style::err(Tone::Warning, t!("Warning: left out duplicate copies of pages:")));
+                for name in names {
+                    eprintln!("  - {}", style::err(Tone::Dim, name));
+                }
+            }
+            Warning::IdenticalPages(pairs) => {
+                eprintln!("{}", style::err(Tone::Warning, t!("Warning: pages identical to an earlier page:")));
+                for (copy, original) in pairs {
+                    eprintln!("  - {}", style::err(Tone::Dim, t!("{0} repeats {1}", copy, original)));
+                }
+            }
+            Warning::OverMemory(names) => {
+                let message = t!("Warning: stored as they are, preparing them would take more than --max-memory:");
+                eprintln!("{}", style::err(Tone::Warning, message));
+                for name in names {
+                    eprintln!("  - {}", style::err(Tone::Dim, name));
+                }
+            }
+            Warning::Unfiltered(pages) => {
+                eprintln!("{}", style::err(Tone::Warning, t!("Warning: stored unfiltered, the filter failed on:")));
+                for (name, reason) in pages {
+                    eprintln!("  - {}", style::err(Tone::Dim, format!("{}: {}", name, reason)));
+                }
+            }
+            Warning::MissingPages(numbers) => {
+                let message = t!("Warning: missing page numbers: {0}", format!("{:?}", numbers));
+                eprintln!("{}", style::err(Tone::Warning, message));
+            }
+            Warning::MissingChapterPages(gaps) => {
+                let message = t!("Warning: missing page numbers: {0}", describe_gaps(gaps));
+                eprintln!("{}", style::err(Tone::Warning, message));
+            }
+            other => eprintln!("{}", style::err(Tone::Warning, t!("Warning: {0}", format!("{:?}", other)))),
+        }
+        self.draw();
+    }
+
+    fn finished(&mut self, summary: &Summary) {
+        log::line(format_args!("Wrote {}: {}", summary.output.display(), summary.stats.line()));
+        if let Some(cover) = &summary.cover {
+            log::line(format_args!("Wrote the cover {}", cover.display()));
+        }
+        for page in summary.quality.iter().flat_map(|q| &q.pages) {
+            let issues = page.issues.join(", ");
+            log::line(format_args!("Quality of {}: {} {}", page.name, page.score, issues));
+        }
+        self.clear();
+    }
+}
+
+impl Drop for Bar {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
@@ -0,0 +1,86 @@
+use super::i18n::t;
+use manga_compiler::archive;
+use anyhow::{Context, Result};
+use std::{collections::HashSet, fs, path::PathBuf};
+use walkdir::WalkDir;
+
+#[derive(clap::Args, Debug)]
+pub struct RenameArgs {
+    pub folder: PathBuf,
+    /// Only print the planned renames
+    #[arg(short = 'n', long)]
+    pub dry_run: bool,
+}
+
+pub fn run(args: &RenameArgs) -> Result<()> {
+    let folder = &args.folder;
+    let title = folder
+        .file_name()
+        .and_then(|s| s.to_str())
+        .context("Could not determine folder name as title")?;
+
+    let mut files = Vec::new();
+    for entry in WalkDir::new(folder).min_depth(1).max_depth(1) {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if entry.file_type().is_file() && archive::is_image(&name) && !archive::is_junk(&name) {
+            files.push(name);
+        }
+    }
+    if files.is_empty() {
+        anyhow::bail!("No image files found in {}", folder.display());
+    }
+    files.sort_by(|a, b| archive::natural_cmp(a, b));
+
+    let plan: Vec<(String, String)> = (1..)
+        .zip(&files)
+        .map(|(n, name)| {
+            let ext = name.rsplit('.').next().unwrap();
+            (name.clone(), archive::page_file_name(title, n, files.len(), ext))
+        })
+        .filter(|(old, new)| old != new)
+        .collect();
+
+    for (old, new) in &plan {
+        println!("{} -> {}", old, new);
+    }
+    if plan.is_empty() {
+        println!("{}", t!("{0} is already named canonically", folder.display()));
+        return Ok(());
+    }
+    if args.dry_run {
+        println!("{}", t!("{0} files would be renamed", plan.len()));
+        return Ok(());
+    }
+
+    // Only files that move out of the way free their names.
+    let moving: HashSet<&str> = plan.iter().map(|(old, _)| old.as_str()).collect();
+    if let Some((_, new)) = plan.iter().find(|(_, new)| !moving.contains(new.as_str()) && folder.join(new).exists()) {
+        anyhow::bail!("{} already exists", folder.join(new).display());
+    }
+
+    // Go through temporary names first so that a target never clobbers a file still waiting to move.
+    let temp: Vec<PathBuf> = (0..plan.len()).map(|i| folder.join(format!(".manga-compiler-rename-{}", i))).collect();
+    let mut moved = Vec::new();
+    let result: Result<()> = (|| {
+        for ((old, _), tmp) in plan.iter().zip(&temp) {
+            fs::rename(folder.join(old), tmp).with_context(|| format!("Failed to rename {}", old))?;
+            moved.push((folder.join(old), tmp.clone()));
+        }
+        for ((_, new), tmp) in plan.iter().zip(&temp) {
+            let target = folder.join(new);
+            fs::rename(tmp, &target).with_context(|| format!("Failed to rename to {}", new))?;
+            moved.push((tmp.clone(), target));
+        }
+        Ok(())
+    })();
+    if result.is_err() {
+        // Undone last to first, so every file gets its old name back and no temporary one is left.
+        for (from, to) in moved.iter().rev() {
+            let _ = fs::rename(to, from);
+        }
+    }
+    result?;
+    println!("{}", t!("Renamed {0} files", plan.len()));
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+pub const STATE_FILE: &str = ".manga-compiler-batch.json";
+
+/// Books of a batch run whose output passed verification, keyed by source folder.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct BatchState {
+    books: BTreeMap<PathBuf, Verified>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct Verified {
+    output: PathBuf,
+    size: u64,
+    modified: u64,
+}
+
+impl Verified {
+    fn of(output: &Path) -> Option<Self> {
+        let meta = fs::metadata(output).ok()?;
+        let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_nanos() as u64;
+        Some(Verified { output: output.to_path_buf(), size: meta.len(), modified })
+    }
+}
+
+impl BatchState {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("Invalid batch state {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text = serde_json::to_string_pretty(self)?;
+        fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// The recorded output of `source`, if it is still exactly the file that was verified.
+    pub fn finished(&self, source: &Path) -> Option<&Path> {
+        let recorded = self.books.get(source)?;
+        (Verified::of(&recorded.output).as_ref() == Some(recorded)).then_some(recorded.output.as_path())
+    }
+
+    pub fn mark_verified(&mut self, source: &Path, output: &Path) {
+        match Verified::of(output) {
+            Some(v) => self.books.insert(source.to_path_buf(), v),
+            None => self.books.remove(source),
+        };
+    }
+
+    pub fn forget(&mut self, source: &Path) {
+        self.books.remove(source);
+    }
+}
@@ -0,0 +1,237 @@
+use super::build::{self, CompileOpts};
+use super::index;
+use super::upload;
+use manga_compiler::{Book, Config, archive};
+use anyhow::Result;
+use image::RgbImage;
+use ratatui::{
+    DefaultTerminal, Frame,
+    buffer::Buffer,
+    crossterm::event::{self, KeyCode, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, List, ListItem, ListState, Paragraph, Widget},
+};
+use std::{collections::HashMap, path::PathBuf};
+
+#[derive(clap::Args, Debug)]
+pub struct ReviewArgs {
+    pub folder: PathBuf,
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    #[command(flatten)]
+    pub opts: CompileOpts,
+}
+
+struct Item {
+    path: PathBuf,
+    name: String,
+    number: Option<u32>,
+    included: bool,
+}
+
+struct App {
+    book: Book,
+    items: Vec<Item>,
+    state: ListState,
+    thumbs: HashMap<PathBuf, Option<RgbImage>>,
+    status: String,
+}
+
+enum Outcome {
+    Write,
+    Quit,
+}
+
+pub fn run(args: &ReviewArgs) -> Result<()> {
+    let (compiler, book) = build::discover(&args.folder, args.output.as_deref(), &args.opts, Config::default())?;
+    let mut items: Vec<Item> = book
+        .pages
+        .iter()
+        .map(|page| Item::new(page.path.clone(), Some(page.number), true))
+        .collect();
+    let left_out = book.noise.iter().chain(&book.duplicates);
+    let mut noise: Vec<Item> = left_out.map(|p| Item::new(p.clone(), None, false)).collect();
+    noise.sort_by(|a, b| archive::natural_cmp(&a.name, &b.name));
+    items.extend(noise);
+    if items.is_empty() {
+        anyhow::bail!("{} contains no files", args.folder.display());
+    }
+
+    let mut app = App {
+        book,
+        items,
+        state: ListState::default().with_selected(Some(0)),
+        thumbs: HashMap::new(),
+        status: String::new(),
+    };
+    let outcome = ratatui::run(|terminal| app.event_loop(terminal))?;
+    if let Outcome::Write = outcome {
+        // Written like any other book, hooks, signing and all, only its pages as reviewed.
+        let included: Vec<PathBuf> = app.items.iter().filter(|i| i.included).map(|i| i.path.clone()).collect();
+        let summary = build::compile_book(&compiler, &app.book.reorder(&included))?;
+        build::report(&summary, false);
+        index::record(&args.opts, &summary, Config::default())?;
+        upload::run(&args.opts.upload, &summary.output)?;
+    }
+    Ok(())
+}
+
+impl Item {
+    fn new(path: PathBuf, number: Option<u32>, included: bool) -> Self {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        Item { path, name, number, included }
+    }
+}
+
+impl App {
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<Outcome> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Some(key) = event::read()?.as_key_press_event() else {
+                continue;
+            };
+            let selected = self.state.selected().unwrap_or(0);
+            let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+            self.status.clear();
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(Outcome::Quit),
+                KeyCode::Char('w') => {
+                    if self.items.iter().any(|i| i.included) {
+                        return Ok(Outcome::Write);
+                    }
+                    self.status = "Nothing to write, include at least one page".into();
+                }
+                KeyCode::Char('K') => self.move_item(selected, -1),
+                KeyCode::Char('J') => self.move_item(selected, 1),
+                KeyCode::Up if shift => self.move_item(selected, -1),
+                KeyCode::Down if shift => self.move_item(selected, 1),
+                KeyCode::Up | KeyCode::Char('k') => self.state.select_previous(),
+                KeyCode::Down | KeyCode::Char('j') => self.state.select_next(),
+                KeyCode::Home | KeyCode::Char('g') => self.state.select_first(),
+                KeyCode::End | KeyCode::Char('G') => self.state.select_last(),
+                KeyCode::Char(' ') => {
+                    let item = &mut self.items[selected];
+                    if !item.included && !archive::is_image(&item.name) {
+                        self.status = format!("{} is not an image", item.name);
+                    } else {
+                        item.included = !item.included;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn move_item(&mut self, index: usize, delta: isize) {
+        let Some(target) = index.checked_add_signed(delta).filter(|t| *t < self.items.len()) else {
+            return;
+        };
+        self.items.swap(index, target);
+        self.state.select(Some(target));
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, body, footer] = Layout::vertical([
+            Constraint::Length(2),
+            Constraint::Min(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [list_area, preview_area] =
+            Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)]).areas(body);
+
+        let included: Vec<&Item> = self.items.iter().filter(|i| i.included).collect();
+        let numbers: Vec<u32> = included.iter().filter_map(|i| i.number).collect();
+        let gaps = archive::missing_pages(&numbers);
+        let mut summary = vec![Line::from(format!(
+            "{} - {} of {} files included",
+            self.book.title,
+            included.len(),
+            self.items.len()
+        ))];
+        if gaps.is_empty() {
+            summary.push(Line::from(String::new()));
+        } else {
+            summary.push(Line::styled(
+                format!("Numbering gaps (the list order is used regardless): {:?}", gaps),
+                Style::new().fg(Color::Yellow),
+            ));
+        }
+        frame.render_widget(Paragraph::new(summary), header);
+
+        let mut page = 0;
+        let rows: Vec<ListItem> = self
+            .items
+            .iter()
+            .map(|item| {
+                let (marker, style) = if item.included {
+                    page += 1;
+                    (format!("{:>4}", page), Style::new())
+                } else {
+                    ("   -".to_string(), Style::new().fg(Color::DarkGray))
+                };
+                ListItem::new(format!("{} {}", marker, item.name)).style(style)
+            })
+            .collect();
+        let list = List::new(rows)
+            .block(Block::bordered().title("Pages"))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, list_area, &mut self.state);
+
+        let block = Block::bordered().title("Preview");
+        let inner = block.inner(preview_area);
+        frame.render_widget(block, preview_area);
+        if let Some(item) = self.state.selected().and_then(|i| self.items.get(i)) {
+            let thumb = self
+                .thumbs
+                .entry(item.path.clone())
+                .or_insert_with(|| load_thumbnail(&item.path));
+            match thumb {
+                Some(img) => frame.render_widget(Thumbnail(img), inner),
+                None => frame.render_widget(Paragraph::new("No preview available"), inner),
+            }
+        }
+
+        let help = if self.status.is_empty() {
+            "j/k move  J/K reorder  space include/exclude  w write  q quit"
+        } else {
+            self.status.as_str()
+        };
+        frame.render_widget(Paragraph::new(help), footer);
+    }
+}
+
+fn load_thumbnail(path: &std::path::Path) -> Option<RgbImage> {
+    Some(image::open(path).ok()?.thumbnail(320, 480).to_rgb8())
+}
+
+/// Draws an image with half-block characters, two pixel rows per terminal cell.
+struct Thumbnail<'a>(&'a RgbImage);
+
+impl Widget for Thumbnail<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let (w, h) = self.0.dimensions();
+        if area.is_empty() || w == 0 || h == 0 {
+            return;
+        }
+        let scale = f64::min(area.width as f64 / w as f64, area.height as f64 * 2.0 / h as f64);
+        let (tw, th) = (((w as f64 * scale) as u32).max(1), ((h as f64 * scale) as u32).max(2));
+        let img = image::imageops::thumbnail(self.0, tw, th);
+        let x0 = area.x + (area.width - tw as u16) / 2;
+        for row in 0..th.div_ceil(2) {
+            for col in 0..tw {
+                let top = img.get_pixel(col, row * 2);
+                let bottom = img.get_pixel_checked(col, row * 2 + 1).unwrap_or(top);
+                if let Some(cell) = buf.cell_mut((x0 + col as u16, area.y + row as u16)) {
+                    cell.set_symbol("▀").set_style(
+                        Style::new()
+                            .fg(Color::Rgb(top[0], top[1], top[2]))
+                            .bg(Color::Rgb(bottom[0], bottom[1], bottom[2])),
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use anyhow::{Context, Result};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// A folder below the system's temporary directory, removed with everything in it on drop.
+pub struct Scratch {
+    path: PathBuf,
+}
+
+impl Scratch {
+    pub fn new(purpose: &str) -> Result<Self> {
+        let path = env::temp_dir().join(format!("manga-compiler-{}-{}", purpose, std::process::id()));
+        // Left over by an earlier process with the same id that did not get to clean up.
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+        Ok(Scratch { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,209 @@
+use super::build::{self, CompileOpts};
+use super::i18n::{self, t};
+use super::index;
+use super::log;
+use super::merge;
+use super::progress::Bar;
+use super::scratch::Scratch;
+use super::style::{self, Tone};
+use super::upload;
+use manga_compiler::archive;
+use manga_compiler::exit::{self, Code, WithCode};
+use manga_compiler::summary::BatchSummary;
+use manga_compiler::{Book, Compiler, Config, MissingPolicy};
+use anyhow::{Context, Result};
+use regex::RegexBuilder;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+#[derive(clap::Args, Debug)]
+#[command(group(clap::ArgGroup::new("cut").required(true)))]
+pub struct SplitArgs {
+    /// Folder or archive holding the pages of many chapters
+    pub source: PathBuf,
+    /// Start a chapter at each of these page numbers, e.g. `1,35,68`
+    #[arg(long, value_name = "PAGES", group = "cut", value_delimiter = ',')]
+    pub at: Vec<usize>,
+    /// Start a chapter every N pages
+    #[arg(long, value_name = "N", group = "cut", value_parser = clap::value_parser!(u32).range(1..))]
+    pub every: Option<u32>,
+    /// Start a chapter at every page whose file name matches this regex (case-insensitive)
+    #[arg(long, value_name = "REGEX", group = "cut")]
+    pub at_pattern: Option<String>,
+    /// File listing the first page of each chapter, by file name or page number, one per line
+    #[arg(long, value_name = "FILE", group = "cut")]
+    pub markers: Option<PathBuf>,
+    /// Start a chapter wherever the pages of an archive move to another folder inside it
+    #[arg(long, group = "cut")]
+    pub by_folder: bool,
+    /// Start a chapter at each bookmark in the ComicInfo.xml of an archive, named after it
+    #[arg(long, group = "cut")]
+    pub by_bookmark: bool,
+    /// Number of the first chapter
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub first_chapter: u32,
+    /// Only print where each chapter starts
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Print the run summary as JSON instead of text
+    #[arg(long)]
+    pub json: bool,
+    #[command(flatten)]
+    pub opts: CompileOpts,
+}
+
+pub fn run(args: &SplitArgs) -> Result<()> {
+    let started = Instant::now();
+    let scratch = Scratch::new("split")?;
+    let mut entries = Vec::new();
+    let mut bookmarks = Vec::new();
+    let book = if args.source.is_file() {
+        // Found as a folder next to the archive, so its config applies as for a source folder.
+        let compiler = Compiler::new(args.source.with_extension("")).config(args.opts.to_config());
+        let mut config = compiler.effective_config()?;
+        config.pattern = None;
+        config.output_dir = config.output_dir.or_else(|| args.source.parent().map(Path::to_path_buf));
+        let unpacked = merge::unpack(&args.source, scratch.path(), config)?;
+        entries = unpacked.entries;
+        bookmarks = unpacked.info.bookmarks;
+        unpacked.book
+    } else if args.by_folder || args.by_bookmark {
+        return Err(exit::fail(Code::Usage, "--by-folder and --by-bookmark need an archive to split"));
+    } else {
+        Compiler::new(&args.source).config(args.opts.to_config()).discover()?
+    };
+    if !book.missing.is_empty() {
+        let message = format!("Missing page numbers {:?}", book.missing);
+        match book.config.missing.unwrap_or_default() {
+            MissingPolicy::Fail => return Err(exit::fail(Code::MissingPages, message)),
+            MissingPolicy::Warn => eprintln!("{}", style::err(Tone::Warning, t!("Warning: {0}", message))),
+            // Each chapter is numbered anew anyway.
+            MissingPolicy::Compact => {}
+        }
+    }
+    // An unpacked page's number is one past the index of its entry, and stays so for the pages
+    // `--pages` and `--dedupe-exact` leave, so the cuts fall among the pages that remain.
+    let entry = |page: usize| (book.pages[page].number as usize).saturating_sub(1);
+    let folder = |page: usize| entries[entry(page)].rsplit_once('/').map_or("", |(dir, _)| dir);
+    // The bookmark whose pages a page is among.
+    let bookmark = |page: usize| bookmarks.iter().rposition(|(index, _)| *index <= entry(page));
+    let starts = if args.by_folder {
+        (0..book.pages.len()).filter(|&i| i == 0 || folder(i) != folder(i - 1)).collect()
+    } else if args.by_bookmark {
+        if bookmarks.is_empty() {
+            return Err(exit::fail(Code::Usage, format!("{} has no bookmarks", args.source.display())));
+        }
+        (0..book.pages.len()).filter(|&i| i == 0 || bookmark(i) != bookmark(i - 1)).collect()
+    } else {
+        starts(args, &book)?
+    };
+    let mut chapters = book.split(&starts, args.first_chapter);
+    if args.by_bookmark {
+        for (chapter, start) in chapters.iter_mut().zip(&starts) {
+            chapter.config.chapter_title = bookmark(*start).map(|i| bookmarks[i].1.clone());
+        }
+    } else if args.by_folder && book.config.infer.unwrap_or(true) {
+        // A folder such as `Ch 3 - The Hawk` names its chapter.
+        for (chapter, start) in chapters.iter_mut().zip(&starts) {
+            let dir = folder(*start);
+            chapter.config.chapter_title = archive::chapter_title(dir.rsplit('/').next().unwrap_or(dir));
+        }
+    }
+
+    if args.dry_run {
+        for chapter in &chapters {
+            let (first, last) = (&chapter.pages[0], &chapter.pages[chapter.pages.len() - 1]);
+            println!(
+                "{}",
+                t!(
+                    "{0}: {1} - {2} ({3} pages)",
+                    chapter.title,
+                    first.path.file_name().unwrap_or_default().to_string_lossy(),
+                    last.path.file_name().unwrap_or_default().to_string_lossy(),
+                    chapter.pages.len()
+                )
+            );
+        }
+        return Ok(());
+    }
+
+    let mut summary = BatchSummary::default();
+    for chapter in &chapters {
+        let result = (|| {
+            log::book(chapter);
+            let summary = Compiler::new(&args.source).compile_with(chapter, &mut Bar::new())?;
+            index::record(&args.opts, &summary, Config::default())?;
+            upload::run(&args.opts.upload, &summary.output)?;
+            Ok(summary)
+        })();
+        match &result {
+            Ok(s) if !args.json => build::report(s, false),
+            Ok(_) => {}
+            Err(e) => {
+                log::line(format_args!("Failed {}: {:#}", chapter.title, e));
+                eprintln!("{}", style::err(Tone::Error, t!("Failed {0}: {1}", chapter.title, format!("{:#}", e))));
+            }
+        }
+        summary.push(args.source.clone(), &result);
+    }
+    summary.total.set_elapsed(started.elapsed());
+
+    if args.json {
+        println!("{}", serde_json::to_string(&summary).unwrap());
+    } else {
+        println!("{}", t!("{0} of {1} chapters compiled", summary.compiled, chapters.len()));
+        println!("  {}", i18n::stats(&summary.total));
+    }
+    if summary.failed > 0 {
+        return Err(exit::fail(Code::Batch, format!("{} of {} chapters failed", summary.failed, chapters.len())));
+    }
+    Ok(())
+}
+
+/// Indices of the pages that open a chapter.
+fn starts(args: &SplitArgs, book: &Book) -> Result<Vec<usize>> {
+    if !args.at.is_empty() {
+        if let Some(page) = args.at.iter().find(|&&p| p == 0 || p > book.pages.len()) {
+            let message = format!("--at {} is outside the {} pages of {}", page, book.pages.len(), book.title);
+            return Err(exit::fail(Code::Usage, message));
+        }
+        return Ok(args.at.iter().map(|p| p - 1).collect());
+    }
+    if let Some(every) = args.every {
+        return Ok((0..book.pages.len()).step_by(every as usize).collect());
+    }
+    if let Some(pattern) = &args.at_pattern {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .with_context(|| format!("Invalid --at-pattern {:?}", pattern))
+            .code(Code::Usage)?;
+        return Ok(book
+            .pages
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| regex.is_match(&p.path.file_name().unwrap_or_default().to_string_lossy()))
+            .map(|(i, _)| i)
+            .collect());
+    }
+    let Some(path) = &args.markers else { return Ok(Vec::new()) };
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display())).code(Code::Usage)?;
+    let mut starts = Vec::new();
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let found = book.pages.iter().position(|p| {
+            line.parse() == Ok(p.number) || p.path.file_name().is_some_and(|n| n.to_string_lossy() == line)
+        });
+        match found {
+            Some(index) => starts.push(index),
+            None => {
+                let message = format!("{}: no page {:?} in {}", path.display(), line, book.title);
+                return Err(exit::fail(Code::Usage, message));
+            }
+        }
+    }
+    Ok(starts)
+}
@@ -0,0 +1,52 @@
+use std::{
+    fmt::Display,
+    io::{self, IsTerminal},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static STDOUT_COLOR: AtomicBool = AtomicBool::new(false);
+static STDERR_COLOR: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, Debug)]
+pub enum Tone {
+    Success,
+    Warning,
+    Error,
+    Dim,
+}
+
+impl Tone {
+    fn ansi(self) -> &'static str {
+        match self {
+            Tone::Success => "\x1b[32m",
+            Tone::Warning => "\x1b[33m",
+            Tone::Error => "\x1b[1;31m",
+            Tone::Dim => "\x1b[2m",
+        }
+    }
+}
+
+/// Enables color per stream unless `--no-color` or `NO_COLOR` is set or the stream is not a terminal.
+pub fn init(no_color: bool) {
+    let allowed = !no_color && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty());
+    STDOUT_COLOR.store(allowed && io::stdout().is_terminal(), Ordering::Relaxed);
+    STDERR_COLOR.store(allowed && io::stderr().is_terminal(), Ordering::Relaxed);
+}
+
+fn paint(enabled: &AtomicBool, tone: Tone, text: impl Display) -> String {
+    if enabled.load(Ordering::Relaxed) {
+        format!("{}{}\x1b[0m", tone.ansi(), text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Styles text that will be printed to stdout.
+pub fn out(tone: Tone, text: impl Display) -> String {
+    paint(&STDOUT_COLOR, tone, text)
+}
+
+/// Styles text that will be printed to stderr.
+pub fn err(tone: Tone, text: impl Display) -> String {
+    paint(&STDERR_COLOR, tone, text)
+}
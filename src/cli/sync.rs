@@ -0,0 +1,181 @@
+use super::i18n::t;
+use super::style::{self, Tone};
+use manga_compiler::device::{self, Device};
+use manga_compiler::exit::{self, Code};
+use manga_compiler::paths;
+use anyhow::{Context, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use walkdir::WalkDir;
+
+/// How much older than the book its copy on the device may look.
+const MTIME_SLACK: Duration = Duration::from_secs(2);
+
+/// Formats `convert` writes that a device may open instead of CBZ, the one to suggest first.
+const CONVERTED: &[&str] = &["pdf", "epub"];
+
+#[derive(clap::Args, Debug)]
+pub struct SyncArgs {
+    /// Archives, or folders searched recursively for them
+    #[arg(required = true)]
+    pub sources: Vec<PathBuf>,
+    /// Device profile to use instead of detecting one: kindle-koreader, kindle, kobo, a KCC id or
+    /// a profile from devices.toml
+    #[arg(long, value_name = "NAME")]
+    pub device: Option<String>,
+    /// Mount point of the device, for readers that are not found automatically
+    #[arg(long, value_name = "DIR")]
+    pub mount: Option<PathBuf>,
+    /// Only print what would be copied
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+pub fn run(args: &SyncArgs) -> Result<()> {
+    let (device, mount) = find_device(args)?;
+    let folder = mount.join(&device.folder);
+    println!("{}", t!("Syncing to {0} at {1}", device.name, folder.display()));
+
+    let (mut copied, mut present, mut failed) = (0, 0, 0);
+    let mut unsupported = Vec::new();
+    for (path, rel) in find_books(&args.sources)? {
+        if !device.accepts(&path) {
+            unsupported.push(rel);
+            continue;
+        }
+        let dest = folder.join(&rel);
+        if is_present(&path, &dest) {
+            present += 1;
+            continue;
+        }
+        if args.dry_run {
+            println!("{}", t!("Would copy {0}", rel.display()));
+            copied += 1;
+            continue;
+        }
+        match copy(&path, &dest) {
+            Ok(()) => {
+                println!("{}", style::out(Tone::Success, t!("Copied {0}", rel.display())));
+                copied += 1;
+            }
+            Err(e) => {
+                eprintln!("{}", style::err(Tone::Error, t!("Failed {0}: {1}", rel.display(), format!("{:#}", e))));
+                failed += 1;
+            }
+        }
+    }
+
+    if !unsupported.is_empty() {
+        eprintln!(
+            "{}",
+            style::err(Tone::Warning, t!("{0} cannot open these files, skipped:", device.name))
+        );
+        for rel in &unsupported {
+            eprintln!("  {}", style::err(Tone::Dim, format!("- {}", rel.display())));
+        }
+    }
+    let copied = if args.dry_run {
+        t!("Would copy {0} books, {1} already on the device", copied, present)
+    } else {
+        t!("Copied {0} books, {1} already on the device", copied, present)
+    };
+    println!("{}", copied);
+    if failed > 0 {
+        return Err(exit::fail(Code::Write, format!("{} books could not be copied", failed)));
+    }
+    if !unsupported.is_empty() {
+        let mut message = format!("{} cannot open {} of the books", device.name, unsupported.len());
+        let cbz = unsupported.iter().any(|rel| rel.extension().is_some_and(|e| e.eq_ignore_ascii_case("cbz")));
+        let target = CONVERTED.iter().find(|to| device.formats.iter().any(|f| f.eq_ignore_ascii_case(to)));
+        if let Some(to) = target.filter(|_| cbz) {
+            message += &format!(", convert the CBZ ones first with `manga-compiler convert --to {}`", to);
+        }
+        return Err(exit::fail(Code::Write, message));
+    }
+    Ok(())
+}
+
+fn find_device(args: &SyncArgs) -> Result<(Device, PathBuf)> {
+    let all = Device::all()?;
+    let profiles = match &args.device {
+        Some(name) => vec![Device::find(&all, name)?],
+        None => all,
+    };
+    if let Some(mount) = &args.mount {
+        if !mount.is_dir() {
+            return Err(exit::fail(Code::Usage, format!("{} is not a directory", mount.display())));
+        }
+        let device = match profiles.iter().find(|d| d.matches(mount)) {
+            Some(d) => d.clone(),
+            // An explicit profile wins over the markers, which a fresh device may lack.
+            None if args.device.is_some() => profiles[0].clone(),
+            None => {
+                return Err(exit::fail(
+                    Code::Usage,
+                    format!("{} does not look like a Kindle or Kobo, pass --device", mount.display()),
+                ));
+            }
+        };
+        return Ok((device, mount.clone()));
+    }
+
+    let mut found = device::detect(&profiles);
+    match found.len() {
+        0 => Err(exit::fail(Code::Usage, "No mounted e-reader found, connect one or pass --mount")),
+        1 => Ok(found.remove(0)),
+        _ => {
+            let list: Vec<String> = found.iter().map(|(d, m)| format!("{} at {}", d.name, m.display())).collect();
+            Err(exit::fail(Code::Usage, format!("Several e-readers found ({}), pass --mount", list.join(", "))))
+        }
+    }
+}
+
+/// Every file below the sources with its path relative to the source folder, so series
+/// folders are kept on the device.
+fn find_books(sources: &[PathBuf]) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut books = Vec::new();
+    for source in sources {
+        if source.is_file() {
+            let name = source.file_name().context("Source has no file name")?;
+            books.push((source.clone(), PathBuf::from(name)));
+            continue;
+        }
+        for entry in WalkDir::new(source).sort_by_file_name() {
+            let entry = entry.with_context(|| format!("Failed to read {}", source.display()))?;
+            let is_book = entry.path().extension().is_some_and(|e| {
+                ["cbz", "epub", "kepub", "pdf", "azw3", "mobi"].iter().any(|x| e.eq_ignore_ascii_case(x))
+            });
+            if entry.file_type().is_file() && is_book {
+                let rel = entry.path().strip_prefix(source).unwrap().to_path_buf();
+                books.push((entry.into_path(), rel));
+            }
+        }
+    }
+    Ok(books)
+}
+
+/// A book counts as present when a file of the same name and size is already there and was
+/// written no earlier than the book, so one rebuilt to the same size is copied again.
+fn is_present(src: &Path, dest: &Path) -> bool {
+    let (Ok(a), Ok(b)) = (fs::metadata(src), fs::metadata(dest)) else { return false };
+    let newer = match (a.modified(), b.modified()) {
+        // FAT, which most readers use, keeps times to two seconds.
+        (Ok(a), Ok(b)) => b + MTIME_SLACK >= a,
+        _ => false,
+    };
+    a.len() == b.len() && newer
+}
+
+/// Copies through a temporary name, so an unplugged device never holds a truncated book.
+fn copy(src: &Path, dest: &Path) -> Result<()> {
+    if let Some(dir) = dest.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    paths::write_atomic(dest, |part| {
+        fs::copy(src, part).with_context(|| format!("Failed to write {}", part.display()))?;
+        Ok(())
+    })
+}
@@ -0,0 +1,69 @@
+use super::i18n::t;
+use manga_compiler::archive::{self, Replacement};
+use manga_compiler::exit::{self, Code};
+use anyhow::{Context, Result};
+use std::{fs, path::PathBuf};
+
+#[derive(clap::Args, Debug)]
+pub struct UpdateArgs {
+    /// Archive whose pages are replaced
+    pub archive: PathBuf,
+    /// Page number and the image replacing it, e.g. `--page 5=fixed.png`; may be repeated
+    #[arg(long = "page", value_name = "N=FILE", required = true, value_parser = parse_page)]
+    pub pages: Vec<(usize, PathBuf)>,
+    /// Only print which entries would be replaced
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+fn parse_page(s: &str) -> Result<(usize, PathBuf), String> {
+    let (page, file) = s.split_once('=').ok_or("expected N=FILE")?;
+    match page.trim().parse() {
+        Ok(page) if page > 0 && !file.is_empty() => Ok((page, PathBuf::from(file))),
+        _ => Err(format!("{:?} is not a page number followed by a file", s)),
+    }
+}
+
+pub fn run(args: &UpdateArgs) -> Result<()> {
+    let zip = archive::open(&args.archive)?;
+    let names = archive::page_names(&zip);
+    let mut replacements: Vec<Replacement> = Vec::new();
+    for (page, file) in &args.pages {
+        let Some(entry) = names.get(page - 1) else {
+            let message = format!("{} has no page {}, it has {}", args.archive.display(), page, names.len());
+            return Err(exit::fail(Code::Usage, message));
+        };
+        if replacements.iter().any(|r| r.entry == *entry) {
+            return Err(exit::fail(Code::Usage, format!("Page {} is given twice", page)));
+        }
+        let data = fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+        image::load_from_memory(&data).with_context(|| format!("{} is not a valid image", file.display()))?;
+        // The page keeps its name, with the extension of the new file.
+        let ext = file.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+        let stem = entry.rsplit_once('.').map_or(entry.as_str(), |(stem, _)| stem);
+        let known = archive::IMAGE_EXTENSIONS.contains(&ext.as_str());
+        let name = if known { format!("{}.{}", stem, ext) } else { entry.clone() };
+        if name != *entry && zip.file_names().any(|n| n == name) {
+            let message = format!("Replacing page {} would give it the name of {}", page, name);
+            return Err(exit::fail(Code::Usage, message));
+        }
+        let modified = fs::metadata(file).and_then(|m| m.modified()).ok();
+        replacements.push(Replacement { entry: entry.clone(), name, data, modified });
+    }
+    drop(zip);
+
+    for (replacement, (page, file)) in replacements.iter().zip(&args.pages) {
+        let line = if replacement.name == replacement.entry {
+            t!("Page {0} ({1}): {2}", page, replacement.entry, file.display())
+        } else {
+            t!("Page {0} ({1}): {2} as {3}", page, replacement.entry, file.display(), replacement.name)
+        };
+        println!("{}", line);
+    }
+    if args.dry_run {
+        return Ok(());
+    }
+    archive::replace(&args.archive, &replacements)?;
+    println!("{}", t!("Updated {0} pages in {1}", replacements.len(), args.archive.display()));
+    Ok(())
+}
@@ -0,0 +1,182 @@
+use super::i18n::t;
+use super::style::{self, Tone};
+use manga_compiler::exit::{self, Code};
+use manga_compiler::paths;
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+use ureq::{Agent, Error, RequestBuilder};
+
+const TOKEN_VAR: &str = "MANGA_COMPILER_KOMGA_TOKEN";
+const ATTEMPTS: u32 = 4;
+/// How long the scan may take before the book must show up in the library.
+const SCAN_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Target {
+    Komga,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct UploadOpts {
+    /// Make a media server pick up each finished archive
+    #[arg(long, value_enum, requires_all = ["server", "library"])]
+    pub upload: Option<Target>,
+    /// Base URL of the server, e.g. `http://nas:25600`
+    #[arg(long, value_name = "URL")]
+    pub server: Option<String>,
+    /// Id of the library the archives are written into
+    #[arg(long, value_name = "ID")]
+    pub library: Option<String>,
+    /// Folder of the library on the server, e.g. a mounted share, to copy each archive into
+    /// before the scan; without it the archive must already be below the library's root
+    #[arg(long, value_name = "DIR", requires = "upload")]
+    pub library_path: Option<PathBuf>,
+    /// API key; defaults to MANGA_COMPILER_KOMGA_TOKEN
+    #[arg(long, value_name = "TOKEN")]
+    pub token: Option<String>,
+}
+
+/// Komga's API cannot receive files, a book only enters a library by being scanned from its
+/// root folder. So the archive is copied into `--library-path` (a share mounted here) unless it
+/// is already there, and the upload asks for a scan, then polls until the book is listed.
+pub fn run(opts: &UploadOpts, archive: &Path) -> Result<()> {
+    let (Some(Target::Komga), Some(server), Some(library)) = (opts.upload, &opts.server, &opts.library) else {
+        return Ok(());
+    };
+    if let Some(dir) = &opts.library_path {
+        copy(archive, dir)?;
+    }
+    let token = opts.token.clone().or_else(|| env::var(TOKEN_VAR).ok());
+    let komga = Komga { agent: Agent::new_with_defaults(), server: server.trim_end_matches('/'), token };
+    let name = archive.file_stem().unwrap_or_default().to_string_lossy();
+    let file = archive.file_name().unwrap_or_default().to_string_lossy();
+
+    komga.send(&format!("scan library {}", library), || {
+        komga.with_token(komga.agent.post(komga.url(&format!("libraries/{}/scan", library)))).send_empty()
+    })?;
+    let mut waited = Duration::ZERO;
+    loop {
+        let response = komga.send("list books", || {
+            komga
+                .with_token(komga.agent.get(komga.url("books")))
+                .query("library_id", library)
+                .query("search", &*name)
+                .query("size", "100")
+                .call()
+        })?;
+        let listed = response["content"].as_array().into_iter().flatten().any(|book| {
+            book["name"].as_str() == Some(&name) || book["url"].as_str().is_some_and(|u| u.ends_with(&*file))
+        });
+        if listed {
+            println!("{}", style::out(Tone::Success, t!("{0} is available in Komga library {1}", file, library)));
+            return Ok(());
+        }
+        if waited >= SCAN_TIMEOUT {
+            return Err(exit::fail(
+                Code::Write,
+                format!(
+                    "Komga did not list {} after scanning library {}; is --library-path the library's root?",
+                    file, library
+                ),
+            ));
+        }
+        thread::sleep(Duration::from_secs(2));
+        waited += Duration::from_secs(2);
+    }
+}
+
+/// Copies `archive` into `dir` through a temporary name, so that a scan never picks up half a
+/// book, retrying failures with a growing delay as shares drop out. An archive already written
+/// into `dir` stays as it is.
+fn copy(archive: &Path, dir: &Path) -> Result<()> {
+    let dest = dir.join(archive.file_name().unwrap_or_default());
+    if fs::canonicalize(archive).ok().zip(fs::canonicalize(&dest).ok()).is_some_and(|(a, b)| a == b) {
+        return Ok(());
+    }
+    let mut delay = Duration::from_secs(1);
+    let mut attempt = 1;
+    loop {
+        let copied = fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))
+            .and_then(|()| {
+                paths::write_atomic(&dest, |part| {
+                    fs::copy(archive, part).with_context(|| format!("Failed to write {}", part.display()))
+                })
+            });
+        let error = match copied {
+            Ok(_) => return Ok(()),
+            Err(e) => e,
+        };
+        if attempt == ATTEMPTS {
+            return Err(exit::fail(Code::Write, format!("{:#}", error)));
+        }
+        eprintln!("{}", style::err(Tone::Warning, format!("{:#}, retrying in {}s", error, delay.as_secs())));
+        thread::sleep(delay);
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+struct Komga<'a> {
+    agent: Agent,
+    server: &'a str,
+    token: Option<String>,
+}
+
+impl Komga<'_> {
+    fn url(&self, endpoint: &str) -> String {
+        format!("{}/api/v1/{}", self.server, endpoint)
+    }
+
+    fn with_token<B>(&self, builder: RequestBuilder<B>) -> RequestBuilder<B> {
+        match &self.token {
+            Some(token) => builder.header("X-API-Key", token),
+            None => builder,
+        }
+    }
+
+    /// Retries connection problems and server errors with a growing delay; the JSON body, if
+    /// any, is returned.
+    fn send(
+        &self,
+        what: &str,
+        call: impl Fn() -> Result<ureq::http::Response<ureq::Body>, Error>,
+    ) -> Result<Value> {
+        let mut delay = Duration::from_secs(1);
+        let mut attempt = 1;
+        loop {
+            let error = match call() {
+                Ok(mut response) => {
+                    let body = response.body_mut().read_to_string().unwrap_or_default();
+                    return Ok(serde_json::from_str(&body).unwrap_or(Value::Null));
+                }
+                Err(Error::StatusCode(401 | 403)) => {
+                    return Err(exit::fail(
+                        Code::Write,
+                        format!("Komga rejected the credentials to {}, check --token or {}", what, TOKEN_VAR),
+                    ));
+                }
+                Err(Error::StatusCode(status)) if status < 500 => {
+                    return Err(exit::fail(Code::Write, format!("Komga refused to {}: HTTP {}", what, status)));
+                }
+                Err(e) => e,
+            };
+            if attempt == ATTEMPTS {
+                return Err(exit::fail(Code::Write, format!("Failed to {} on Komga: {}", what, error)));
+            }
+            eprintln!(
+                "{}",
+                style::err(Tone::Warning, format!("Failed to {} ({}), retrying in {}s", what, error, delay.as_secs()))
+            );
+            thread::sleep(delay);
+            delay *= 2;
+            attempt += 1;
+        }
+    }
+}
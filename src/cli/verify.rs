@@ -0,0 +1,91 @@
+use super::i18n::t;
+use super::index::{self, Index};
+use super::style::{self, Tone};
+use manga_compiler::archive;
+use manga_compiler::exit::{self, Code};
+use anyhow::{Context, Result};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+#[derive(clap::Args, Debug)]
+pub struct VerifyArgs {
+    #[arg(required = true)]
+    pub archives: Vec<PathBuf>,
+    /// Also check that each archive is still the one this SQLite index recorded; needs the
+    /// sqlite3 command line shell, 3.33 or later, on PATH
+    #[arg(long, value_name = "PATH")]
+    pub index: Option<PathBuf>,
+}
+
+pub fn run(args: &VerifyArgs) -> Result<()> {
+    let index = args.index.as_deref().map(Index::open).transpose()?;
+    let mut failed = 0;
+    for path in &args.archives {
+        let checked = archive::verify(path).and_then(|mut problems| {
+            if let Some(index) = &index {
+                problems.extend(against_index(index, path)?);
+            }
+            Ok(problems)
+        });
+        match checked {
+            Ok(problems) if problems.is_empty() => {
+                println!("{}", style::out(Tone::Success, t!("Verified {0}", path.display())));
+            }
+            Ok(problems) => {
+                failed += 1;
+                println!("{}", style::out(Tone::Error, t!("{0} failed verification:", path.display())));
+                for p in problems {
+                    println!("  - {}", p);
+                }
+            }
+            Err(e) => {
+                failed += 1;
+                println!("{}", style::out(Tone::Error, t!("Failed {0}: {1}", path.display(), format!("{:#}", e))));
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(exit::fail(
+            Code::Validation,
+            format!("{} of {} archives failed verification", failed, args.archives.len()),
+        ));
+    }
+    Ok(())
+}
+
+/// How the archive at `path` differs from what `index` recorded of it.
+fn against_index(index: &Index, path: &Path) -> Result<Vec<String>> {
+    let Some(record) = index.book(path)? else {
+        return Ok(vec!["not in the index".to_string()]);
+    };
+    let sha256 = index::sha256_of(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if sha256 == record.sha256 {
+        return Ok(Vec::new());
+    }
+    let mut zip = archive::open(path)?;
+    let mut current: HashMap<String, (u64, u32)> = HashMap::new();
+    for name in archive::page_names(&zip) {
+        let entry = zip.by_name(&name)?;
+        current.insert(name, (entry.size(), entry.crc32()));
+    }
+    let mut problems = Vec::new();
+    for page in index.pages(&record)? {
+        match current.remove(&page.name) {
+            None => problems.push(format!("{} is missing since it was indexed", page.name)),
+            Some(found) if found != (page.size, page.crc32) => {
+                problems.push(format!("{} changed since it was indexed", page.name));
+            }
+            Some(_) => {}
+        }
+    }
+    let mut added: Vec<String> = current.into_keys().collect();
+    added.sort();
+    problems.extend(added.into_iter().map(|name| format!("{} was added since it was indexed", name)));
+    if problems.is_empty() {
+        problems.push("changed since it was indexed, though its pages did not".to_string());
+    }
+    problems.insert(0, format!("differs from the archive indexed at {}", record.compiled_at));
+    Ok(problems)
+}
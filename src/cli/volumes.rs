@@ -0,0 +1,147 @@
+use super::build::{self, CompileOpts};
+use super::i18n::{self, t};
+use super::index;
+use super::log;
+use super::merge::TitlePageOpts;
+use super::progress::Bar;
+use super::scratch::Scratch;
+use super::style::{self, Tone};
+use super::upload;
+use manga_compiler::exit::{self, Code};
+use manga_compiler::summary::{BatchSummary, Summary};
+use manga_compiler::volumes::{VOLUMES_FILE, VolumeMap};
+use manga_compiler::{Book, Compiler, Config, MissingPolicy, archive};
+use anyhow::{Context, Result};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+use walkdir::WalkDir;
+
+#[derive(clap::Args, Debug)]
+pub struct VolumesArgs {
+    /// Series folder whose subfolders are chapters
+    pub folder: PathBuf,
+    /// Chapter to volume map, defaults to volumes.toml in the series folder
+    #[arg(long, value_name = "FILE")]
+    pub map: Option<PathBuf>,
+    /// Only print which chapters go into which volume
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Print the run summary as JSON instead of text
+    #[arg(long)]
+    pub json: bool,
+    #[command(flatten)]
+    pub titles: TitlePageOpts,
+    #[command(flatten)]
+    pub opts: CompileOpts,
+}
+
+pub fn run(args: &VolumesArgs) -> Result<()> {
+    let started = Instant::now();
+    let map_path = args.map.clone().unwrap_or_else(|| args.folder.join(VOLUMES_FILE));
+    let map = VolumeMap::load(&map_path)?;
+
+    let mut volumes: BTreeMap<u32, Vec<(f64, PathBuf)>> = BTreeMap::new();
+    let mut unmapped = Vec::new();
+    for entry in WalkDir::new(&args.folder).min_depth(1).max_depth(1) {
+        let entry = entry.with_context(|| format!("Failed to read {}", args.folder.display()))?;
+        if !entry.file_type().is_dir() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        match archive::chapter_value(&name).and_then(|c| Some((c, map.volume_of(c)?))) {
+            Some((chapter, volume)) => volumes.entry(volume).or_default().push((chapter, entry.into_path())),
+            None => unmapped.push(name),
+        }
+    }
+    for chapters in volumes.values_mut() {
+        chapters.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+    if !unmapped.is_empty() {
+        unmapped.sort_by(|a, b| archive::natural_cmp(a, b));
+        eprintln!("{}", style::err(Tone::Warning, t!("Folders outside every range of {0}:", VOLUMES_FILE)));
+        for name in &unmapped {
+            eprintln!("  {}", style::err(Tone::Dim, format!("- {}", name)));
+        }
+    }
+    if volumes.is_empty() {
+        return Err(exit::fail(Code::NoPages, format!("No chapter folders of {} are mapped", args.folder.display())));
+    }
+
+    if args.dry_run {
+        for (volume, chapters) in &volumes {
+            let names: Vec<String> = chapters.iter().map(|(_, p)| file_name(p)).collect();
+            println!("{}", t!("Volume {0}: {1}", volume, names.join(", ")));
+        }
+        return Ok(());
+    }
+
+    let mut summary = BatchSummary::default();
+    for (&volume, chapters) in &volumes {
+        let result = compile_volume(&args.folder, volume, chapters, args);
+        match &result {
+            Ok(s) if !args.json => build::report(s, false),
+            Ok(_) => {}
+            Err(e) => {
+                let message = t!("Failed volume {0}: {1}", volume, format!("{:#}", e));
+                eprintln!("{}", style::err(Tone::Error, message));
+            }
+        }
+        summary.push(args.folder.clone(), &result);
+    }
+    summary.total.set_elapsed(started.elapsed());
+
+    if args.json {
+        println!("{}", serde_json::to_string(&summary).unwrap());
+    } else {
+        println!("{}", t!("{0} of {1} volumes compiled", summary.compiled, volumes.len()));
+        println!("  {}", i18n::stats(&summary.total));
+    }
+    if summary.failed > 0 {
+        return Err(exit::fail(Code::Batch, format!("{} of {} volumes failed", summary.failed, volumes.len())));
+    }
+    Ok(())
+}
+
+/// Discovers every chapter on its own, so each keeps its folder config and page pattern, then
+/// writes them as one book of the series folder.
+fn compile_volume(folder: &Path, volume: u32, chapters: &[(f64, PathBuf)], args: &VolumesArgs) -> Result<Summary> {
+    let opts = &args.opts;
+    // `volumes .` still needs the folder's name for the series.
+    let folder = &folder.canonicalize().with_context(|| format!("Failed to read {}", folder.display()))?;
+    let mut parts = Vec::new();
+    for (_, chapter) in chapters {
+        let part = Compiler::new(chapter).config(opts.to_config()).discover()?;
+        if !part.missing.is_empty() {
+            let message = format!("{}: missing page numbers {:?}", file_name(chapter), part.missing);
+            match part.config.missing.unwrap_or_default() {
+                MissingPolicy::Fail => return Err(exit::fail(Code::MissingPages, message)),
+                MissingPolicy::Warn => eprintln!("{}", style::err(Tone::Warning, t!("Warning: {0}", message))),
+                // The volume is numbered anew anyway.
+                MissingPolicy::Compact => {}
+            }
+        }
+        parts.push(part);
+    }
+
+    let mut config = opts.to_config();
+    let series = config.series.clone().unwrap_or_else(|| file_name(folder));
+    config.title = Some(format!("{} Vol. {:02}", series, volume));
+    config.series = Some(series);
+    config.volume = Some(volume);
+    let compiler = Compiler::new(folder).config(config);
+    let mut book = Book::merge(folder.to_path_buf(), compiler.effective_config()?, parts);
+    let scratch = Scratch::new("volumes")?;
+    args.titles.insert(&mut book, scratch.path())?;
+    log::book(&book);
+    let summary = compiler.compile_with(&book, &mut Bar::new())?;
+    index::record(opts, &summary, Config::default())?;
+    upload::run(&opts.upload, &summary.output)?;
+    Ok(summary)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+}
@@ -0,0 +1,85 @@
+use super::i18n::t;
+use super::log;
+use super::style::{self, Tone};
+use anyhow::Result;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+use walkdir::WalkDir;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct WatchOpts {
+    /// Keep running and recompile books whenever their source files change
+    #[arg(short, long)]
+    pub watch: bool,
+    /// Seconds without further changes before a changed book is recompiled
+    #[arg(long, value_name = "SECS", default_value_t = 2.0)]
+    pub debounce: f64,
+}
+
+type Snapshot = BTreeMap<PathBuf, (SystemTime, u64)>;
+
+fn snapshot(folder: &Path) -> Snapshot {
+    WalkDir::new(folder)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let meta = e.metadata().ok()?;
+            Some((e.into_path(), (meta.modified().ok()?, meta.len())))
+        })
+        .collect()
+}
+
+/// Polls the folders returned by `books` forever, calling `compile` for each one that
+/// changed once it has been quiet for the debounce interval. `books` is re-evaluated on
+/// every poll so folders created while watching are picked up.
+pub fn run(
+    opts: &WatchOpts,
+    books: impl Fn() -> Result<Vec<PathBuf>>,
+    compile: impl Fn(&Path),
+) -> Result<()> {
+    let debounce = Duration::from_secs_f64(opts.debounce.max(0.0));
+    let mut known: HashMap<PathBuf, Snapshot> = HashMap::new();
+    for folder in books()? {
+        known.insert(folder.clone(), snapshot(&folder));
+    }
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    eprintln!("{}", t!("Watching {0} folders for changes, press Ctrl-C to stop", known.len()));
+    log::line(format_args!("Watching {} folders", known.len()));
+
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let current = match books() {
+            Ok(b) => b,
+            Err(e) => {
+                eprintln!("{}", style::err(Tone::Warning, t!("Warning: {0}", format!("{:#}", e))));
+                continue;
+            }
+        };
+        known.retain(|folder, _| current.contains(folder));
+        for folder in current {
+            let snap = snapshot(&folder);
+            if known.get(&folder) != Some(&snap) {
+                known.insert(folder.clone(), snap);
+                pending.insert(folder, Instant::now());
+            }
+        }
+
+        let ready: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, changed)| changed.elapsed() >= debounce)
+            .map(|(folder, _)| folder.clone())
+            .collect();
+        for folder in ready {
+            pending.remove(&folder);
+            log::line(format_args!("{} changed, compiling it again", folder.display()));
+            compile(&folder);
+        }
+    }
+}
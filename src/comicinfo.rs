@@ -0,0 +1,150 @@
+use crate::config::Direction;
+use regex::Regex;
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct ComicInfo {
+    pub title: Option<String>,
+    pub series: Option<String>,
+    pub writer: Option<String>,
+    pub volume: Option<u32>,
+    /// The chapter number.
+    pub number: Option<f64>,
+    /// Books in the whole series.
+    pub count: Option<u32>,
+    pub page_count: Option<usize>,
+    /// Free text, here why the pages were renumbered.
+    pub notes: Option<String>,
+    pub direction: Option<Direction>,
+    /// Page index and text of each `<Page Bookmark="...">`, marking where a chapter starts.
+    pub bookmarks: Vec<(usize, String)>,
+    /// Page index and `Type` of the pages that are not story pages, e.g. `FrontCover`.
+    pub page_types: Vec<(usize, String)>,
+}
+
+impl ComicInfo {
+    /// Reads the fields this crate writes back out of a ComicInfo.xml; anything else is ignored.
+    pub fn parse(xml: &str) -> ComicInfo {
+        let direction = element(xml, "Manga").map(|m| match m.as_str() {
+            "YesAndRightToLeft" => Direction::Rtl,
+            _ => Direction::Ltr,
+        });
+        ComicInfo {
+            title: element(xml, "Title"),
+            series: element(xml, "Series"),
+            writer: element(xml, "Writer"),
+            volume: element(xml, "Volume").and_then(|v| v.parse().ok()),
+            number: element(xml, "Number").and_then(|v| v.parse().ok()),
+            count: element(xml, "Count").and_then(|v| v.parse().ok()),
+            page_count: element(xml, "PageCount").and_then(|v| v.parse().ok()),
+            notes: element(xml, "Notes"),
+            direction,
+            bookmarks: page_attributes(xml, "Bookmark"),
+            page_types: page_attributes(xml, "Type"),
+        }
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <ComicInfo xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+             xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\">\n",
+        );
+        if let Some(title) = &self.title {
+            push_element(&mut xml, "Title", title);
+        }
+        if let Some(series) = &self.series {
+            push_element(&mut xml, "Series", series);
+        }
+        if let Some(writer) = &self.writer {
+            push_element(&mut xml, "Writer", writer);
+        }
+        if let Some(number) = self.number {
+            push_element(&mut xml, "Number", &number.to_string());
+        }
+        if let Some(count) = self.count {
+            push_element(&mut xml, "Count", &count.to_string());
+        }
+        if let Some(volume) = self.volume {
+            push_element(&mut xml, "Volume", &volume.to_string());
+        }
+        if let Some(notes) = &self.notes {
+            push_element(&mut xml, "Notes", notes);
+        }
+        if let Some(count) = self.page_count {
+            push_element(&mut xml, "PageCount", &count.to_string());
+        }
+        if let Some(direction) = self.direction {
+            let manga = match direction {
+                Direction::Rtl => "YesAndRightToLeft",
+                Direction::Ltr => "No",
+            };
+            push_element(&mut xml, "Manga", manga);
+        }
+        let mut pages: Vec<usize> = self.bookmarks.iter().chain(&self.page_types).map(|(image, _)| *image).collect();
+        pages.sort_unstable();
+        pages.dedup();
+        if !pages.is_empty() {
+            xml.push_str("  <Pages>\n");
+            for image in pages {
+                xml.push_str(&format!("    <Page Image=\"{}\"", image));
+                if let Some((_, kind)) = self.page_types.iter().find(|(i, _)| *i == image) {
+                    xml.push_str(&format!(" Type=\"{}\"", escape(kind)));
+                }
+                if let Some((_, bookmark)) = self.bookmarks.iter().find(|(i, _)| *i == image) {
+                    xml.push_str(&format!(" Bookmark=\"{}\"", escape(bookmark)));
+                }
+                xml.push_str("/>\n");
+            }
+            xml.push_str("  </Pages>\n");
+        }
+        xml.push_str("</ComicInfo>\n");
+        xml
+    }
+}
+
+fn push_element(xml: &mut String, name: &str, value: &str) {
+    xml.push_str(&format!("  <{name}>{}</{name}>\n", escape(value)));
+}
+
+/// Authors as listed in `Writer`, which separates several with commas.
+pub fn authors(writer: &str) -> impl Iterator<Item = &str> {
+    writer.split(',').map(str::trim).filter(|a| !a.is_empty())
+}
+
+fn element(xml: &str, name: &str) -> Option<String> {
+    let re = Regex::new(&format!(r"(?s)<{name}>(.*?)</{name}>")).unwrap();
+    let value = unescape(re.captures(xml)?[1].trim());
+    (!value.is_empty()).then_some(value)
+}
+
+/// Page index and the value of `name` for each `<Page>` that has one.
+fn page_attributes(xml: &str, name: &str) -> Vec<(usize, String)> {
+    let page = Regex::new(r"<Page\b[^>]*>").unwrap();
+    let attribute = |tag: &str, name: &str| {
+        let re = Regex::new(&format!(r#"\b{name}="([^"]*)""#)).unwrap();
+        re.captures(tag).map(|c| unescape(&c[1]))
+    };
+    page.find_iter(xml)
+        .filter_map(|tag| {
+            let image = attribute(tag.as_str(), "Image")?.parse().ok()?;
+            Some((image, attribute(tag.as_str(), name).filter(|b| !b.is_empty())?))
+        })
+        .collect()
+}
+
+pub fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,1286 @@
+use crate::archive;
+use crate::comicinfo::ComicInfo;
+use crate::config::{
+    Compression, Config, DedupeExact, Direction, Format, MissingPolicy, OnFilterError, Preset, Sort,
+};
+use crate::cover;
+use crate::device::{Device, PageSettings};
+use crate::discover::{
+    Options, Page, PageRanges, PageSet, chapter_label, describe_gaps, discover_in, discover_pages, file_names,
+};
+use crate::error::{CompileError, Result};
+use crate::filter::Filter;
+use crate::hooks;
+use crate::levels::{self, Histogram};
+use crate::names;
+use crate::opf;
+use crate::paths;
+use crate::progress::{ProgressObserver, Warning};
+use crate::quality::{self, QualityReport};
+use crate::sign;
+use crate::source::Source;
+use crate::summary::{Stats, Summary};
+use crate::template;
+use crate::titlepage::{self, TitleStyle};
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    hash::{DefaultHasher, Hash, Hasher},
+    fs::{self, File},
+    io::{self, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+use walkdir::WalkDir;
+use zip::write::FileOptions;
+
+/// Compiles one source folder into an archive.
+///
+/// Settings given to the builder take precedence over the folder's `.manga-compiler.toml`,
+/// the global config and `MANGA_COMPILER_*` variables, unless [`Compiler::use_config_files`]
+/// turns those layers off.
+///
+/// ```no_run
+/// use manga_compiler::{Compiler, Format, MissingPolicy};
+///
+/// let summary = Compiler::new("Yotsuba v01")
+///     .title("Yotsuba&! Vol. 1")
+///     .format(Format::Cbz)
+///     .missing_policy(MissingPolicy::Warn)
+///     .run()?;
+/// println!("wrote {}", summary.output.display());
+/// # Ok::<(), manga_compiler::CompileError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Compiler {
+    folder: PathBuf,
+    output: Option<PathBuf>,
+    config: Config,
+    inherited: Config,
+    use_config_files: bool,
+    overwrite: bool,
+    append: bool,
+}
+
+impl Compiler {
+    pub fn new(folder: impl Into<PathBuf>) -> Self {
+        Compiler {
+            folder: folder.into(),
+            output: None,
+            config: Config::default(),
+            inherited: Config::default(),
+            use_config_files: true,
+            overwrite: true,
+            append: false,
+        }
+    }
+
+    /// Exact output path, bypassing the output directory and template.
+    pub fn output(mut self, path: impl Into<PathBuf>) -> Self {
+        self.output = Some(path.into());
+        self
+    }
+
+    /// Replaces all builder settings at once; unset fields still fall through to config files.
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Settings below the folder's config but above the global one, e.g. those a series folder
+    /// shares with its volumes.
+    pub fn inherit(mut self, config: Config) -> Self {
+        self.inherited = config;
+        self
+    }
+
+    pub fn use_config_files(mut self, enabled: bool) -> Self {
+        self.use_config_files = enabled;
+        self
+    }
+
+    /// With `false`, an existing output file fails the build with [`CompileError::OutputExists`].
+    pub fn overwrite(mut self, enabled: bool) -> Self {
+        self.overwrite = enabled;
+        self
+    }
+
+    /// Adds the pages an existing output archive lacks to it, keeping the entries it has as they
+    /// are, instead of writing it from scratch.
+    pub fn append(mut self, enabled: bool) -> Self {
+        self.append = enabled;
+        self
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.config.title = Some(title.into());
+        self
+    }
+
+    pub fn series(mut self, series: impl Into<String>) -> Self {
+        self.config.series = Some(series.into());
+        self
+    }
+
+    pub fn volume(mut self, volume: u32) -> Self {
+        self.config.volume = Some(volume);
+        self
+    }
+
+    pub fn chapter(mut self, chapter: f64) -> Self {
+        self.config.chapter = Some(chapter);
+        self
+    }
+
+    pub fn chapter_title(mut self, title: impl Into<String>) -> Self {
+        self.config.chapter_title = Some(title.into());
+        self
+    }
+
+    pub fn format(mut self, format: Format) -> Self {
+        self.config.format = Some(format);
+        self
+    }
+
+    /// Prepares the pages for a device profile, see [`Device`].
+    pub fn device(mut self, name: impl Into<String>) -> Self {
+        self.config.device = Some(name.into());
+        self
+    }
+
+    /// Picks defaults for a purpose, see [`Preset`].
+    pub fn preset(mut self, preset: Preset) -> Self {
+        self.config.preset = Some(preset);
+        self
+    }
+
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.config.compression = Some(compression);
+        self
+    }
+
+    pub fn missing_policy(mut self, policy: MissingPolicy) -> Self {
+        self.config.missing = Some(policy);
+        self
+    }
+
+    pub fn pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.config.pattern = Some(pattern.into());
+        self
+    }
+
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.config.direction = Some(direction);
+        self
+    }
+
+    pub fn extras_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.extras_dir = Some(dir.into());
+        self
+    }
+
+    pub fn output_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.output_dir = Some(dir.into());
+        self
+    }
+
+    pub fn output_template(mut self, template: impl Into<String>) -> Self {
+        self.config.output_template = Some(template.into());
+        self
+    }
+
+    pub fn folder(&self) -> &Path {
+        &self.folder
+    }
+
+    /// The builder settings layered over the config files.
+    pub fn effective_config(&self) -> Result<Config> {
+        if !self.use_config_files {
+            return Ok(self.config.clone().or(self.inherited.clone()).with_preset());
+        }
+        let folder = Config::load_folder(&self.folder).map_err(CompileError::config)?;
+        let global = Config::load_global().map_err(CompileError::config)?;
+        let env = Config::from_env().map_err(CompileError::config)?;
+        Ok(self.config.clone().or(folder).or(self.inherited.clone()).or(global).or(env).with_preset())
+    }
+
+    /// Finds the pages without writing anything.
+    pub fn discover(&self) -> Result<Book> {
+        let folder = &self.folder;
+        let config = self.effective_config()?;
+
+        let set = discover_pages(folder, &Options { pattern: config.pattern.clone(), sort: config.sort })?;
+        let mut extras = Vec::new();
+        if let Some(dir) = &config.extras_dir {
+            let dir = folder.join(dir);
+            for entry in WalkDir::new(&dir).min_depth(1).sort_by_file_name() {
+                let entry = entry.map_err(|e| CompileError::Io { path: dir.clone(), source: e.into() })?;
+                if entry.file_type().is_file() {
+                    let rel = entry.path().strip_prefix(folder).unwrap().to_string_lossy().replace('\\', "/");
+                    extras.push((rel, entry.into_path()));
+                }
+            }
+        }
+
+        let device = match &config.device {
+            Some(name) => Some(Device::all().and_then(|all| Device::find(&all, name)).map_err(CompileError::config)?),
+            None => None,
+        };
+        let mut book = Book::new(folder.to_path_buf(), config, set, extras, device, Vec::new());
+        book.find_identical(&|path| fs::read(path))?;
+        Ok(book)
+    }
+
+    /// Discovers and compiles in one go.
+    pub fn run(&self) -> Result<Summary> {
+        self.run_with(&mut ())
+    }
+
+    pub fn run_with(&self, observer: &mut dyn ProgressObserver) -> Result<Summary> {
+        let book = self.discover()?;
+        self.compile_with(&book, observer)
+    }
+
+    /// Writes the archive for a book returned by [`Compiler::discover`].
+    pub fn compile(&self, book: &Book) -> Result<Summary> {
+        self.compile_with(book, &mut ())
+    }
+
+    /// Like [`Compiler::compile`], reporting warnings and per-page progress to `observer`.
+    pub fn compile_with(&self, book: &Book, observer: &mut dyn ProgressObserver) -> Result<Summary> {
+        let started = Instant::now();
+        let mut stats = check(book, observer)?;
+        let out_path = book.out_path(self.output.as_deref())?;
+        if !self.overwrite && !self.append && out_path.exists() {
+            return Err(CompileError::OutputExists(out_path));
+        }
+        let mut env = book.hook_env(&out_path);
+        if let Some(hook) = &book.config.pre_hook {
+            hooks::run("pre", hook, &env)?;
+        }
+        let mut packing = book.packing(book.pages.len());
+        book.normalize_levels(&mut packing, &|path| fs::read(path))?;
+        let mut appended = None;
+        let mut tally = Tally { observer: &mut *observer, warnings: 0 };
+        let (bytes_in, bytes_out) = if self.append && out_path.is_file() {
+            let (added, bytes_in, bytes_out) = append_archive(&out_path, &book.entries(), &packing, &mut tally)?;
+            // Counts what this run packed, as for a new archive.
+            stats.pages = added;
+            appended = Some(added);
+            (bytes_in, bytes_out)
+        } else {
+            write_archive(&out_path, &book.entries(), &packing, &mut tally)?
+        };
+        stats.warnings += tally.warnings;
+        stats.set_bytes(bytes_in, bytes_out);
+        if book.config.opf.unwrap_or(false) {
+            opf::write_sidecar(&out_path, &book.metadata(book.pages.len()))?;
+        }
+        if let Some(layout) = book.config.layout {
+            layout.write_series_files(&out_path, book)?;
+        }
+        let cover = book.write_cover(&out_path)?;
+        let quality = book.quality(&|path| fs::read(path))?;
+        let mut signature = None;
+        if let Some(signer) = book.config.sign {
+            let path = sign::sign(signer, book.config.sign_key.as_deref(), &out_path)?;
+            env.push(("MANGA_COMPILER_SIGNATURE", path.display().to_string()));
+            signature = Some(path);
+        }
+        if let Some(hook) = &book.config.post_hook {
+            env.push(("MANGA_COMPILER_BYTES", bytes_out.to_string()));
+            hooks::run("post", hook, &env)?;
+        }
+        stats.set_elapsed(started.elapsed());
+        let summary = Summary { appended, signature, cover, quality, ..book.summary(out_path, stats) };
+        observer.finished(&summary);
+        Ok(summary)
+    }
+}
+
+/// Compiles the files of `source` into a zip written to `sink`, without touching the
+/// filesystem, the clock or the environment, so it also runs on `wasm32`. Config files, hooks
+/// and the filter command do not apply; the summary's output is the file name the archive would
+/// get.
+///
+/// ```
+/// use manga_compiler::{Config, compile_source, source::MemorySource};
+/// use std::io::Cursor;
+///
+/// let mut source = MemorySource::new("Vol 1");
+/// source.add("Vol 1-1.png", b"\x89PNG\r\n\x1a\n".to_vec());
+/// let (cbz, summary) = compile_source(&source, Config::default(), Cursor::new(Vec::new()), &mut ())?;
+/// assert_eq!(summary.output.to_str(), Some("Vol 1.cbz"));
+/// assert!(!cbz.into_inner().is_empty());
+/// # Ok::<(), manga_compiler::CompileError>(())
+/// ```
+pub fn compile_source<W: Write + Seek>(
+    source: &dyn Source,
+    config: Config,
+    sink: W,
+    observer: &mut dyn ProgressObserver,
+) -> Result<(W, Summary)> {
+    let config = config.with_preset();
+    let set = discover_in(source, &Options { pattern: config.pattern.clone(), sort: config.sort })?;
+    // Only the built-in profiles, reading devices.toml would touch the filesystem.
+    let device = match &config.device {
+        Some(name) => Some(Device::find(&Device::builtin(), name).map_err(CompileError::config)?),
+        None => None,
+    };
+    let mut book = Book::new(PathBuf::from(source.name()), config, set, Vec::new(), device, Vec::new());
+    book.find_identical(&|path| source.read(path))?;
+    let mut stats = check(&book, observer)?;
+    let out_path = book.out_path(None)?;
+    let mut packing = book.packing(book.pages.len());
+    packing.filter = None;
+    book.normalize_levels(&mut packing, &|path| source.read(path))?;
+    let mut tally = Tally { observer: &mut *observer, warnings: 0 };
+    let (sink, bytes_in, bytes_out) = write_zip(
+        sink,
+        &out_path,
+        &book.entries(),
+        &|path| Ok((source.read(path)?, None)),
+        None,
+        &packing,
+        &mut tally,
+    )?;
+    stats.warnings += tally.warnings;
+    stats.set_bytes(bytes_in, bytes_out);
+    let quality = book.quality(&|path| source.read(path))?;
+    let summary = Summary { quality, ..book.summary(out_path, stats) };
+    observer.finished(&summary);
+    Ok((sink, summary))
+}
+
+/// Passes everything on to `observer`, counting the warnings raised while an archive is written.
+struct Tally<'a> {
+    observer: &'a mut dyn ProgressObserver,
+    warnings: usize,
+}
+
+impl ProgressObserver for Tally<'_> {
+    fn started(&mut self, output: &Path, entries: usize) {
+        self.observer.started(output, entries);
+    }
+    fn page_started(&mut self, index: usize, name: &str) {
+        self.observer.page_started(index, name);
+    }
+    fn page_finished(&mut self, index: usize, name: &str) {
+        self.observer.page_finished(index, name);
+    }
+    fn bytes_written(&mut self, bytes: u64) {
+        self.observer.bytes_written(bytes);
+    }
+    fn warning(&mut self, warning: &Warning) {
+        self.warnings += 1;
+        self.observer.warning(warning);
+    }
+    fn finished(&mut self, summary: &Summary) {
+        self.observer.finished(summary);
+    }
+}
+
+/// Applies the missing page policy and reports the discovery warnings.
+fn check(book: &Book, observer: &mut dyn ProgressObserver) -> Result<Stats> {
+    let skipped = book.noise.len() + book.duplicates.len();
+    let mut stats = Stats { pages: book.pages.len(), skipped, ..Stats::default() };
+    if !book.noise.is_empty() {
+        stats.warnings += 1;
+        observer.warning(&Warning::IgnoredFiles(book.noise_names()));
+    }
+    if !book.duplicates.is_empty() {
+        stats.warnings += 1;
+        observer.warning(&Warning::DroppedDuplicates(file_names(&book.duplicates)));
+    }
+    if !book.identical.is_empty() {
+        stats.warnings += 1;
+        let name = |p: &PathBuf| p.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let pairs = book.identical.iter().map(|(copy, original)| (name(copy), name(original))).collect();
+        observer.warning(&Warning::IdenticalPages(pairs));
+    }
+
+    if book.pages.is_empty() {
+        let by_time = book.config.sort.is_some_and(|s| s != Sort::Number);
+        let mut shown = match book.config.pattern {
+            _ if by_time => book.pattern.clone(),
+            Some(_) => book.pattern.clone(),
+            None => format!("{}-<number>.<ext>", book.folder.file_name().unwrap().to_string_lossy()),
+        };
+        if book.config.pages.is_some() {
+            shown.push_str(" among the selected pages");
+        }
+        return Err(CompileError::NoPagesFound { pattern: shown });
+    }
+
+    if !book.missing.is_empty() {
+        match book.config.missing.unwrap_or_default() {
+            MissingPolicy::Fail => return Err(CompileError::MissingPages(book.missing.clone())),
+            MissingPolicy::Warn => {
+                stats.warnings += 1;
+                observer.warning(&Warning::MissingPages(book.missing.clone()));
+            }
+            MissingPolicy::Compact => {}
+        }
+    }
+    if !book.chapter_gaps.is_empty() {
+        match book.config.missing.unwrap_or_default() {
+            MissingPolicy::Fail => return Err(CompileError::MissingChapterPages(book.chapter_gaps.clone())),
+            MissingPolicy::Warn => {
+                stats.warnings += 1;
+                observer.warning(&Warning::MissingChapterPages(book.chapter_gaps.clone()));
+            }
+            MissingPolicy::Compact => {}
+        }
+    }
+    Ok(stats)
+}
+
+/// A source folder after page discovery, before anything is written.
+#[derive(Debug, Clone)]
+pub struct Book {
+    pub folder: PathBuf,
+    pub title: String,
+    pub config: Config,
+    pub pattern: String,
+    pub pages: Vec<Page>,
+    pub noise: Vec<PathBuf>,
+    /// See [`PageSet::duplicates`]; also the pages left out by [`DedupeExact::Drop`].
+    pub duplicates: Vec<PathBuf>,
+    /// Pages kept by [`DedupeExact::Warn`] that repeat an earlier page, each with that page.
+    pub identical: Vec<(PathBuf, PathBuf)>,
+    pub missing: Vec<u32>,
+    /// Per chapter, for pages named with chapter and page, see [`PageSet::chapter_gaps`].
+    pub chapter_gaps: Vec<(String, Vec<u32>)>,
+    pub extras: Vec<(String, PathBuf)>,
+    /// The profile named by `config.device`.
+    pub device: Option<Device>,
+    /// Where each chapter starts if the book was assembled by [`Book::merge`] or cut by
+    /// [`Book::split`], else empty.
+    pub chapters: Vec<ChapterMark>,
+    /// The pages were put in an order of their own by [`Book::reorder`].
+    pub reordered: bool,
+}
+
+/// The first page of a chapter within a book assembled from several folders.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChapterMark {
+    /// Index into [`Book::pages`].
+    pub first_page: usize,
+    pub title: String,
+}
+
+impl Book {
+    fn new(
+        folder: PathBuf,
+        mut config: Config,
+        set: PageSet,
+        extras: Vec<(String, PathBuf)>,
+        device: Option<Device>,
+        chapters: Vec<ChapterMark>,
+    ) -> Self {
+        let name = folder.file_name().unwrap().to_string_lossy().into_owned();
+        if config.layout.is_some() {
+            // Layouts group books by series, named after the folder holding the volume folders.
+            let parent = folder.parent().and_then(|p| p.file_name());
+            config.series = config.series.or_else(|| parent.map(|p| p.to_string_lossy().into_owned()));
+        }
+        if config.infer.unwrap_or(true) {
+            config.volume = config.volume.or_else(|| archive::volume_number(&name));
+            config.chapter = config.chapter.or_else(|| archive::chapter_number(&name));
+            config.chapter_title = config.chapter_title.or_else(|| archive::chapter_title(&name));
+        }
+        let title = config.title.clone().unwrap_or(name);
+        let mut book = Book {
+            folder,
+            title,
+            config,
+            pattern: set.pattern,
+            pages: set.pages,
+            noise: set.noise,
+            duplicates: set.duplicates,
+            identical: Vec::new(),
+            missing: set.missing,
+            chapter_gaps: set.chapter_gaps,
+            extras,
+            device,
+            chapters,
+            reordered: false,
+        };
+        if book.chapters.is_empty() && book.pages.iter().any(|p| p.chapter.is_some()) {
+            book.chapters = book.chapter_marks();
+        }
+        if let Some(ranges) = book.config.pages.clone() {
+            book.select(&ranges);
+        }
+        book
+    }
+
+    /// A chapter starting at each page whose name gives another chapter than the page before.
+    fn chapter_marks(&self) -> Vec<ChapterMark> {
+        let mut marks = Vec::new();
+        for (index, page) in self.pages.iter().enumerate() {
+            if index == 0 || self.pages[index - 1].chapter != page.chapter {
+                let title = page.chapter.map_or_else(|| self.title.clone(), |c| format!("Chapter {}", c));
+                marks.push(ChapterMark { first_page: index, title });
+            }
+        }
+        marks
+    }
+
+    /// Keeps the pages whose numbers are in `ranges`, and only the gaps among them.
+    fn select(&mut self, ranges: &PageRanges) {
+        let kept: Vec<bool> = self.pages.iter().map(|p| ranges.contains(p.number)).collect();
+        self.missing.retain(|&n| ranges.contains(n));
+        self.retain_pages(&kept);
+    }
+
+    /// Finds the pages byte for byte identical to an earlier page, reading them through `read`,
+    /// and reports or leaves them out as `config.dedupe_exact` says.
+    fn find_identical(&mut self, read: &dyn Fn(&Path) -> io::Result<Vec<u8>>) -> Result<()> {
+        let Some(mode) = self.config.dedupe_exact else { return Ok(()) };
+        // Pages with different contents by size and hash; a copy is only one once the bytes match.
+        let mut distinct: HashMap<(usize, u64), Vec<usize>> = HashMap::new();
+        let mut kept = Vec::new();
+        let mut identical = Vec::new();
+        for (index, page) in self.pages.iter().enumerate() {
+            let data = read(&page.path).map_err(CompileError::io(&page.path))?;
+            let mut hasher = DefaultHasher::new();
+            data.hash(&mut hasher);
+            let candidates = distinct.entry((data.len(), hasher.finish())).or_default();
+            let mut original = None;
+            for &other in candidates.iter() {
+                let path = &self.pages[other].path;
+                if read(path).map_err(CompileError::io(path))? == data {
+                    original = Some(other);
+                    break;
+                }
+            }
+            match original {
+                Some(other) => identical.push((page.path.clone(), self.pages[other].path.clone())),
+                None => candidates.push(index),
+            }
+            kept.push(original.is_none());
+        }
+        match mode {
+            DedupeExact::Warn => self.identical = identical,
+            DedupeExact::Drop => {
+                self.duplicates.extend(identical.into_iter().map(|(copy, _)| copy));
+                self.retain_pages(&kept);
+            }
+        }
+        Ok(())
+    }
+
+    /// Gives the pages of every chapter the levels that bring its black and white points to the
+    /// book's, reading them through `read`, when `config.normalize_levels` asks for it.
+    fn normalize_levels(&self, packing: &mut Packing, read: &dyn Fn(&Path) -> io::Result<Vec<u8>>) -> Result<()> {
+        if !self.config.normalize_levels.unwrap_or(false) || self.chapters.len() < 2 {
+            return Ok(());
+        }
+        let chapter_of = |index: usize| self.chapters.iter().rposition(|mark| mark.first_page <= index).unwrap_or(0);
+        let mut histograms: Vec<Histogram> = vec![[0; 256]; self.chapters.len()];
+        for (index, page) in self.pages.iter().enumerate() {
+            let data = read(&page.path).map_err(CompileError::io(&page.path))?;
+            levels::add_page(&mut histograms[chapter_of(index)], &data);
+        }
+        let matched = levels::match_chapters(&histograms);
+        for (index, (name, _)) in self.entries().into_iter().take(self.pages.len()).enumerate() {
+            if let Some(levels) = matched[chapter_of(index)] {
+                packing.page_settings.entry(name).or_default().levels = Some(levels);
+            }
+        }
+        Ok(())
+    }
+
+    /// Scores the pages, read through `read`, when `config.quality_report` asks for it.
+    fn quality(&self, read: &dyn Fn(&Path) -> io::Result<Vec<u8>>) -> Result<Option<QualityReport>> {
+        if !self.config.quality_report.unwrap_or(false) {
+            return Ok(None);
+        }
+        let mut pages = Vec::new();
+        for page in &self.pages {
+            let name = page.path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            let data = read(&page.path).map_err(CompileError::io(&page.path))?;
+            pages.extend(quality::measure(name, &data));
+        }
+        Ok(Some(QualityReport::new(pages)))
+    }
+
+    /// Leaves out the pages not `kept`, moving the chapter marks along.
+    fn retain_pages(&mut self, kept: &[bool]) {
+        for mark in &mut self.chapters {
+            mark.first_page = kept[..mark.first_page].iter().filter(|k| **k).count();
+        }
+        let mut keep = kept.iter();
+        self.pages.retain(|_| *keep.next().unwrap());
+        let pages = &self.pages;
+        self.chapter_gaps.retain(|(label, _)| pages.iter().any(|p| chapter_label(p.chapter) == *label));
+        // Chapters left without pages start where the next one does, or past the end.
+        let starts: Vec<usize> = self.chapters.iter().map(|m| m.first_page).collect();
+        let count = self.pages.len();
+        let mut next = starts.iter().skip(1);
+        self.chapters.retain(|m| next.next() != Some(&m.first_page) && m.first_page < count);
+    }
+
+    /// Joins `parts`, e.g. the chapters of a volume each found with [`Compiler::discover`], into
+    /// one book of `folder` with the pages renumbered in order, which `config.pages` then selects
+    /// from. Apply the missing page policy to the parts first, their gaps and extras are not carried
+    /// over.
+    pub fn merge(folder: PathBuf, config: Config, parts: Vec<Book>) -> Book {
+        let device = parts.first().and_then(|p| p.device.clone());
+        let pattern = parts.first().map(|p| p.pattern.clone()).unwrap_or_default();
+        let mut set = PageSet {
+            pattern,
+            pages: Vec::new(),
+            noise: Vec::new(),
+            duplicates: Vec::new(),
+            missing: Vec::new(),
+            chapter_gaps: Vec::new(),
+        };
+        let mut chapters = Vec::new();
+        for part in parts {
+            chapters.push(ChapterMark { first_page: set.pages.len(), title: part.chapter_label() });
+            set.pages.extend(part.pages);
+            set.noise.extend(part.noise);
+            set.duplicates.extend(part.duplicates);
+        }
+        for (number, page) in (1..).zip(&mut set.pages) {
+            page.number = number;
+        }
+        Book::new(folder, config, set, Vec::new(), device, chapters)
+    }
+
+    /// `Chapter 12 — The Hawk` from the chapter number and title, as far as they are known.
+    fn chapter_label(&self) -> String {
+        let chapter_title = self.config.chapter_title.as_deref();
+        match (self.config.chapter, chapter_title) {
+            (Some(number), Some(title)) => format!("Chapter {} \u{2014} {}", number, title),
+            (Some(number), None) => format!("Chapter {}", number),
+            (None, title) => title.unwrap_or(&self.title).to_string(),
+        }
+    }
+
+    /// Puts a generated page showing the chapter's title before each chapter, written as PNG
+    /// into `dir` and sized like the chapter's first page.
+    pub fn insert_title_pages(&mut self, dir: &Path, style: &TitleStyle) -> Result<()> {
+        fs::create_dir_all(dir).map_err(CompileError::write(dir))?;
+        for index in (0..self.chapters.len()).rev() {
+            let mark = &self.chapters[index];
+            let first = &self.pages[mark.first_page];
+            let (width, height) = image::image_dimensions(&first.path).unwrap_or((1200, 1800));
+            let path = dir.join(format!("title-{:03}.png", index + 1));
+            titlepage::render(&mark.title, width, height, style)
+                .save(&path)
+                .map_err(|e| CompileError::Write { path: path.clone(), source: io::Error::other(e) })?;
+            let first_page = mark.first_page;
+            self.pages.insert(first_page, Page { number: 0, chapter: None, front: None, time: None, path });
+            for later in &mut self.chapters[index + 1..] {
+                later.first_page += 1;
+            }
+        }
+        for (number, page) in (1..).zip(&mut self.pages) {
+            page.number = number;
+        }
+        Ok(())
+    }
+
+    /// The book with the files at `paths` as its pages in that order, as arranged in a review.
+    /// Pages keep their numbers, so `[page.N]` tables still reach them; other files of the folder
+    /// join as number 0. The numbering no longer has gaps to report, and a chapter starts
+    /// wherever the next page names another one.
+    pub fn reorder(&self, paths: &[PathBuf]) -> Book {
+        let page = |path: &PathBuf| {
+            let found = self.pages.iter().find(|p| p.path == *path).cloned();
+            found.unwrap_or_else(|| Page { number: 0, chapter: None, front: None, time: None, path: path.clone() })
+        };
+        let left_out = |files: &[PathBuf]| files.iter().filter(|f| !paths.contains(f)).cloned().collect();
+        let mut book = Book {
+            pages: paths.iter().map(page).collect(),
+            noise: left_out(&self.noise),
+            duplicates: left_out(&self.duplicates),
+            identical: self.identical.iter().filter(|(copy, _)| paths.contains(copy)).cloned().collect(),
+            missing: Vec::new(),
+            chapter_gaps: Vec::new(),
+            chapters: Vec::new(),
+            reordered: true,
+            ..self.clone()
+        };
+        if book.pages.iter().any(|p| p.chapter.is_some()) {
+            book.chapters = book.chapter_marks();
+        }
+        book
+    }
+
+    /// Cuts the book before each page index in `starts`, giving one chapter per part titled
+    /// `<title> Ch. 001`, ... counting from `first_chapter` and keeping the book's series and
+    /// volume. Unless configured, the series counts up to the last chapter. Missing pages and
+    /// extras stay with the whole book.
+    pub fn split(&self, starts: &[usize], first_chapter: u32) -> Vec<Book> {
+        let mut cuts: Vec<usize> = starts.iter().copied().filter(|&s| s > 0 && s < self.pages.len()).collect();
+        cuts.sort_unstable();
+        cuts.dedup();
+        cuts.insert(0, 0);
+        cuts.push(self.pages.len());
+        let last = first_chapter + cuts.len() as u32 - 2;
+        (first_chapter..)
+            .zip(cuts.windows(2))
+            .map(|(chapter, range)| {
+                let title = format!("{} Ch. {:03}", self.title, chapter);
+                let mut config = self.config.clone();
+                config.title = Some(title.clone());
+                config.series = Some(self.series().to_string());
+                config.chapter = Some(chapter.into());
+                // The source's chapter title, if any, names none of its parts.
+                config.chapter_title = None;
+                config.count = config.count.or(Some(last));
+                let mut pages = self.pages[range[0]..range[1]].to_vec();
+                for (number, page) in (1..).zip(&mut pages) {
+                    page.number = number;
+                }
+                Book {
+                    title: title.clone(),
+                    config,
+                    pages,
+                    noise: Vec::new(),
+                    duplicates: Vec::new(),
+                    identical: Vec::new(),
+                    missing: Vec::new(),
+                    chapter_gaps: Vec::new(),
+                    extras: Vec::new(),
+                    chapters: vec![ChapterMark { first_page: 0, title }],
+                    ..self.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// Archive name and source file of every page, in order, followed by the extras.
+    /// Merged and split books rename their pages to `<title>-001.jpg`, ... since chapters reuse file names,
+    /// as do books ordered by time or with roman front matter, whose names would sort differently,
+    /// and books compacted over gaps in their numbering or reordered.
+    pub fn entries(&self) -> Vec<(String, PathBuf)> {
+        let total = self.pages.len();
+        let renamed = self.reordered
+            || !self.chapters.is_empty()
+            || self.compacted().is_some()
+            || self.config.sort.is_some_and(|s| s != Sort::Number)
+            || self.pages.iter().any(|p| p.front.is_some_and(|f| f > 0));
+        let pages = (1..).zip(&self.pages).map(|(position, page)| {
+            let name = page.path.file_name().unwrap().to_string_lossy().into_owned();
+            // Pages a profile turns into JPEGs are named for it.
+            let jpeg = self.device.as_ref().is_some_and(|d| d.reencodes(&name));
+            if !renamed && jpeg {
+                let stem = name.rsplit_once('.').map_or(name.as_str(), |(stem, _)| stem);
+                return (format!("{}.jpg", stem), page.path.clone());
+            }
+            if !renamed {
+                return (name, page.path.clone());
+            }
+            let ext = if jpeg { "jpg" } else { name.rsplit('.').next().unwrap_or("jpg") };
+            (archive::page_file_name(&self.title, position, total, ext), page.path.clone())
+        });
+        let ascii = self.config.ascii_names.unwrap_or(false);
+        pages
+            .chain(self.extras.iter().cloned())
+            .map(|(name, path)| (if ascii { names::to_ascii(&name) } else { names::compose(&name) }, path))
+            .collect()
+    }
+
+    fn summary(&self, output: PathBuf, stats: Stats) -> Summary {
+        Summary {
+            source: self.folder.clone(),
+            output,
+            ignored: self.noise_names(),
+            duplicates: file_names(&self.duplicates),
+            missing: self.missing.clone(),
+            appended: None,
+            signature: None,
+            cover: None,
+            existing: None,
+            quality: None,
+            stats,
+        }
+    }
+
+    pub fn out_path(&self, output: Option<&Path>) -> Result<PathBuf> {
+        if let Some(out) = output {
+            return Ok(out.to_path_buf());
+        }
+        let ext = self.config.format.unwrap_or_default().extension();
+        if let Some(layout) = self.config.layout {
+            let library = self.config.output_dir.as_ref().ok_or_else(|| {
+                CompileError::Config("A layout needs an output directory as the library root".to_string())
+            })?;
+            let special = archive::is_special(&self.folder.file_name().unwrap().to_string_lossy());
+            let template = layout.template(self.config.volume.is_some(), self.config.chapter.is_some(), special);
+            let name = template::render(template, &self.template_vars())?;
+            return Ok(library.join(paths::relative(&name)));
+        }
+        let dir = match &self.config.output_dir {
+            Some(dir) => dir.clone(),
+            None => self.folder.parent().map(Path::to_path_buf).unwrap_or_default(),
+        };
+        match &self.config.output_template {
+            Some(t) => Ok(dir.join(paths::relative(&template::render(t, &self.template_vars())?))),
+            // A merged book shares its folder with the other volumes, so it is named by title.
+            None if self.config.output_dir.is_some() || !self.chapters.is_empty() => {
+                Ok(dir.join(paths::file_name(&format!("{}.{}", self.title, ext))))
+            }
+            // Not `with_extension`, which would cut `Chapter 21.5` down to `Chapter 21`.
+            None => {
+                let name = self.folder.file_name().unwrap_or_default().to_string_lossy();
+                Ok(self.folder.with_file_name(paths::file_name(&format!("{}.{}", name, ext))))
+            }
+        }
+    }
+
+    fn template_vars(&self) -> Vec<(&'static str, Option<String>)> {
+        let name = |p: Option<&Path>| p.and_then(|p| p.file_name()).map(|n| n.to_string_lossy().into_owned());
+        vec![
+            ("title", Some(self.title.clone())),
+            ("series", Some(self.series().to_string())),
+            ("volume", self.config.volume.map(|v| v.to_string())),
+            ("chapter", self.config.chapter.map(|c| c.to_string())),
+            ("chapter_title", self.config.chapter_title.clone()),
+            ("folder", name(Some(&self.folder))),
+            ("parent", name(self.folder.parent())),
+            ("pages", Some(self.pages.len().to_string())),
+        ]
+    }
+
+    /// Variables describing the book to hooks.
+    pub fn hook_env(&self, out_path: &Path) -> Vec<(&'static str, String)> {
+        vec![
+            ("MANGA_COMPILER_SOURCE", self.folder.display().to_string()),
+            ("MANGA_COMPILER_OUTPUT", out_path.display().to_string()),
+            ("MANGA_COMPILER_TITLE", self.title.clone()),
+            ("MANGA_COMPILER_SERIES", self.series().to_string()),
+            ("MANGA_COMPILER_VOLUME", self.config.volume.map(|v| v.to_string()).unwrap_or_default()),
+            ("MANGA_COMPILER_CHAPTER", self.config.chapter.map(|c| c.to_string()).unwrap_or_default()),
+            ("MANGA_COMPILER_CHAPTER_TITLE", self.config.chapter_title.clone().unwrap_or_default()),
+            ("MANGA_COMPILER_PAGES", self.pages.len().to_string()),
+        ]
+    }
+
+    pub fn series(&self) -> &str {
+        self.config.series.as_deref().unwrap_or(&self.title)
+    }
+
+    pub fn noise_names(&self) -> Vec<String> {
+        self.noise.iter().map(|n| n.file_name().unwrap().to_string_lossy().into_owned()).collect()
+    }
+
+    /// The image to show as the book's cover: a source file such as `cover.jpg` that is not a
+    /// page, or else the first page.
+    pub fn cover(&self) -> Option<&Path> {
+        let named = self.noise.iter().find(|path| {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_lowercase();
+            cover::NAMES.contains(&stem.as_str()) && archive::is_image(&path.to_string_lossy())
+        });
+        named.or(self.pages.first().map(|p| &p.path)).map(PathBuf::as_path)
+    }
+
+    /// Writes the configured cover next to `archive` and returns its path.
+    fn write_cover(&self, archive: &Path) -> Result<Option<PathBuf>> {
+        let (Some(name), Some(source)) = (&self.config.cover, self.cover()) else { return Ok(None) };
+        let stem = archive.file_stem().map(|s| s.to_string_lossy().into_owned());
+        let mut vars = self.template_vars();
+        vars.push(("archive", stem));
+        let path = archive.parent().unwrap_or(Path::new("")).join(paths::relative(&template::render(name, &vars)?));
+        let size = self.config.cover_size.unwrap_or(cover::DEFAULT_SIZE);
+        cover::write(source, &path, size, self.config.max_memory.map(|m| m.0))?;
+        Ok(Some(path))
+    }
+
+    /// Everything known about the book, for ComicInfo.xml and metadata.opf.
+    pub fn metadata(&self, page_count: usize) -> ComicInfo {
+        let config = &self.config;
+        ComicInfo {
+            title: Some(config.chapter_title.clone().unwrap_or_else(|| self.title.clone())),
+            series: config.series.clone(),
+            writer: config.author.clone(),
+            volume: config.volume,
+            number: config.chapter,
+            count: config.count,
+            page_count: Some(page_count),
+            notes: self.compacted(),
+            direction: config.direction,
+            // Readers list these as the table of contents.
+            bookmarks: self
+                .chapters
+                .iter()
+                .filter(|c| c.first_page < page_count)
+                .map(|c| (c.first_page, c.title.clone()))
+                .collect(),
+            page_types: self.front_matter().into_iter().filter(|(i, _)| *i < page_count).collect(),
+        }
+    }
+
+    /// What [`MissingPolicy::Compact`] numbered over, for ComicInfo `Notes`.
+    fn compacted(&self) -> Option<String> {
+        if self.config.missing != Some(MissingPolicy::Compact) {
+            return None;
+        }
+        let mut gaps = Vec::new();
+        if !self.missing.is_empty() {
+            gaps.push(format!("{:?}", self.missing));
+        }
+        if !self.chapter_gaps.is_empty() {
+            gaps.push(describe_gaps(&self.chapter_gaps));
+        }
+        let gaps = gaps.join(", ");
+        (!gaps.is_empty()).then(|| format!("Pages renumbered without gaps, the source lacks page numbers {}", gaps))
+    }
+
+    /// The leading front matter pages: the cover, then inner covers such as colour pages.
+    fn front_matter(&self) -> Vec<(usize, String)> {
+        let front = self.pages.iter().take_while(|p| p.front.is_some()).count();
+        let kind = |i: usize| if i == 0 { "FrontCover" } else { "InnerCover" };
+        (0..front).map(|i| (i, kind(i).to_string())).collect()
+    }
+
+    /// The ComicInfo.xml to embed, only written once there is more to say than the title.
+    pub fn comicinfo(&self, page_count: usize) -> Option<ComicInfo> {
+        let info = self.metadata(page_count);
+        let empty = self.config.chapter_title.is_none()
+            && info.series.is_none()
+            && info.writer.is_none()
+            && info.volume.is_none()
+            && info.number.is_none()
+            && info.count.is_none()
+            && info.direction.is_none()
+            && info.bookmarks.is_empty()
+            && info.page_types.is_empty()
+            && info.notes.is_none();
+        (!empty).then_some(info)
+    }
+
+    /// How to write an archive of the first `page_count` pages.
+    pub fn packing(&self, page_count: usize) -> Packing {
+        Packing {
+            compression: self.config.compression.unwrap_or_default(),
+            comicinfo: self.comicinfo(page_count),
+            device: self.device.clone(),
+            page_settings: self.page_settings(),
+            max_memory: self.config.max_memory.map(|m| m.0),
+            reproducible: self.config.reproducible.unwrap_or(false),
+            filter: self.config.filter_cmd.clone().map(|command| {
+                Filter::new(command, self.config.filter_jobs, self.config.on_filter_error)
+            }),
+        }
+    }
+
+    /// The [`PageSettings`] of every page that has some, by entry name.
+    fn page_settings(&self) -> HashMap<String, PageSettings> {
+        let Some(overrides) = &self.config.page else { return HashMap::new() };
+        let names = self.entries().into_iter().map(|(name, _)| name);
+        let settings = names.zip(&self.pages).map(|(name, page)| (name, overrides.get(page.number)));
+        settings.filter(|(_, s)| *s != PageSettings::default()).collect()
+    }
+}
+
+/// Settings for [`write_archive`].
+#[derive(Debug, Clone, Default)]
+pub struct Packing {
+    pub compression: Compression,
+    /// Written as `ComicInfo.xml` after the entries.
+    pub comicinfo: Option<ComicInfo>,
+    /// Pages are prepared for this profile's screen.
+    pub device: Option<Device>,
+    /// Exceptions for single pages from the device profile, by entry name.
+    pub page_settings: HashMap<String, PageSettings>,
+    /// Bytes preparing a page may take at most; pages needing more are stored unprepared.
+    pub max_memory: Option<u64>,
+    /// Every entry gets the same fixed timestamp instead of its file's modification time.
+    pub reproducible: bool,
+    /// Every page goes through this command before it is prepared for the device.
+    pub filter: Option<Filter>,
+}
+
+/// Writes `entries` (archive name, source file) in order, followed by an optional ComicInfo.xml.
+/// Returns the bytes read and written.
+///
+/// The archive is written through [`paths::write_atomic`], so a failed or interrupted run never
+/// leaves a truncated archive under the real name.
+pub fn write_archive(
+    out_path: &Path,
+    entries: &[(String, PathBuf)],
+    packing: &Packing,
+    observer: &mut dyn ProgressObserver,
+) -> Result<(u64, u64)> {
+    write_file(out_path, entries, None, packing, observer)
+}
+
+/// Adds the entries `out_path` lacks to it, raw-copying those it has, and replaces its
+/// ComicInfo.xml when `packing` has one. An archive lacking none is left as is.
+/// Returns the number of entries added, the bytes read and how much the archive grew.
+pub fn append_archive(
+    out_path: &Path,
+    entries: &[(String, PathBuf)],
+    packing: &Packing,
+    observer: &mut dyn ProgressObserver,
+) -> Result<(usize, u64, u64)> {
+    let file = File::open(out_path).map_err(CompileError::io(out_path))?;
+    let old_len = file.metadata().map_err(CompileError::io(out_path))?.len();
+    let existing = zip::ZipArchive::new(file).map_err(|e| CompileError::io(out_path)(e.into()))?;
+    let present: HashSet<&str> = existing.file_names().collect();
+    let added: Vec<(String, PathBuf)> =
+        entries.iter().filter(|(name, _)| !present.contains(name.as_str())).cloned().collect();
+    if added.is_empty() {
+        return Ok((0, 0, 0));
+    }
+    let (bytes_in, bytes_out) = write_file(out_path, &added, Some(existing), packing, observer)?;
+    Ok((added.len(), bytes_in, bytes_out.saturating_sub(old_len)))
+}
+
+/// Writes through `<name>.part`, see [`write_archive`].
+fn write_file(
+    out_path: &Path,
+    entries: &[(String, PathBuf)],
+    existing: Option<zip::ZipArchive<File>>,
+    packing: &Packing,
+    observer: &mut dyn ProgressObserver,
+) -> Result<(u64, u64)> {
+    if let Some(parent) = out_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(paths::extended(parent)).map_err(CompileError::write(parent))?;
+    }
+    paths::write_atomic(out_path, |part| {
+        let file = File::create(paths::extended(part)).map_err(CompileError::write(part))?;
+        let read = |path: &Path| Ok((fs::read(path)?, fs::metadata(path).and_then(|m| m.modified()).ok()));
+        let (file, bytes_in, bytes_out) = write_zip(file, out_path, entries, &read, existing, packing, observer)?;
+        file.sync_all().map_err(CompileError::write(part))?;
+        check_names(part, out_path, entries)?;
+        Ok((bytes_in, bytes_out))
+    })
+}
+
+/// The contents of a page file and its modification time, if known.
+type ReadEntry<'a> = dyn Fn(&Path) -> io::Result<(Vec<u8>, Option<SystemTime>)> + 'a;
+
+/// [`write_archive`] into any sink, reading entries and their modification time (the write time
+/// when there is none) through `read`, after the entries of `existing`; `out_path` only labels
+/// errors.
+fn write_zip<W: Write + Seek>(
+    sink: W,
+    out_path: &Path,
+    entries: &[(String, PathBuf)],
+    read: &ReadEntry,
+    existing: Option<zip::ZipArchive<File>>,
+    packing: &Packing,
+    observer: &mut dyn ProgressObserver,
+) -> Result<(W, u64, u64)> {
+    let written = Rc::new(Cell::new(0));
+    let mut zip = zip::ZipWriter::new(Counting { inner: sink, pos: 0, high: written.clone() });
+    let mut options = FileOptions::default().compression_method(packing.compression.into());
+    if packing.reproducible {
+        options = options.last_modified_time(zip::DateTime::default());
+    }
+    let zip_error = |e: zip::result::ZipError| CompileError::write(out_path)(e.into());
+
+    if let Some(mut existing) = existing {
+        for i in 0..existing.len() {
+            let entry = existing.by_index_raw(i).map_err(|e| CompileError::io(out_path)(e.into()))?;
+            if entry.name() != "ComicInfo.xml" || packing.comicinfo.is_none() {
+                zip.raw_copy_file(entry).map_err(zip_error)?;
+            }
+        }
+    }
+
+    observer.started(out_path, entries.len());
+    let mut bytes_in = 0;
+    let mut seen = HashSet::new();
+    let mut over_memory = Vec::new();
+    let mut unfiltered = Vec::new();
+    let plain = Device::default();
+    // Read a batch at a time so that the filter command can work on several pages at once.
+    let batch = packing.filter.as_ref().map_or(1, |f| f.jobs);
+    for (first, chunk) in (0..).step_by(batch).zip(entries.chunks(batch)) {
+        let mut pages = Vec::with_capacity(chunk.len());
+        for (index, (arc_name, path)) in (first..).zip(chunk) {
+            // A name that is not UTF-8 only gets here with replacement characters in it.
+            if arc_name.contains('\u{fffd}') && path.file_name().and_then(|n| n.to_str()).is_none() {
+                return Err(CompileError::InvalidName(path.clone()));
+            }
+            if !seen.insert(arc_name) {
+                return Err(CompileError::DuplicateEntry(arc_name.clone()));
+            }
+            observer.page_started(index, arc_name);
+            let (buffer, modified) = read(path).map_err(CompileError::io(path))?;
+            // Checked against the source's name, which a page bound for a JPEG entry need not share.
+            if let Some(reason) = image_problem(&path.to_string_lossy(), &buffer) {
+                return Err(CompileError::InvalidImage { path: path.clone(), reason });
+            }
+            bytes_in += buffer.len() as u64;
+            pages.push((buffer, modified));
+        }
+        if let Some(filter) = &packing.filter {
+            filter_pages(filter, chunk, &mut pages, &mut unfiltered)?;
+        }
+        for ((index, (arc_name, path)), (buffer, modified)) in (first..).zip(chunk).zip(pages) {
+            let settings = packing.page_settings.get(arc_name).copied().unwrap_or_default();
+            // Matched levels apply without a device as well.
+            let device = packing.device.as_ref().or(settings.levels.map(|_| &plain));
+            let buffer = match device {
+                Some(device) if archive::is_image(arc_name) => {
+                    let needed = || device.working_memory(arc_name, &buffer, &settings).unwrap_or(0);
+                    if packing.max_memory.is_some_and(|max| needed() > max) {
+                        if device.reencodes(&path.to_string_lossy()) {
+                            // Stored as it is, it would be no JPEG under a JPEG's name.
+                            let reason = "too large to re-encode as JPEG within --max-memory";
+                            return Err(CompileError::InvalidImage { path: path.clone(), reason });
+                        }
+                        over_memory.push(arc_name.clone());
+                        buffer
+                    } else {
+                        device.prepare(arc_name, &buffer, &settings).map_err(|_| CompileError::InvalidImage {
+                            path: path.clone(),
+                            reason: "could not be decoded",
+                        })?
+                    }
+                }
+                _ => buffer,
+            };
+            let entry_options = match modified.and_then(dos_time) {
+                Some(time) if !packing.reproducible => options.last_modified_time(time),
+                _ => options,
+            };
+            zip.start_file(arc_name.as_str(), entry_options).map_err(zip_error)?;
+            zip.write_all(&buffer).map_err(CompileError::write(out_path))?;
+            observer.page_finished(index, arc_name);
+            observer.bytes_written(written.get());
+        }
+    }
+
+    if !over_memory.is_empty() {
+        observer.warning(&Warning::OverMemory(over_memory));
+    }
+    if !unfiltered.is_empty() {
+        observer.warning(&Warning::Unfiltered(unfiltered));
+    }
+
+    if let Some(info) = &packing.comicinfo {
+        zip.start_file("ComicInfo.xml", options).map_err(zip_error)?;
+        zip.write_all(info.to_xml().as_bytes()).map_err(CompileError::write(out_path))?;
+    }
+
+    let sink = zip.finish().map_err(zip_error)?;
+    observer.bytes_written(written.get());
+    Ok((sink.inner, bytes_in, written.get()))
+}
+
+/// Replaces the image `pages` of `chunk` by what `filter` makes of them. A page it fails on
+/// fails the book, or with [`OnFilterError::Keep`] stays as it was and is added to `unfiltered`
+/// with the reason.
+fn filter_pages(
+    filter: &Filter,
+    chunk: &[(String, PathBuf)],
+    pages: &mut [(Vec<u8>, Option<SystemTime>)],
+    unfiltered: &mut Vec<(String, String)>,
+) -> Result<()> {
+    let images: Vec<usize> = (0..chunk.len()).filter(|&i| archive::is_image(&chunk[i].0)).collect();
+    let input: Vec<(&str, &[u8])> = images.iter().map(|&i| (chunk[i].0.as_str(), pages[i].0.as_slice())).collect();
+    let results = filter.run_all(&input);
+    for (i, result) in images.into_iter().zip(results) {
+        let (name, path) = &chunk[i];
+        // The page is prepared as if it came from the source, so the filter has to keep its format.
+        let result = result.and_then(|data| match image_problem(&path.to_string_lossy(), &data) {
+            Some(problem) => Err(format!("printed no page of the same format ({})", problem)),
+            None => Ok(data),
+        });
+        match result {
+            Ok(data) => pages[i].0 = data,
+            Err(reason) if filter.on_error == OnFilterError::Keep => unfiltered.push((name.clone(), reason)),
+            Err(reason) => {
+                return Err(CompileError::Filter { command: filter.command.clone(), page: name.clone(), reason });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads the entry names back from the written archive, which only gives the names written if
+/// the non-ASCII ones were flagged as UTF-8.
+fn check_names(part: &Path, out_path: &Path, entries: &[(String, PathBuf)]) -> Result<()> {
+    let mismatch = |message: String| CompileError::write(out_path)(io::Error::other(message));
+    let file = File::open(paths::extended(part)).map_err(CompileError::io(part))?;
+    let zip = zip::ZipArchive::new(file).map_err(|e| mismatch(e.to_string()))?;
+    let names: HashSet<&str> = zip.file_names().collect();
+    match entries.iter().find(|(name, _)| !names.contains(name.as_str())) {
+        Some((name, _)) => Err(mismatch(format!("entry {:?} does not read back under its name", name))),
+        None => Ok(()),
+    }
+}
+
+/// `time` in UTC as a zip timestamp, if it falls in the years zip can store (1980 to 2107).
+pub(crate) fn dos_time(time: SystemTime) -> Option<zip::DateTime> {
+    let (year, month, day, hour, minute, second) = civil(time)?;
+    zip::DateTime::from_date_and_time(u16::try_from(year).ok()?, month, day, hour, minute, second).ok()
+}
+
+/// `YYYY-MM-DD HH:MM:SS` in UTC.
+pub fn timestamp(time: SystemTime) -> Option<String> {
+    let (year, month, day, hour, minute, second) = civil(time)?;
+    Some(format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, hour, minute, second))
+}
+
+/// UTC year, month, day, hour, minute and second.
+fn civil(time: SystemTime) -> Option<(i64, u8, u8, u8, u8, u8)> {
+    let secs = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let (days, rest) = ((secs / 86_400) as i64, secs % 86_400);
+    // Civil date from days since the epoch, after Howard Hinnant's `civil_from_days`.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    let (hour, minute, second) = ((rest / 3600) as u8, (rest / 60 % 60) as u8, (rest % 60) as u8);
+    Some((year, month, day, hour, minute, second))
+}
+
+/// Tracks how far into the sink the zip writer got, for [`ProgressObserver::bytes_written`].
+struct Counting<W> {
+    inner: W,
+    pos: u64,
+    high: Rc<Cell<u64>>,
+}
+
+impl<W: Write> Write for Counting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.pos += n as u64;
+        self.high.set(self.high.get().max(self.pos));
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Seek> Seek for Counting<W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = self.inner.seek(pos)?;
+        Ok(self.pos)
+    }
+}
+
+/// Catches files that cannot be the image their extension claims; other formats pass unchecked.
+fn image_problem(name: &str, data: &[u8]) -> Option<&'static str> {
+    if !archive::is_image(name) {
+        return None;
+    }
+    if data.is_empty() {
+        return Some("file is empty");
+    }
+    let ext = name.rsplit('.').next().unwrap_or_default().to_ascii_lowercase();
+    match ext.as_str() {
+        "jpg" | "jpeg" if !data.starts_with(&[0xFF, 0xD8, 0xFF]) => Some("missing JPEG signature"),
+        "png" if !data.starts_with(b"\x89PNG\r\n\x1a\n") => Some("missing PNG signature"),
+        _ => None,
+    }
+}
@@ -0,0 +1,407 @@
+use crate::device::PageSettings;
+use crate::discover::PageRanges;
+use crate::exit::{self, Code, WithCode};
+use crate::layout::Layout;
+use crate::sign::Signer;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    Stored,
+    Deflated,
+}
+
+impl From<Compression> for zip::CompressionMethod {
+    fn from(c: Compression) -> Self {
+        match c {
+            Compression::Stored => zip::CompressionMethod::Stored,
+            Compression::Deflated => zip::CompressionMethod::Deflated,
+        }
+    }
+}
+
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Format {
+    #[default]
+    Cbz,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Cbz => "cbz",
+        }
+    }
+}
+
+/// What to do when page numbers between 1 and the highest page are missing.
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MissingPolicy {
+    #[default]
+    Fail,
+    Warn,
+    /// Number the pages found 1, 2, 3, ... and note the missing numbers in ComicInfo.xml
+    Compact,
+}
+
+/// What to do with a page the `filter-cmd` fails on.
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OnFilterError {
+    /// Fail the book
+    #[default]
+    Fail,
+    /// Store the page unfiltered and report it
+    Keep,
+}
+
+/// What to do with pages byte for byte identical to an earlier page, e.g. a cover repeated as page 1.
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupeExact {
+    /// Keep them and report them
+    Warn,
+    /// Store the first and leave out its copies
+    Drop,
+}
+
+/// An amount of memory such as `512M` or `2G`, in bytes or with a `K`, `M` or `G` suffix
+/// counting in 1024s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String")]
+pub struct MemorySize(pub u64);
+
+impl std::str::FromStr for MemorySize {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, String> {
+        let trimmed = s.trim().trim_end_matches(['B', 'b']).trim_end_matches(['i', 'I']);
+        let (number, shift) = match trimmed.char_indices().last() {
+            Some((i, 'K' | 'k')) => (&trimmed[..i], 10),
+            Some((i, 'M' | 'm')) => (&trimmed[..i], 20),
+            Some((i, 'G' | 'g')) => (&trimmed[..i], 30),
+            _ => (trimmed, 0),
+        };
+        let bytes = number.trim().parse::<u64>().ok().and_then(|n| n.checked_mul(1 << shift));
+        match bytes {
+            Some(bytes) if bytes > 0 => Ok(MemorySize(bytes)),
+            _ => Err(format!("{:?} is not an amount of memory such as 512M", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for MemorySize {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, String> {
+        s.parse()
+    }
+}
+
+/// How the pages of a folder are put in order.
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    /// By the page number in the file name
+    #[default]
+    Number,
+    /// By modification time, for files whose names have no usable numbers
+    Mtime,
+    /// By the EXIF time a photo or screenshot was taken, else the modification time
+    Exif,
+}
+
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Ltr,
+    Rtl,
+}
+
+/// Format, screen fit, encoding and compression chosen together for a purpose. Each of them
+/// ranks below the same setting given on its own, in any config file or on the command line.
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    /// The pages as they are, deflated, for keeping lossless originals
+    Archive,
+    /// Fitted to an e-reader screen, KCC's KO profile unless a device is given
+    Device,
+    /// Small for reading online, in colour at most 1200 pixels wide and JPEG quality 75
+    Web,
+}
+
+impl Preset {
+    /// The settings the preset stands for.
+    pub fn config(self) -> Config {
+        let (compression, device) = match self {
+            Preset::Archive => (Compression::Deflated, None),
+            Preset::Device => (Compression::Stored, Some("KO")),
+            Preset::Web => (Compression::Stored, Some("web")),
+        };
+        Config {
+            format: Some(Format::Cbz),
+            compression: Some(compression),
+            device: device.map(String::from),
+            ..Config::default()
+        }
+    }
+}
+
+/// Settings for single pages from `[page.<pages>]` tables such as `[page.47]` or `[page."1-3,9"]`.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct PageOverrides(Vec<(PageRanges, PageSettings)>);
+
+impl PageOverrides {
+    /// The settings of page `number`, later tables taking precedence over earlier ones.
+    pub fn get(&self, number: u32) -> PageSettings {
+        let matching = self.0.iter().rev().filter(|(ranges, _)| ranges.contains(number));
+        matching.fold(PageSettings::default(), |settings, (_, page)| settings.or(*page))
+    }
+}
+
+impl<'de> Deserialize<'de> for PageOverrides {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        // By hand, since a map type would lose the order of the tables.
+        struct Tables;
+        impl<'de> serde::de::Visitor<'de> for Tables {
+            type Value = PageOverrides;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("tables of page settings named by pages such as 47 or \"1-3,9\"")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> std::result::Result<PageOverrides, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let mut tables = Vec::new();
+                while let Some((pages, settings)) = map.next_entry::<String, PageSettings>()? {
+                    tables.push((pages.parse().map_err(serde::de::Error::custom)?, settings));
+                }
+                Ok(PageOverrides(tables))
+            }
+        }
+        deserializer.deserialize_map(Tables)
+    }
+}
+
+pub const FOLDER_CONFIG: &str = ".manga-compiler.toml";
+
+/// Keys that may also be set as `MANGA_COMPILER_<KEY>` environment variables. Per-book keys such as
+/// title or volume are left out since hooks export variables of those names to their commands.
+const ENV_KEYS: &[&str] = &[
+    "format",
+    "compression",
+    "missing",
+    "output-dir",
+    "output-template",
+    "layout",
+    "pattern",
+    "sort",
+    "preset",
+    "dedupe-exact",
+    "direction",
+    "device",
+    "max-memory",
+    "filter-cmd",
+    "on-filter-error",
+    "cover",
+    "extras-dir",
+    "pre-hook",
+    "post-hook",
+    "sign",
+    "sign-key",
+];
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// Defaults for the format, device and compression, see [`Preset`].
+    pub preset: Option<Preset>,
+    pub format: Option<Format>,
+    pub compression: Option<Compression>,
+    pub missing: Option<MissingPolicy>,
+    pub output_dir: Option<PathBuf>,
+    pub output_template: Option<String>,
+    pub layout: Option<Layout>,
+    pub title: Option<String>,
+    pub series: Option<String>,
+    pub volume: Option<u32>,
+    pub chapter: Option<f64>,
+    /// Title of the chapter, written as ComicInfo `Title` in place of the book title.
+    pub chapter_title: Option<String>,
+    /// Books in the whole series, written as ComicInfo `Count`.
+    pub count: Option<u32>,
+    /// Read volume, chapter and chapter title from the folder name when they are not given.
+    pub infer: Option<bool>,
+    pub author: Option<String>,
+    pub pattern: Option<String>,
+    /// Order pages by time instead of the number in their name.
+    pub sort: Option<Sort>,
+    /// Page numbers to compile, leaving out the others and any gaps around them.
+    pub pages: Option<PageRanges>,
+    /// Compare the pages' bytes and report or leave out repeated ones.
+    pub dedupe_exact: Option<DedupeExact>,
+    /// How the device profile treats single pages.
+    pub page: Option<PageOverrides>,
+    /// Most memory preparing one page for the device may take; pages needing more are stored as they are.
+    pub max_memory: Option<MemorySize>,
+    /// Shell command every page is piped through, see [`Filter`](crate::filter::Filter).
+    pub filter_cmd: Option<String>,
+    /// Pages filtered at the same time, one per CPU without.
+    pub filter_jobs: Option<usize>,
+    pub on_filter_error: Option<OnFilterError>,
+    /// Match the black and white points of the chapters of a book assembled from several.
+    pub normalize_levels: Option<bool>,
+    /// Also write a Calibre `metadata.opf` next to the archive.
+    pub opf: Option<bool>,
+    /// Score the source pages for blur, JPEG artifacts, resolution and skew.
+    pub quality_report: Option<bool>,
+    /// File name of a cover image written next to the archive, e.g. `cover.jpg` or `{archive}.jpg`.
+    pub cover: Option<String>,
+    /// Longest side of that cover in pixels.
+    pub cover_size: Option<u32>,
+    /// Stamp every entry with the same fixed time instead of its file's modification time.
+    pub reproducible: Option<bool>,
+    /// Spell entry names in ASCII for readers that cannot decode UTF-8 names.
+    pub ascii_names: Option<bool>,
+    pub direction: Option<Direction>,
+    /// Name of the device profile whose screen the pages are prepared for.
+    pub device: Option<String>,
+    pub extras_dir: Option<PathBuf>,
+    pub pre_hook: Option<String>,
+    pub post_hook: Option<String>,
+    /// Tool making a detached signature of each archive.
+    pub sign: Option<Signer>,
+    /// minisign secret key file or gpg key id to sign with.
+    pub sign_key: Option<String>,
+}
+
+impl Config {
+    /// Fills every unset field from `lower`, so `a.or(b)` gives `a` precedence.
+    pub fn or(self, lower: Config) -> Config {
+        Config {
+            preset: self.preset.or(lower.preset),
+            format: self.format.or(lower.format),
+            compression: self.compression.or(lower.compression),
+            missing: self.missing.or(lower.missing),
+            output_dir: self.output_dir.or(lower.output_dir),
+            output_template: self.output_template.or(lower.output_template),
+            layout: self.layout.or(lower.layout),
+            title: self.title.or(lower.title),
+            series: self.series.or(lower.series),
+            volume: self.volume.or(lower.volume),
+            chapter: self.chapter.or(lower.chapter),
+            chapter_title: self.chapter_title.or(lower.chapter_title),
+            count: self.count.or(lower.count),
+            infer: self.infer.or(lower.infer),
+            author: self.author.or(lower.author),
+            pattern: self.pattern.or(lower.pattern),
+            sort: self.sort.or(lower.sort),
+            pages: self.pages.or(lower.pages),
+            dedupe_exact: self.dedupe_exact.or(lower.dedupe_exact),
+            page: self.page.or(lower.page),
+            max_memory: self.max_memory.or(lower.max_memory),
+            filter_cmd: self.filter_cmd.or(lower.filter_cmd),
+            filter_jobs: self.filter_jobs.or(lower.filter_jobs),
+            on_filter_error: self.on_filter_error.or(lower.on_filter_error),
+            normalize_levels: self.normalize_levels.or(lower.normalize_levels),
+            opf: self.opf.or(lower.opf),
+            quality_report: self.quality_report.or(lower.quality_report),
+            cover: self.cover.or(lower.cover),
+            cover_size: self.cover_size.or(lower.cover_size),
+            reproducible: self.reproducible.or(lower.reproducible),
+            ascii_names: self.ascii_names.or(lower.ascii_names),
+            direction: self.direction.or(lower.direction),
+            device: self.device.or(lower.device),
+            extras_dir: self.extras_dir.or(lower.extras_dir),
+            pre_hook: self.pre_hook.or(lower.pre_hook),
+            post_hook: self.post_hook.or(lower.post_hook),
+            sign: self.sign.or(lower.sign),
+            sign_key: self.sign_key.or(lower.sign_key),
+        }
+    }
+
+    /// Fills what is still unset from the [`Preset`], if one is chosen.
+    pub fn with_preset(self) -> Config {
+        match self.preset {
+            Some(preset) => self.or(preset.config()),
+            None => self,
+        }
+    }
+
+    pub fn load_folder(folder: &Path) -> Result<Self> {
+        Self::load(&folder.join(FOLDER_CONFIG))
+    }
+
+    pub fn global_path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os("MANGA_COMPILER_CONFIG").filter(|v| !v.is_empty()) {
+            return Some(PathBuf::from(path));
+        }
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+        Some(base.join("manga-compiler").join("config.toml"))
+    }
+
+    pub fn load_global() -> Result<Self> {
+        match Self::global_path() {
+            Some(path) => Self::load(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn from_env() -> Result<Self> {
+        let mut table = toml::Table::new();
+        for key in ENV_KEYS {
+            let var = env_var_name(key);
+            match std::env::var(&var) {
+                Ok(value) if !value.is_empty() => {
+                    table.insert(key.to_string(), toml::Value::String(value));
+                }
+                Err(std::env::VarError::NotUnicode(_)) => {
+                    return Err(exit::fail(Code::Config, format!("{} is not valid UTF-8", var)));
+                }
+                _ => {}
+            }
+        }
+        toml::Value::Table(table)
+            .try_into()
+            .context("Invalid MANGA_COMPILER_* environment variable")
+            .code(Code::Config)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config {}", path.display()))
+            .code(Code::Config)?;
+        Self::parse(&text)
+            .with_context(|| format!("Invalid config {}", path.display()))
+            .code(Code::Config)
+    }
+
+    /// Parses a config in the `.manga-compiler.toml` format.
+    pub fn parse(text: &str) -> Result<Self> {
+        toml::from_str::<Self>(text).code(Code::Config)
+    }
+}
+
+fn env_var_name(key: &str) -> String {
+    format!("MANGA_COMPILER_{}", key.to_ascii_uppercase().replace('-', "_"))
+}
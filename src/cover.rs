@@ -0,0 +1,46 @@
+//! Cover images next to the archive, such as the `cover.jpg` or `folder.jpg` library apps and
+//! file managers show as thumbnails.
+
+use crate::error::{CompileError, Result};
+use crate::paths;
+use image::{ImageError, ImageFormat, ImageReader, Limits, imageops::FilterType};
+use std::{fs, io::Cursor, path::Path};
+
+/// Longest side of the cover in pixels when none is configured.
+pub const DEFAULT_SIZE: u32 = 1500;
+
+/// Source files named like this, e.g. `cover.png`, are taken as the cover instead of the first page.
+pub const NAMES: &[&str] = &["cover", "folder", "poster"];
+
+/// Writes `source` to `path`, scaled down to fit `size` pixels on its longest side. The
+/// extension of `path` picks PNG or JPEG, JPEG for any other. Decoding fails rather than take
+/// more than `max_memory` bytes.
+pub fn write(source: &Path, path: &Path, size: u32, max_memory: Option<u64>) -> Result<()> {
+    let data = fs::read(source).map_err(CompileError::io(source))?;
+    let invalid = |_| CompileError::InvalidImage { path: source.to_path_buf(), reason: "could not be decoded" };
+    let mut reader = ImageReader::new(Cursor::new(&data)).with_guessed_format().map_err(CompileError::io(source))?;
+    if let Some(max) = max_memory {
+        let mut limits = Limits::default();
+        limits.max_alloc = Some(max);
+        reader.limits(limits);
+    }
+    let reason = "needs more memory than max-memory allows";
+    let mut image = reader.decode().map_err(|e| match e {
+        ImageError::Limits(_) => CompileError::InvalidImage { path: source.to_path_buf(), reason },
+        _ => invalid(e),
+    })?;
+    if image.width() > size || image.height() > size {
+        image = image.resize(size, size, FilterType::Lanczos3);
+    }
+    let format = match ImageFormat::from_path(path) {
+        Ok(ImageFormat::Png) => ImageFormat::Png,
+        _ => ImageFormat::Jpeg,
+    };
+    let mut out = Cursor::new(Vec::new());
+    let written = match format {
+        ImageFormat::Png => image.write_to(&mut out, format),
+        _ => image.to_rgb8().write_to(&mut out, format),
+    };
+    written.map_err(invalid)?;
+    fs::write(paths::extended(path), out.into_inner()).map_err(CompileError::write(path))
+}
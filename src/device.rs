@@ -0,0 +1,368 @@
+//! E-reader profiles: how to find a mounted reader, where its books go, and what its screen
+//! can show.
+
+use crate::config::Config;
+use crate::exit::{Code, WithCode};
+use crate::levels::Levels;
+use anyhow::{Context, Result};
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, codecs::jpeg::JpegEncoder, imageops::FilterType};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+/// User profiles, next to the global config, e.g. `~/.config/manga-compiler/devices.toml`.
+pub const PROFILES_FILE: &str = "devices.toml";
+
+/// KCC's profile ids with their screen size and gray levels.
+const KCC: &[(&str, u32, u32, u32)] = &[
+    ("K1", 600, 670, 4),
+    ("K2", 600, 670, 15),
+    ("K34", 600, 800, 16),
+    ("K578", 600, 800, 16),
+    ("KDX", 824, 1000, 16),
+    ("KPW", 758, 1024, 16),
+    ("KV", 1072, 1448, 16),
+    ("K11", 1072, 1448, 16),
+    ("KO", 1264, 1680, 16),
+    ("KS", 1860, 2480, 16),
+    ("KoMT", 600, 800, 16),
+    ("KoG", 768, 1024, 16),
+    ("KoGHD", 1072, 1448, 16),
+    ("KoA", 758, 1024, 16),
+    ("KoAHD", 1080, 1440, 16),
+    ("KoAH2O", 1080, 1430, 16),
+    ("KoAO", 1404, 1872, 16),
+    ("KoN", 758, 1024, 16),
+    ("KoC", 1072, 1448, 16),
+    ("KoL", 1264, 1680, 16),
+    ("KoF", 1440, 1920, 16),
+    ("KoE", 1404, 1872, 16),
+];
+
+/// How to recognise a mounted e-reader, where its books go and how pages are prepared for it.
+/// Every field can be set in `devices.toml`:
+///
+/// ```toml
+/// [pocketbook-era]
+/// markers = ["system/config"]
+/// folder = "Books/Manga"
+/// resolution = [1264, 1680]
+/// gamma = 1.8
+/// palette = 16
+/// ```
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Device {
+    #[serde(skip)]
+    pub name: String,
+    /// Paths that exist on the device's storage, relative to its mount point.
+    pub markers: Vec<String>,
+    /// Folder below the mount point that receives the books.
+    pub folder: String,
+    /// File extensions the device can open.
+    pub formats: Vec<String>,
+    /// Screen width and height in pixels; larger pages are scaled down to fit.
+    pub resolution: Option<(u32, u32)>,
+    /// Applied to the page brightness, above 1 darkens the mid tones.
+    pub gamma: Option<f32>,
+    /// Number of gray levels the screen shows; unset keeps colour.
+    pub palette: Option<u32>,
+    /// JPEG quality of the prepared pages, 90 without.
+    pub quality: Option<u8>,
+    /// Encode every page as JPEG, other pages' entries renamed to `.jpg`.
+    pub jpeg: bool,
+}
+
+/// Exceptions to how [`Device::prepare`] treats single pages, e.g. a colour insert.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct PageSettings {
+    /// `false` leaves the page at its size instead of fitting it to the screen.
+    pub scale: Option<bool>,
+    /// Keep the colours instead of reducing the page to the palette.
+    pub keep_color: Option<bool>,
+    pub gamma: Option<f32>,
+    /// JPEG quality of the prepared page in place of the profile's.
+    pub quality: Option<u8>,
+    /// Set by [`Config::normalize_levels`](crate::Config::normalize_levels) rather than in a table.
+    #[serde(skip)]
+    pub levels: Option<Levels>,
+}
+
+impl PageSettings {
+    /// Fills every unset field from `lower`.
+    pub fn or(self, lower: PageSettings) -> PageSettings {
+        PageSettings {
+            scale: self.scale.or(lower.scale),
+            keep_color: self.keep_color.or(lower.keep_color),
+            gamma: self.gamma.or(lower.gamma),
+            quality: self.quality.or(lower.quality),
+            levels: self.levels.or(lower.levels),
+        }
+    }
+}
+
+impl Default for Device {
+    fn default() -> Self {
+        Device {
+            name: String::new(),
+            markers: Vec::new(),
+            folder: String::new(),
+            formats: vec!["cbz".to_string()],
+            resolution: None,
+            gamma: None,
+            palette: None,
+            quality: None,
+            jpeg: false,
+        }
+    }
+}
+
+impl Device {
+    fn new(name: &str, markers: &[&str], folder: &str, formats: &[&str]) -> Self {
+        let owned = |list: &[&str]| list.iter().map(|s| s.to_string()).collect();
+        Device {
+            name: name.to_string(),
+            markers: owned(markers),
+            folder: folder.to_string(),
+            formats: owned(formats),
+            ..Device::default()
+        }
+    }
+
+    /// The built-in profiles, most specific first so detection picks e.g. KOReader over Kindle.
+    /// The KCC ids share the markers and folder of their family.
+    pub fn builtin() -> Vec<Device> {
+        let kindle = Device::new("kindle", &["documents", "system"], "documents", &["azw3", "mobi", "pdf"]);
+        let kobo = Device::new("kobo", &[".kobo"], "Manga", &["cbz", "epub", "kepub", "pdf"]);
+        let mut devices = vec![
+            // KOReader on a jailbroken Kindle opens CBZ directly, the stock reader does not.
+            Device::new("kindle-koreader", &["koreader", "documents", "system"], "documents/Manga", &["cbz", "pdf"]),
+            kindle.clone(),
+            kobo.clone(),
+        ];
+        for &(id, width, height, palette) in KCC {
+            let family = if id.starts_with("Ko") { &kobo } else { &kindle };
+            devices.push(Device {
+                name: id.to_string(),
+                resolution: Some((width, height)),
+                gamma: Some(1.8),
+                palette: Some(palette),
+                ..family.clone()
+            });
+        }
+        // Pages for reading in a browser, of any height so that long strips keep their width.
+        devices.push(Device {
+            name: "web".to_string(),
+            resolution: Some((1200, u32::MAX)),
+            quality: Some(75),
+            jpeg: true,
+            ..Device::default()
+        });
+        devices
+    }
+
+    /// The profiles of `devices.toml`, then the built-in ones; a user profile replaces a
+    /// built-in one of the same name.
+    pub fn all() -> Result<Vec<Device>> {
+        let mut devices = match Config::global_path().and_then(|p| Some(p.parent()?.join(PROFILES_FILE))) {
+            Some(path) => Device::load(&path)?,
+            None => Vec::new(),
+        };
+        for device in Device::builtin() {
+            if !devices.iter().any(|d| d.name.eq_ignore_ascii_case(&device.name)) {
+                devices.push(device);
+            }
+        }
+        Ok(devices)
+    }
+
+    /// Reads a profiles file, one table per device; a missing file holds none.
+    pub fn load(path: &Path) -> Result<Vec<Device>> {
+        if !path.is_file() {
+            return Ok(Vec::new());
+        }
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read device profiles {}", path.display()))
+            .code(Code::Config)?;
+        let table: BTreeMap<String, Device> = toml::from_str(&text)
+            .with_context(|| format!("Invalid device profiles {}", path.display()))
+            .code(Code::Config)?;
+        Ok(table.into_iter().map(|(name, device)| Device { name, ..device }).collect())
+    }
+
+    /// Looks `name` up among `profiles`, ignoring case.
+    pub fn find(profiles: &[Device], name: &str) -> Result<Device> {
+        match profiles.iter().find(|d| d.name.eq_ignore_ascii_case(name)) {
+            Some(device) => Ok(device.clone()),
+            None => {
+                let names: Vec<&str> = profiles.iter().map(|d| d.name.as_str()).collect();
+                Err(anyhow::anyhow!("Unknown device {:?}, expected one of: {}", name, names.join(", ")))
+                    .code(Code::Config)
+            }
+        }
+    }
+
+    pub fn matches(&self, mount: &Path) -> bool {
+        !self.markers.is_empty() && self.markers.iter().all(|m| mount.join(m).exists())
+    }
+
+    pub fn accepts(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| self.formats.iter().any(|f| f.eq_ignore_ascii_case(e)))
+    }
+
+    /// Whether [`Device::prepare`] changes pages that have no [`PageSettings`].
+    pub fn processes_pages(&self) -> bool {
+        self.resolution.is_some() || self.gamma.is_some() || self.palette.is_some()
+    }
+
+    /// Roughly the most memory [`Device::prepare`] holds at once for a page: the decoded page,
+    /// the floating point copy scaling goes through and the result. Zero for a page it stores as
+    /// is, `None` if the page's header cannot be read.
+    pub fn working_memory(&self, name: &str, data: &[u8], settings: &PageSettings) -> Option<u64> {
+        let resolution = self.resolution.filter(|_| settings.scale != Some(false));
+        let gamma = settings.gamma.or(self.gamma);
+        let palette = self.palette.filter(|_| settings.keep_color != Some(true));
+        if resolution.is_none() && gamma.is_none() && palette.is_none() && settings.levels.is_none() {
+            return Some(0);
+        }
+        let format = page_format(name, data).ok()?;
+        let decoder = ImageReader::with_format(Cursor::new(data), format).into_decoder().ok()?;
+        let (width, height) = decoder.dimensions();
+        let pixel = u64::from(decoder.color_type().bytes_per_pixel());
+        let decoded = u64::from(width) * u64::from(height) * pixel;
+        let working = match resolution {
+            Some((w, h)) if width > w || height > h => {
+                let ratio = f64::min(f64::from(w) / f64::from(width), f64::from(h) / f64::from(height));
+                let (scaled_width, scaled_height) =
+                    ((f64::from(width) * ratio) as u64, (f64::from(height) * ratio) as u64);
+                // Lanczos3 first scales the height into 16-byte RGBA floats, then the width.
+                u64::from(width) * scaled_height * 16 + scaled_width * scaled_height * pixel
+            }
+            _ => decoded,
+        };
+        Some(decoded + working)
+    }
+
+    /// Whether [`Device::prepare`] turns the page `name` into a JPEG, so that its entry needs
+    /// renaming.
+    pub fn reencodes(&self, name: &str) -> bool {
+        let ext = name.rsplit('.').next().unwrap_or_default().to_ascii_lowercase();
+        self.jpeg && ext != "jpg" && ext != "jpeg"
+    }
+
+    /// Scales a JPEG or PNG page down to the screen, applies the page's levels and the gamma
+    /// and reduces it to the palette, keeping its format unless the profile encodes JPEG,
+    /// except where `settings` says otherwise.
+    pub fn prepare(&self, name: &str, data: &[u8], settings: &PageSettings) -> image::ImageResult<Vec<u8>> {
+        let resolution = self.resolution.filter(|_| settings.scale != Some(false));
+        let gamma = settings.gamma.or(self.gamma);
+        let palette = self.palette.filter(|_| settings.keep_color != Some(true));
+        let format = page_format(name, data)?;
+        let untouched = resolution.is_none() && gamma.is_none() && palette.is_none() && settings.levels.is_none();
+        if untouched && !(self.jpeg && format != ImageFormat::Jpeg) {
+            return Ok(data.to_vec());
+        }
+        let mut page = image::load_from_memory_with_format(data, format)?;
+        if let Some((width, height)) = resolution
+            && (page.width() > width || page.height() > height)
+        {
+            page = page.resize(width, height, FilterType::Lanczos3);
+        }
+        let gamma = gamma.unwrap_or(1.0);
+        let tone = |levels: u32| {
+            let table = tone_table(gamma, levels);
+            match settings.levels {
+                Some(matched) => matched.table().map(|value| table[value as usize]),
+                None => table,
+            }
+        };
+        if let Some(levels) = palette {
+            let table = tone(levels);
+            let mut gray = page.to_luma8();
+            gray.pixels_mut().for_each(|p| p.0[0] = table[p.0[0] as usize]);
+            page = DynamicImage::ImageLuma8(gray);
+        } else if let DynamicImage::ImageLuma8(gray) = &mut page {
+            let table = tone(256);
+            gray.pixels_mut().for_each(|p| p.0[0] = table[p.0[0] as usize]);
+        } else if gamma != 1.0 || settings.levels.is_some() {
+            let table = tone(256);
+            let mut rgb = page.to_rgb8();
+            rgb.pixels_mut().for_each(|p| p.0 = p.0.map(|c| table[c as usize]));
+            page = DynamicImage::ImageRgb8(rgb);
+        }
+
+        let mut out = Cursor::new(Vec::new());
+        let quality = settings.quality.or(self.quality).unwrap_or(90);
+        if self.jpeg || format == ImageFormat::Jpeg {
+            // JPEG holds 8-bit gray or RGB only.
+            let page = if page.color().has_color() {
+                DynamicImage::ImageRgb8(page.to_rgb8())
+            } else {
+                DynamicImage::ImageLuma8(page.to_luma8())
+            };
+            page.write_with_encoder(JpegEncoder::new_with_quality(&mut out, quality))?;
+        } else {
+            page.write_to(&mut out, format)?;
+        }
+        Ok(out.into_inner())
+    }
+}
+
+/// The format of page `data`, by its contents since a page bound for a JPEG entry keeps the
+/// source's format until it is prepared, else by the extension of `name`.
+fn page_format(name: &str, data: &[u8]) -> image::ImageResult<ImageFormat> {
+    image::guess_format(data).or_else(|_| ImageFormat::from_path(name))
+}
+
+/// Maps each 8-bit value through the gamma curve onto `levels` evenly spaced grays.
+fn tone_table(gamma: f32, levels: u32) -> [u8; 256] {
+    let steps = levels.clamp(2, 256) as f32 - 1.0;
+    let mut table = [0; 256];
+    for (value, slot) in table.iter_mut().enumerate() {
+        let curved = (value as f32 / 255.0).powf(gamma);
+        *slot = ((curved * steps).round() / steps * 255.0).round() as u8;
+    }
+    table
+}
+
+/// Mounted devices whose storage matches one of `profiles`, with their mount points.
+pub fn detect(profiles: &[Device]) -> Vec<(Device, PathBuf)> {
+    mount_points()
+        .into_iter()
+        .filter_map(|mount| Some((profiles.iter().find(|d| d.matches(&mount))?.clone(), mount)))
+        .collect()
+}
+
+/// Candidate mount points of removable storage.
+fn mount_points() -> Vec<PathBuf> {
+    if cfg!(windows) {
+        return ('D'..='Z').map(|c| PathBuf::from(format!("{}:\\", c))).filter(|p| p.exists()).collect();
+    }
+    let mut points = Vec::new();
+    if let Ok(mounts) = fs::read_to_string("/proc/mounts") {
+        for line in mounts.lines() {
+            if let Some(point) = line.split(' ').nth(1).map(unescape_mount)
+                && ["/media/", "/run/media/", "/mnt/"].iter().any(|p| point.starts_with(p))
+            {
+                points.push(PathBuf::from(point));
+            }
+        }
+    }
+    // macOS mounts every volume here.
+    if let Ok(entries) = fs::read_dir("/Volumes") {
+        points.extend(entries.filter_map(|e| e.ok()).map(|e| e.path()));
+    }
+    points
+}
+
+/// `/proc/mounts` writes spaces, tabs and backslashes in paths as octal escapes.
+fn unescape_mount(field: &str) -> String {
+    field.replace("\\040", " ").replace("\\011", "\t").replace("\\134", "\\")
+}
@@ -0,0 +1,313 @@
+use crate::archive;
+use crate::compiler;
+use crate::config::Sort;
+use crate::exif;
+use crate::error::{CompileError, Result};
+use crate::source::{DirSource, Source};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::LazyLock,
+};
+
+pub const DEFAULT_PATTERN: &str = r"^{folder}-(\d+|[ivxlc]+)\.(jpg|jpeg|png)$";
+/// Names carrying a chapter and a page such as `c003_p012.jpg` or `Series Ch.3 - 12.png`.
+pub const CHAPTER_PAGE_PATTERN: &str =
+    r"\b(?:ch(?:apter)?\.?|c)\s*(?P<chapter>\d+(?:\.\d+)?)\D+(?P<page>\d+)\.(jpg|jpeg|png)$";
+
+static COPY_SUFFIX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(.+?) ?\((\d+)\)(\.[^.]+)$").unwrap());
+
+/// How [`discover_pages`] recognizes page files.
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// Filename regex matched case-insensitively; `{folder}` expands to the escaped folder
+    /// name and group 1, or the group named `page`, is the page number. A group named `chapter`
+    /// groups the pages by chapter. Defaults to [`DEFAULT_PATTERN`].
+    pub pattern: Option<String>,
+    /// [`Sort::Mtime`] and [`Sort::Exif`] take every image in the folder and ignore the pattern.
+    pub sort: Option<Sort>,
+}
+
+/// A file whose name matched the page pattern.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Page {
+    pub number: u32,
+    /// The chapter the name gives, for a pattern with a `chapter` group.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chapter: Option<f64>,
+    /// Place among the front matter before page 1: 0 for page 0, then 1, 2, ... for pages
+    /// numbered `i`, `ii`, ... Front matter pages all have number 0.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub front: Option<u32>,
+    /// `YYYY-MM-DD HH:MM:SS` the page was taken or modified, for pages ordered by time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+    pub path: PathBuf,
+}
+
+/// A selection of page numbers such as `1-20,35,40-`, the last range open to the end. Front
+/// matter has number 0.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PageRanges(Vec<(u32, Option<u32>)>);
+
+impl PageRanges {
+    pub fn contains(&self, number: u32) -> bool {
+        self.0.iter().any(|&(first, last)| number >= first && last.is_none_or(|last| number <= last))
+    }
+}
+
+impl FromStr for PageRanges {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, String> {
+        let number = |n: &str| n.trim().parse::<u32>().ok();
+        let mut ranges = Vec::new();
+        for part in s.split(',') {
+            let range = match part.split_once('-') {
+                Some((first, "")) => number(first).map(|first| (first, None)),
+                Some((first, last)) => {
+                    number(first).zip(number(last)).filter(|(f, l)| f <= l).map(|(f, l)| (f, Some(l)))
+                }
+                None => number(part).map(|n| (n, Some(n))),
+            };
+            ranges.push(range.ok_or_else(|| format!("{:?} is not a page or range of pages such as 3 or 1-20", part))?);
+        }
+        Ok(PageRanges(ranges))
+    }
+}
+
+impl TryFrom<String> for PageRanges {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, String> {
+        s.parse()
+    }
+}
+
+impl From<PageRanges> for String {
+    fn from(ranges: PageRanges) -> String {
+        let range = |&(first, last): &(u32, Option<u32>)| match last {
+            Some(last) if last == first => first.to_string(),
+            Some(last) => format!("{}-{}", first, last),
+            None => format!("{}-", first),
+        };
+        ranges.0.iter().map(range).collect::<Vec<_>>().join(",")
+    }
+}
+
+/// The files of a folder sorted into pages and noise.
+#[derive(Debug, Clone, Serialize)]
+pub struct PageSet {
+    /// The pattern after `{folder}` expansion.
+    pub pattern: String,
+    /// Ordered by page number, or by chapter and the page within it, then numbered in that order.
+    pub pages: Vec<Page>,
+    /// Files that did not match the pattern, in file name order.
+    pub noise: Vec<PathBuf>,
+    /// Copies of a page such as `page-5 (1).jpg` that lost to another copy of it.
+    pub duplicates: Vec<PathBuf>,
+    /// Numbers absent between 1 and the highest page.
+    pub missing: Vec<u32>,
+    /// For pages grouped by chapter instead, the numbers absent in each chapter by its label.
+    pub chapter_gaps: Vec<(String, Vec<u32>)>,
+}
+
+impl PageSet {
+    pub fn noise_names(&self) -> Vec<String> {
+        file_names(&self.noise)
+    }
+}
+
+pub(crate) fn file_names(paths: &[PathBuf]) -> Vec<String> {
+    paths.iter().map(|n| n.file_name().unwrap().to_string_lossy().into_owned()).collect()
+}
+
+/// Lists the files directly inside `folder` and classifies them, without reading any of them.
+///
+/// ```no_run
+/// use manga_compiler::discover::{Options, discover_pages};
+///
+/// let set = discover_pages("Yotsuba v01".as_ref(), &Options::default())?;
+/// println!("{} pages, missing {:?}", set.pages.len(), set.missing);
+/// # Ok::<(), manga_compiler::CompileError>(())
+/// ```
+pub fn discover_pages(folder: &Path, options: &Options) -> Result<PageSet> {
+    let source = DirSource::new(folder).ok_or_else(|| CompileError::InvalidFolder(folder.to_path_buf()))?;
+    discover_in(&source, options).map_err(|e| match e {
+        CompileError::Io { source, .. } => CompileError::Io { path: folder.to_path_buf(), source },
+        e => e,
+    })
+}
+
+/// [`discover_pages`] for any [`Source`]. Without a pattern of its own, a folder whose files do
+/// not match [`DEFAULT_PATTERN`] is tried with [`CHAPTER_PAGE_PATTERN`].
+pub fn discover_in(source: &dyn Source, options: &Options) -> Result<PageSet> {
+    if let Some(sort @ (Sort::Mtime | Sort::Exif)) = options.sort {
+        return scan_by_time(source, sort);
+    }
+    let set = scan(source, options.pattern.as_deref().unwrap_or(DEFAULT_PATTERN))?;
+    if options.pattern.is_none() && set.pages.is_empty() {
+        let grouped = scan(source, CHAPTER_PAGE_PATTERN)?;
+        if !grouped.pages.is_empty() {
+            return Ok(grouped);
+        }
+    }
+    Ok(set)
+}
+
+fn scan(source: &dyn Source, pattern: &str) -> Result<PageSet> {
+    let pattern = pattern.replace("{folder}", &regex::escape(source.name()));
+    let name_rx = RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|source| CompileError::InvalidPattern { pattern: pattern.clone(), source })?;
+    let grouped = name_rx.capture_names().any(|name| name == Some("chapter"));
+
+    let mut files = source.files().map_err(CompileError::io(source.name()))?;
+    files.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+    let mut found = Vec::new();
+    let mut noise = Vec::new();
+    for path in files {
+        let fname = path.file_name().map(|f| f.to_string_lossy().into_owned()).unwrap_or_default();
+        // A browser saving a file again names it `page-5 (1).jpg`, which stands for `page-5.jpg`.
+        let (name, copy) = match COPY_SUFFIX.captures(&fname) {
+            Some(caps) if !name_rx.is_match(&fname) => {
+                (format!("{}{}", &caps[1], &caps[3]), caps[2].parse().unwrap_or(u32::MAX))
+            }
+            _ => (fname, 0),
+        };
+        let caps = name_rx.captures(&name);
+        let number = caps.as_ref().and_then(|caps| page_number(caps.name("page").or_else(|| caps.get(1))?.as_str()));
+        let chapter = caps.as_ref().and_then(|caps| caps.name("chapter")?.as_str().parse::<f64>().ok());
+        match number {
+            Some((number, front)) => found.push((name, copy, Page { number, chapter, front, time: None, path })),
+            None => noise.push(path),
+        }
+    }
+
+    // Of a file and its copies the newest is kept, else the largest, else the one saved first.
+    found.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+    let mut pages = Vec::new();
+    let mut duplicates = Vec::new();
+    for copies in found.chunk_by(|a, b| a.0 == b.0) {
+        let best = copies
+            .iter()
+            .enumerate()
+            .max_by_key(|(i, (_, _, page))| (source.modified(&page.path), source.size(&page.path), Reverse(*i)))
+            .map_or(0, |(i, _)| i);
+        for (i, (_, _, page)) in copies.iter().enumerate() {
+            if i == best {
+                pages.push(page.clone());
+            } else {
+                duplicates.push(page.path.clone());
+            }
+        }
+    }
+
+    if !grouped {
+        pages.sort_by_key(|p| (p.number, p.front));
+        let numbers: Vec<u32> = pages.iter().map(|p| p.number).collect();
+        let missing = archive::missing_pages(&numbers);
+        return Ok(PageSet { pattern, pages, noise, duplicates, missing, chapter_gaps: Vec::new() });
+    }
+
+    // Pages are checked for gaps within their chapter, then numbered through the book.
+    let chapter_of = |p: &Page| p.chapter.unwrap_or(f64::NEG_INFINITY);
+    pages.sort_by(|a, b| chapter_of(a).total_cmp(&chapter_of(b)).then(a.number.cmp(&b.number)));
+    let mut chapter_gaps = Vec::new();
+    for chapter in pages.chunk_by(|a, b| a.chapter == b.chapter) {
+        let missing = archive::missing_pages(&chapter.iter().map(|p| p.number).collect::<Vec<_>>());
+        if !missing.is_empty() {
+            chapter_gaps.push((chapter_label(chapter[0].chapter), missing));
+        }
+    }
+    for (number, page) in (1..).zip(&mut pages) {
+        page.number = number;
+    }
+    Ok(PageSet { pattern, pages, noise, duplicates, missing: Vec::new(), chapter_gaps })
+}
+
+/// A page number and the page's place in the front matter, for `12`, `0` or a roman numeral.
+fn page_number(text: &str) -> Option<(u32, Option<u32>)> {
+    match text.parse::<u32>() {
+        Ok(0) => Some((0, Some(0))),
+        Ok(number) => Some((number, None)),
+        Err(_) => Some((0, Some(roman(text)?))),
+    }
+}
+
+/// The value of a roman numeral written the usual way, such as `iv` or `XII`.
+fn roman(text: &str) -> Option<u32> {
+    let digit = |c: char| match c.to_ascii_lowercase() {
+        'i' => Some(1),
+        'v' => Some(5),
+        'x' => Some(10),
+        'l' => Some(50),
+        'c' => Some(100),
+        _ => None,
+    };
+    let digits: Vec<u32> = text.chars().map(digit).collect::<Option<_>>()?;
+    // A digit before a larger one is subtracted, as in `iv`.
+    let signed = |i: usize| {
+        let digit = i64::from(digits[i]);
+        if digits.get(i + 1).is_some_and(|&next| next > digits[i]) { -digit } else { digit }
+    };
+    let value: i64 = (0..digits.len()).map(signed).sum();
+    // Only the canonical spelling, so words such as `civil` are not read as numbers.
+    let value = u32::try_from(value).ok().filter(|&v| v > 0)?;
+    (to_roman(value) == text.to_ascii_lowercase()).then_some(value)
+}
+
+fn to_roman(mut value: u32) -> String {
+    const NUMERALS: [(u32, &str); 9] =
+        [(100, "c"), (90, "xc"), (50, "l"), (40, "xl"), (10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i")];
+    let mut text = String::new();
+    for (step, numeral) in NUMERALS {
+        while value >= step {
+            text.push_str(numeral);
+            value -= step;
+        }
+    }
+    text
+}
+
+/// Every image in `source`, oldest first; images without a known time follow in name order.
+fn scan_by_time(source: &dyn Source, sort: Sort) -> Result<PageSet> {
+    let mut files = source.files().map_err(CompileError::io(source.name()))?;
+    let name = |path: &PathBuf| path.file_name().map(|f| f.to_string_lossy().into_owned()).unwrap_or_default();
+    files.sort_by(|a, b| archive::natural_cmp(&name(a), &name(b)));
+    let mut timed = Vec::new();
+    let mut noise = Vec::new();
+    for path in files {
+        if !archive::is_image(&name(&path)) {
+            noise.push(path);
+            continue;
+        }
+        let taken = match sort {
+            Sort::Exif => source.read(&path).ok().and_then(|data| exif::date_taken(&data)),
+            _ => None,
+        };
+        let time = taken.or_else(|| source.modified(&path).and_then(compiler::timestamp));
+        timed.push((time, path));
+    }
+    timed.sort_by(|a, b| a.0.is_none().cmp(&b.0.is_none()).then_with(|| a.0.cmp(&b.0)));
+    let page = |(number, (time, path))| Page { number, chapter: None, front: None, time, path };
+    let pages = (1..).zip(timed).map(page).collect();
+    let pattern = format!(r"\.({})$", archive::IMAGE_EXTENSIONS.join("|"));
+    Ok(PageSet { pattern, pages, noise, duplicates: Vec::new(), missing: Vec::new(), chapter_gaps: Vec::new() })
+}
+
+/// `3` or `15.5`, how a chapter is named in reports; pages matched without one belong to `-`.
+pub fn chapter_label(chapter: Option<f64>) -> String {
+    chapter.map_or_else(|| "-".to_string(), |c| c.to_string())
+}
+
+/// `chapter 3: [4, 5], chapter 7: [2]`
+pub fn describe_gaps(gaps: &[(String, Vec<u32>)]) -> String {
+    let chapters = gaps.iter().map(|(chapter, missing)| format!("chapter {}: {:?}", chapter, missing));
+    chapters.collect::<Vec<_>>().join(", ")
+}
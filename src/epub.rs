@@ -0,0 +1,172 @@
+//! Fixed-layout EPUB 3 books of whole-page images, one XHTML page per image, for readers that
+//! do not open CBZ. The KEPUB variant adds the markup Kobo's own renderer looks for.
+
+use crate::comicinfo::{self, ComicInfo, escape};
+use crate::config::Direction;
+use image::{ImageFormat, ImageReader};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    io::{self, Cursor, Seek, Write},
+};
+use zip::{CompressionMethod, ZipWriter, write::FileOptions};
+
+const CONTAINER: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n  \
+<rootfiles>\n    \
+<rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>\n  \
+</rootfiles>\n\
+</container>\n";
+
+/// Writes `pages`, each its name in the archive and its contents in reading order, as an EPUB
+/// described by `info`, its bookmarks becoming the table of contents. `modified` is the
+/// required `dcterms:modified` time, e.g. `2024-05-01T12:00:00Z`; with `kobo` it is a KEPUB.
+pub fn write<W: Write + Seek>(
+    sink: W,
+    pages: &[(String, Vec<u8>)],
+    info: &ComicInfo,
+    modified: &str,
+    kobo: bool,
+) -> io::Result<W> {
+    let mut zip = ZipWriter::new(sink);
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    // Readers recognise the book by this first entry, which has to be stored uncompressed.
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/epub+zip")?;
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(CONTAINER.as_bytes())?;
+
+    let title = info.title.as_deref().unwrap_or_default();
+    let mut manifest = String::new();
+    let mut spine = String::new();
+    let mut hasher = DefaultHasher::new();
+    for (number, (name, data)) in (1..).zip(pages) {
+        data.hash(&mut hasher);
+        let (format, width, height) = probe(name, data)?;
+        let (ext, media_type) = match format {
+            ImageFormat::Png => ("png", "image/png"),
+            _ => ("jpg", "image/jpeg"),
+        };
+        zip.start_file(format!("OEBPS/images/{:04}.{}", number, ext), stored)?;
+        zip.write_all(data)?;
+        zip.start_file(format!("OEBPS/pages/{:04}.xhtml", number), deflated)?;
+        zip.write_all(page(title, number, ext, width, height, kobo).as_bytes())?;
+        let cover = if number == 1 { " properties=\"cover-image\"" } else { "" };
+        manifest.push_str(&format!(
+            "    <item id=\"image-{n:04}\" href=\"images/{n:04}.{ext}\" media-type=\"{media_type}\"{cover}/>\n    \
+             <item id=\"page-{n:04}\" href=\"pages/{n:04}.xhtml\" media-type=\"application/xhtml+xml\"/>\n",
+            n = number
+        ));
+        spine.push_str(&format!("    <itemref idref=\"page-{:04}\"/>\n", number));
+    }
+
+    zip.start_file("OEBPS/nav.xhtml", deflated)?;
+    zip.write_all(nav(info, pages.len()).as_bytes())?;
+    zip.start_file("OEBPS/content.opf", deflated)?;
+    let identifier = format!("urn:manga-compiler:{:016x}", hasher.finish());
+    zip.write_all(package(info, &identifier, modified, &manifest, &spine).as_bytes())?;
+    Ok(zip.finish()?)
+}
+
+/// Format and size of a page, which the fixed layout needs for its viewport.
+fn probe(name: &str, data: &[u8]) -> io::Result<(ImageFormat, u32, u32)> {
+    let invalid = |_| io::Error::new(io::ErrorKind::InvalidData, format!("{} is not a JPEG or PNG image", name));
+    let format = image::guess_format(data).map_err(invalid)?;
+    let (width, height) = ImageReader::with_format(Cursor::new(data), format).into_dimensions().map_err(invalid)?;
+    Ok((format, width, height))
+}
+
+fn page(title: &str, number: usize, ext: &str, width: u32, height: u32, kobo: bool) -> String {
+    let image = format!("<img src=\"../images/{:04}.{}\" alt=\"{}\"/>", number, ext, number);
+    // Kobo's renderer pages and tracks reading progress by these wrappers and spans.
+    let body = if kobo {
+        let span = format!("<span class=\"koboSpan\" id=\"kobo.1.1\">{}</span>", image);
+        format!("<div id=\"book-columns\"><div id=\"book-inner\">{}</div></div>", span)
+    } else {
+        image
+    };
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <!DOCTYPE html>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">\n\
+         <head>\n  \
+         <title>{title} - {number}</title>\n  \
+         <meta name=\"viewport\" content=\"width={width}, height={height}\"/>\n  \
+         <style>html, body {{ margin: 0; padding: 0; }} img {{ display: block; width: 100%; height: 100%; }}</style>\n\
+         </head>\n\
+         <body>\n  {body}\n</body>\n\
+         </html>\n",
+        title = escape(title)
+    )
+}
+
+/// The table of contents: a link to each bookmarked page, or to the first page under the title.
+fn nav(info: &ComicInfo, page_count: usize) -> String {
+    let title = info.title.as_deref().unwrap_or_default();
+    let mut entries: Vec<(usize, &str)> =
+        info.bookmarks.iter().filter(|(i, _)| *i < page_count).map(|(i, text)| (*i, text.as_str())).collect();
+    if entries.is_empty() {
+        entries.push((0, title));
+    }
+    let mut items = String::new();
+    for (index, text) in entries {
+        items.push_str(&format!("      <li><a href=\"pages/{:04}.xhtml\">{}</a></li>\n", index + 1, escape(text)));
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <!DOCTYPE html>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">\n\
+         <head><title>{title}</title></head>\n\
+         <body>\n  \
+         <nav epub:type=\"toc\">\n    <ol>\n{items}    </ol>\n  </nav>\n\
+         </body>\n\
+         </html>\n",
+        title = escape(title)
+    )
+}
+
+/// The OPF package document: the metadata of ComicInfo.xml, with the series both as EPUB 3
+/// collection and in Calibre's metas, and the pages as a pre-paginated spine.
+fn package(info: &ComicInfo, identifier: &str, modified: &str, manifest: &str, spine: &str) -> String {
+    let mut metadata = format!(
+        "    <dc:identifier id=\"id\">{}</dc:identifier>\n    \
+         <dc:title>{}</dc:title>\n    \
+         <dc:language>und</dc:language>\n",
+        identifier,
+        escape(info.title.as_deref().unwrap_or_default())
+    );
+    for author in info.writer.as_deref().into_iter().flat_map(comicinfo::authors) {
+        metadata.push_str(&format!("    <dc:creator>{}</dc:creator>\n", escape(author)));
+    }
+    if let Some(series) = &info.series {
+        let series = escape(series);
+        metadata.push_str(&format!("    <meta property=\"belongs-to-collection\" id=\"series\">{}</meta>\n", series));
+        metadata.push_str("    <meta refines=\"#series\" property=\"collection-type\">series</meta>\n");
+        metadata.push_str(&format!("    <meta name=\"calibre:series\" content=\"{}\"/>\n", series));
+        if let Some(index) = info.volume.map(f64::from).or(info.number) {
+            metadata.push_str(&format!("    <meta refines=\"#series\" property=\"group-position\">{}</meta>\n", index));
+            metadata.push_str(&format!("    <meta name=\"calibre:series_index\" content=\"{}\"/>\n", index));
+        }
+    }
+    metadata.push_str(&format!("    <meta property=\"dcterms:modified\">{}</meta>\n", modified));
+    metadata.push_str("    <meta property=\"rendition:layout\">pre-paginated</meta>\n");
+    metadata.push_str("    <meta property=\"rendition:spread\">landscape</meta>\n");
+    // EPUB 2 readers find the cover this way.
+    metadata.push_str("    <meta name=\"cover\" content=\"image-0001\"/>\n");
+    let direction = match info.direction {
+        Some(Direction::Rtl) => " page-progression-direction=\"rtl\"",
+        _ => "",
+    };
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"id\" \
+         prefix=\"rendition: http://www.idpf.org/vocab/rendition/#\">\n  \
+         <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n{metadata}  </metadata>\n  \
+         <manifest>\n    \
+         <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n\
+         {manifest}  </manifest>\n  \
+         <spine{direction}>\n{spine}  </spine>\n\
+         </package>\n"
+    )
+}
@@ -0,0 +1,104 @@
+use crate::exit::Code;
+use std::{io, path::PathBuf, process::ExitStatus};
+
+pub type Result<T, E = CompileError> = std::result::Result<T, E>;
+
+/// Why compiling a book failed.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum CompileError {
+    #[error("No valid image files found matching pattern {pattern}")]
+    NoPagesFound { pattern: String },
+    #[error("Missing page numbers: {0:?}")]
+    MissingPages(Vec<u32>),
+    #[error("Missing page numbers: {}", crate::discover::describe_gaps(.0))]
+    MissingChapterPages(Vec<(String, Vec<u32>)>),
+    #[error("Invalid filename pattern {pattern}")]
+    InvalidPattern {
+        pattern: String,
+        #[source]
+        source: regex::Error,
+    },
+    #[error("Could not determine folder name of {}", .0.display())]
+    InvalidFolder(PathBuf),
+    #[error("{} is not a valid image: {reason}", path.display())]
+    InvalidImage { path: PathBuf, reason: &'static str },
+    #[error("{} does not have a UTF-8 name", .0.display())]
+    InvalidName(PathBuf),
+    #[error("Two entries of the archive would be named {0}")]
+    DuplicateEntry(String),
+    #[error("{} already exists", .0.display())]
+    OutputExists(PathBuf),
+    #[error("Failed to read {}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Failed to write {}", path.display())]
+    Write {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// A config file, environment variable or output template was rejected.
+    #[error("{0}")]
+    Config(String),
+    #[error("Failed to start {kind} hook {command:?}")]
+    HookStart {
+        kind: &'static str,
+        command: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{kind} hook {command:?} failed with {status}")]
+    Hook { kind: &'static str, command: String, status: ExitStatus },
+    #[error("Filter {command:?} failed on {page}: {reason}")]
+    Filter { command: String, page: String, reason: String },
+    #[error("Failed to start {command:?} to sign the archive")]
+    SignStart {
+        command: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Signing with {command:?} failed with {status}")]
+    Sign { command: String, status: ExitStatus },
+    /// The runtime running an async compilation shut down first.
+    #[error("Compilation was cancelled")]
+    Cancelled,
+}
+
+impl CompileError {
+    /// The process exit code the CLI reports for this error.
+    pub fn code(&self) -> Code {
+        match self {
+            CompileError::NoPagesFound { .. } => Code::NoPages,
+            CompileError::MissingPages(_) | CompileError::MissingChapterPages(_) => Code::MissingPages,
+            CompileError::InvalidPattern { .. } | CompileError::Config(_) => Code::Config,
+            CompileError::OutputExists(_) | CompileError::Write { .. } => Code::Write,
+            CompileError::HookStart { .. } | CompileError::Hook { .. } => Code::Hook,
+            CompileError::SignStart { .. } | CompileError::Sign { .. } => Code::Sign,
+            CompileError::Filter { .. } => Code::Filter,
+            CompileError::InvalidFolder(_)
+            | CompileError::InvalidImage { .. }
+            | CompileError::InvalidName(_)
+            | CompileError::DuplicateEntry(_)
+            | CompileError::Io { .. }
+            | CompileError::Cancelled => Code::Failure,
+        }
+    }
+
+    pub(crate) fn config(err: anyhow::Error) -> Self {
+        CompileError::Config(format!("{:#}", err))
+    }
+
+    pub(crate) fn io(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.into();
+        move |source| CompileError::Io { path, source }
+    }
+
+    pub(crate) fn write(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.into();
+        move |source| CompileError::Write { path, source }
+    }
+}
@@ -0,0 +1,82 @@
+//! When a photo or screenshot was taken, from the EXIF block of a JPEG or PNG.
+
+/// `DateTimeOriginal` as `YYYY-MM-DD HH:MM:SS`, in the local time of the device that took it.
+pub fn date_taken(data: &[u8]) -> Option<String> {
+    let tiff = Tiff::new(tiff_block(data)?)?;
+    let ifd0 = tiff.u32(4)? as usize;
+    let exif_ifd = tiff.u32(tiff.entry(ifd0, 0x8769)?)? as usize;
+    // 20 bytes of ASCII, too long to sit in the entry, so the entry holds their offset.
+    let start = tiff.u32(tiff.entry(exif_ifd, 0x9003)?)? as usize;
+    let text = std::str::from_utf8(tiff.data.get(start..start + 19)?).ok()?;
+    let valid = text.char_indices().all(|(i, c)| match i {
+        4 | 7 => c == ':',
+        10 => c == ' ',
+        13 | 16 => c == ':',
+        _ => c.is_ascii_digit(),
+    });
+    // Cameras without a clock write zeros.
+    if !valid || text.starts_with("0000") {
+        return None;
+    }
+    Some(format!("{}-{}-{}", &text[..4], &text[5..7], &text[8..]))
+}
+
+/// The TIFF structure holding the EXIF tags: a JPEG's APP1 segment or a PNG's `eXIf` chunk.
+fn tiff_block(data: &[u8]) -> Option<&[u8]> {
+    if let Some(mut rest) = data.strip_prefix(&[0xFF, 0xD8]) {
+        while rest.len() >= 4 && rest[0] == 0xFF {
+            let (marker, len) = (rest[1], usize::from(u16::from_be_bytes([rest[2], rest[3]])));
+            // The image data starts at SOS, after every segment of metadata.
+            if marker == 0xDA {
+                return None;
+            }
+            let segment = rest.get(4..2 + len)?;
+            if let Some(tiff) = segment.strip_prefix(b"Exif\0\0").filter(|_| marker == 0xE1) {
+                return Some(tiff);
+            }
+            rest = &rest[2 + len..];
+        }
+    } else if let Some(mut rest) = data.strip_prefix(b"\x89PNG\r\n\x1a\n") {
+        while rest.len() >= 12 {
+            let len = u32::from_be_bytes(rest[..4].try_into().ok()?) as usize;
+            let chunk = rest.get(8..8 + len)?;
+            if &rest[4..8] == b"eXIf" {
+                return Some(chunk);
+            }
+            rest = rest.get(12 + len..)?;
+        }
+    }
+    None
+}
+
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(..4)? {
+            b"II*\0" => true,
+            b"MM\0*" => false,
+            _ => return None,
+        };
+        Some(Tiff { data, little_endian })
+    }
+
+    fn u16(&self, at: usize) -> Option<u16> {
+        let bytes = self.data.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let bytes = self.data.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    }
+
+    /// Where the value of `tag` is in the directory at `ifd`.
+    fn entry(&self, ifd: usize, tag: u16) -> Option<usize> {
+        let count = usize::from(self.u16(ifd)?);
+        (0..count).map(|i| ifd + 2 + 12 * i).find(|&at| self.u16(at) == Some(tag)).map(|at| at + 8)
+    }
+}
@@ -0,0 +1,71 @@
+use crate::error::CompileError;
+use std::fmt;
+
+/// Process exit codes, documented in `--help`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Code {
+    Failure = 1,
+    Usage = 2,
+    NoPages = 3,
+    MissingPages = 4,
+    Write = 5,
+    Validation = 6,
+    Config = 7,
+    Batch = 8,
+    Hook = 9,
+    Sign = 10,
+    Filter = 11,
+}
+
+pub const HELP: &str = "\
+Exit codes:
+  0  success
+  1  unexpected failure
+  2  invalid command line usage
+  3  no files matched the page pattern
+  4  page numbers are missing
+  5  the output could not be written
+  6  an archive failed verification
+  7  a config file or pattern is invalid
+  8  some books of a batch failed
+  9  a pre or post build hook failed
+ 10  the archive could not be signed
+ 11  a --filter-cmd command failed on a page";
+
+/// Tags an error with the exit code it should produce; found anywhere in the error chain.
+#[derive(Debug)]
+pub struct Coded {
+    code: Code,
+    inner: anyhow::Error,
+}
+
+impl fmt::Display for Coded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#}", self.inner)
+    }
+}
+
+impl std::error::Error for Coded {}
+
+pub fn fail(code: Code, message: impl fmt::Display) -> anyhow::Error {
+    Coded { code, inner: anyhow::anyhow!("{}", message) }.into()
+}
+
+pub trait WithCode<T> {
+    fn code(self, code: Code) -> anyhow::Result<T>;
+}
+
+impl<T, E: Into<anyhow::Error>> WithCode<T> for Result<T, E> {
+    fn code(self, code: Code) -> anyhow::Result<T> {
+        self.map_err(|e| Coded { code, inner: e.into() }.into())
+    }
+}
+
+pub fn code_of(err: &anyhow::Error) -> Code {
+    err.chain()
+        .find_map(|e| match e.downcast_ref::<Coded>() {
+            Some(coded) => Some(coded.code),
+            None => e.downcast_ref::<CompileError>().map(CompileError::code),
+        })
+        .unwrap_or(Code::Failure)
+}
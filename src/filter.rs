@@ -0,0 +1,60 @@
+//! The external command of `filter-cmd`, which every page goes through on its way into the
+//! archive, for processing the compiler does not do itself.
+
+use crate::config::OnFilterError;
+use crate::hooks;
+use std::{io::Write, process::Stdio, thread};
+
+/// A shell command reading a page on stdin and printing it, processed and in the same image
+/// format, on stdout, e.g. `magick - -deskew 40% -`. It sees the page's entry name as
+/// `MANGA_COMPILER_PAGE`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    pub command: String,
+    /// Pages filtered at the same time.
+    pub jobs: usize,
+    pub on_error: OnFilterError,
+}
+
+impl Filter {
+    pub fn new(command: String, jobs: Option<usize>, on_error: Option<OnFilterError>) -> Filter {
+        let cpus = || thread::available_parallelism().map_or(1, |n| n.get());
+        Filter { command, jobs: jobs.unwrap_or_else(cpus).max(1), on_error: on_error.unwrap_or_default() }
+    }
+
+    /// Runs each of `pages`, named and with their data, through the command, [`Filter::jobs`] at a
+    /// time. A page the command fails on gives what went wrong instead.
+    pub fn run_all(&self, pages: &[(&str, &[u8])]) -> Vec<Result<Vec<u8>, String>> {
+        let mut results = Vec::with_capacity(pages.len());
+        for batch in pages.chunks(self.jobs) {
+            thread::scope(|scope| {
+                let running: Vec<_> =
+                    batch.iter().map(|&(name, data)| scope.spawn(move || self.run(name, data))).collect();
+                results.extend(running.into_iter().map(|r| r.join().unwrap_or_else(|_| Err("panicked".to_string()))));
+            });
+        }
+        results
+    }
+
+    fn run(&self, name: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+        let mut cmd = hooks::shell(&self.command);
+        cmd.env("MANGA_COMPILER_PAGE", name).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        let mut child = cmd.spawn().map_err(|e| format!("could not be started: {}", e))?;
+        let stdin = child.stdin.take();
+        let output = thread::scope(|scope| {
+            // Fed from a thread of its own, since a filter may print before it has read the whole
+            // page. One that stops reading early is judged by its exit status alone.
+            scope.spawn(move || stdin.map(|mut stdin| stdin.write_all(data)));
+            child.wait_with_output()
+        });
+        let output = output.map_err(|e| format!("could not be read from: {}", e))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return match stderr.lines().map(str::trim).rfind(|line| !line.is_empty()) {
+                Some(line) => Err(format!("exited with {}: {}", output.status, line)),
+                None => Err(format!("exited with {}", output.status)),
+            };
+        }
+        Ok(output.stdout)
+    }
+}
@@ -0,0 +1,32 @@
+use crate::error::{CompileError, Result};
+use std::{io, process::Command};
+
+/// Runs `command` through the platform shell with `env` added, failing if it exits unsuccessfully.
+pub fn run(kind: &'static str, command: &str, env: &[(&str, String)]) -> Result<()> {
+    let mut cmd = shell(command);
+    // Hook output goes to stderr so it can never corrupt `--json` output on stdout.
+    cmd.stdout(io::stderr()).env("MANGA_COMPILER_HOOK", kind);
+    for (key, value) in env {
+        cmd.env(key, value);
+    }
+    let status = cmd
+        .status()
+        .map_err(|source| CompileError::HookStart { kind, command: command.to_string(), source })?;
+    if !status.success() {
+        return Err(CompileError::Hook { kind, command: command.to_string(), status });
+    }
+    Ok(())
+}
+
+/// `command` run by the platform shell.
+pub(crate) fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.arg("/C").arg(command);
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.arg("-c").arg(command);
+        c
+    }
+}
@@ -0,0 +1,97 @@
+use crate::compiler::Book;
+use crate::error::{CompileError, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{fs, path::Path};
+
+/// Library structures that media servers scan; each one fixes the output names.
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Layout {
+    /// `Series/Series - Volume NN.cbz` plus a Mylar-style `series.json` per series.
+    Komga,
+    /// `Series/Series Vol. NN Ch. NNN.cbz`, with specials in `Series/Specials/`.
+    Kavita,
+    /// Mihon's (Tachiyomi's) local source: `Series/Chapter NNN.cbz` with a cover and `details.json`.
+    Mihon,
+}
+
+impl Layout {
+    /// Output template relative to the library root, for a book with the given tokens.
+    pub fn template(self, volume: bool, chapter: bool, special: bool) -> &'static str {
+        match (self, volume, chapter, special) {
+            (Layout::Komga, true, true, _) => "{series}/{series} - Volume {volume:02} Chapter {chapter:03}.cbz",
+            (Layout::Komga, true, false, _) => "{series}/{series} - Volume {volume:02}.cbz",
+            (Layout::Komga, false, true, _) => "{series}/{series} - Chapter {chapter:03}.cbz",
+            (Layout::Komga, false, false, _) => "{series}/{series} - {title}.cbz",
+            // Kavita treats any file below a Specials folder as a special, whatever its name.
+            (Layout::Kavita, _, _, true) => "{series}/Specials/{series} - {title}.cbz",
+            (Layout::Kavita, true, true, false) => "{series}/{series} Vol. {volume:02} Ch. {chapter:03}.cbz",
+            (Layout::Kavita, true, false, false) => "{series}/{series} Vol. {volume:02}.cbz",
+            (Layout::Kavita, false, true, false) => "{series}/{series} Ch. {chapter:03}.cbz",
+            (Layout::Kavita, false, false, false) => "{series}/Specials/{series} - {title}.cbz",
+            (Layout::Mihon, true, true, _) => "{series}/Vol. {volume:02} Chapter {chapter:03}.cbz",
+            (Layout::Mihon, false, true, _) => "{series}/Chapter {chapter:03}.cbz",
+            (Layout::Mihon, true, false, _) => "{series}/Vol. {volume:02}.cbz",
+            (Layout::Mihon, false, false, _) => "{series}/{title}.cbz",
+        }
+    }
+
+    /// Creates or updates the per-series files after `archive` was written into its series folder.
+    pub fn write_series_files(self, archive: &Path, book: &Book) -> Result<()> {
+        let Some(dir) = archive.parent() else { return Ok(()) };
+        match self {
+            Layout::Komga => write_series_json(dir, book.series()),
+            Layout::Kavita => Ok(()),
+            Layout::Mihon => write_mihon_details(dir, book),
+        }
+    }
+}
+
+/// Keeps every field already in `series.json`, so edits made in the server survive recompiles.
+fn write_series_json(dir: &Path, series: &str) -> Result<()> {
+    let path = dir.join("series.json");
+    let mut doc = match fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map_err(|e| CompileError::Config(format!("Invalid {}: {}", path.display(), e)))?,
+        Err(_) => json!({ "version": "1.0.2", "metadata": { "type": "comicSeries", "booktype": "Print" } }),
+    };
+    let issues = fs::read_dir(dir)
+        .map_err(CompileError::io(dir))?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|x| x.eq_ignore_ascii_case("cbz")))
+        .count();
+    if !doc.is_object() {
+        return Err(CompileError::Config(format!("Invalid {}: not a JSON object", path.display())));
+    }
+    if !doc["metadata"].is_object() {
+        doc["metadata"] = json!({});
+    }
+    doc["metadata"]["name"] = Value::from(series);
+    doc["metadata"]["total_issues"] = Value::from(issues);
+    let text = serde_json::to_string_pretty(&doc).unwrap() + "\n";
+    fs::write(&path, text).map_err(CompileError::write(&path))
+}
+
+/// Mihon shows `cover.*` as the series cover; the cover of the first book compiled stands in
+/// until one is added by hand. `details.json` is only created, never changed.
+fn write_mihon_details(dir: &Path, book: &Book) -> Result<()> {
+    let has_cover = fs::read_dir(dir)
+        .map_err(CompileError::io(dir))?
+        .filter_map(|e| e.ok())
+        .any(|e| Path::new(&e.file_name()).file_stem().is_some_and(|s| s == "cover"));
+    if let (false, Some(source)) = (has_cover, book.cover()) {
+        let ext = source.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        let cover = dir.join(format!("cover.{}", ext));
+        fs::copy(source, &cover).map_err(CompileError::write(&cover))?;
+    }
+    let details = dir.join("details.json");
+    if !details.exists() {
+        let doc = json!({ "title": book.series(), "status": "0" });
+        let text = serde_json::to_string_pretty(&doc).unwrap() + "\n";
+        fs::write(&details, text).map_err(CompileError::write(&details))?;
+    }
+    Ok(())
+}
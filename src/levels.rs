@@ -0,0 +1,90 @@
+//! Levels matched across the chapters of a book, for volumes assembled from chapters of
+//! different scans whose paper and ink would otherwise change tone from one chapter to the next.
+
+use std::array;
+
+/// Share of the darkest and of the lightest pixels of a chapter taken as its ink and paper.
+const CLIP: f64 = 0.01;
+/// Chapters whose black and white points are closer than this are too flat to measure.
+const MIN_RANGE: u8 = 32;
+/// Chapters this close to the book's points in both are left alone.
+const CLOSE: u8 = 3;
+/// Pixels sampled from a page at most.
+const SAMPLES: usize = 250_000;
+
+/// Counts of each gray value among the pixels of some pages.
+pub type Histogram = [u64; 256];
+
+/// A linear map of the gray values taking the `black` and `white` points of a chapter to the
+/// book's `target_black` and `target_white`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Levels {
+    pub black: u8,
+    pub white: u8,
+    pub target_black: u8,
+    pub target_white: u8,
+}
+
+impl Levels {
+    pub fn table(&self) -> [u8; 256] {
+        let (black, white) = (f64::from(self.black), f64::from(self.white));
+        let (to_black, to_white) = (f64::from(self.target_black), f64::from(self.target_white));
+        let scale = (to_white - to_black) / (white - black).max(1.0);
+        array::from_fn(|value| (to_black + (value as f64 - black) * scale).round().clamp(0.0, 255.0) as u8)
+    }
+}
+
+/// Adds the gray values of the page `data` to `histogram`, sampling large pages. `false` if it
+/// cannot be decoded.
+pub fn add_page(histogram: &mut Histogram, data: &[u8]) -> bool {
+    let Ok(page) = image::load_from_memory(data) else { return false };
+    let gray = page.to_luma8();
+    let step = (gray.as_raw().len() / SAMPLES).max(1);
+    for value in gray.as_raw().iter().step_by(step) {
+        histogram[usize::from(*value)] += 1;
+    }
+    true
+}
+
+/// The gray values with [`CLIP`] of the pixels below and above them, `None` for a histogram
+/// without enough contrast to tell ink from paper.
+pub fn points(histogram: &Histogram) -> Option<(u8, u8)> {
+    let total: u64 = histogram.iter().sum();
+    let clip = (total as f64 * CLIP) as u64;
+    let black = percentile(histogram.iter().enumerate(), clip)?;
+    let white = percentile(histogram.iter().enumerate().rev(), clip)?;
+    (white >= black.saturating_add(MIN_RANGE)).then_some((black, white))
+}
+
+/// The first gray value of `values` past `clip` pixels.
+fn percentile<'a>(values: impl Iterator<Item = (usize, &'a u64)>, clip: u64) -> Option<u8> {
+    let mut seen = 0;
+    for (value, count) in values {
+        seen += count;
+        if seen > clip {
+            return Some(value as u8);
+        }
+    }
+    None
+}
+
+/// For each chapter, given by the histogram of its pages, the levels taking its black and white
+/// points to the median ones of all chapters; `None` for chapters that cannot be measured or
+/// already match.
+pub fn match_chapters(chapters: &[Histogram]) -> Vec<Option<Levels>> {
+    let points: Vec<Option<(u8, u8)>> = chapters.iter().map(points).collect();
+    let median = |mut values: Vec<u8>| {
+        values.sort_unstable();
+        values.get(values.len() / 2).copied()
+    };
+    let target_black = median(points.iter().flatten().map(|p| p.0).collect());
+    let target_white = median(points.iter().flatten().map(|p| p.1).collect());
+    let (Some(target_black), Some(target_white)) = (target_black, target_white) else {
+        return vec![None; chapters.len()];
+    };
+    let levels = |(black, white): (u8, u8)| {
+        let close = black.abs_diff(target_black) <= CLOSE && white.abs_diff(target_white) <= CLOSE;
+        (!close).then_some(Levels { black, white, target_black, target_white })
+    };
+    points.into_iter().map(|p| p.and_then(levels)).collect()
+}
@@ -0,0 +1,54 @@
+//! Compile folders of numbered manga pages into CBZ archives.
+//!
+//! The binary is a thin layer over [`Compiler`]; other tools can embed the same pipeline:
+//!
+//! ```no_run
+//! let summary = manga_compiler::Compiler::new("Berserk v01").series("Berserk").volume(1).run()?;
+//! println!("{} pages", summary.stats.pages);
+//! # Ok::<(), manga_compiler::CompileError>(())
+//! ```
+
+pub mod archive;
+pub mod comicinfo;
+pub mod compiler;
+pub mod config;
+pub mod cover;
+pub mod device;
+pub mod discover;
+pub mod epub;
+pub mod error;
+pub mod exif;
+pub mod exit;
+pub mod filter;
+pub mod hooks;
+pub mod layout;
+pub mod levels;
+pub mod names;
+pub mod ocr;
+pub mod opf;
+pub mod paths;
+pub mod pdf;
+#[cfg(feature = "async")]
+pub mod nonblocking;
+pub mod progress;
+pub mod quality;
+pub mod sign;
+pub mod source;
+pub mod summary;
+pub mod template;
+pub mod titlepage;
+pub mod volumes;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+
+pub use compiler::{Book, ChapterMark, Compiler, Packing, compile_source};
+pub use config::{
+    Compression, Config, DedupeExact, Direction, Format, MemorySize, MissingPolicy, OnFilterError, Preset, Sort,
+};
+pub use device::Device;
+pub use discover::{Options, Page, PageRanges, PageSet, discover_pages};
+pub use error::CompileError;
+pub use layout::Layout;
+pub use progress::{ProgressObserver, Warning};
+pub use source::{DirSource, MemorySource, Source};
+pub use summary::{Stats, Summary};
@@ -1,93 +1,149 @@
-use anyhow::{Context, Result};
-use clap::Parser;
-use regex::RegexBuilder;
-use std::{collections::HashSet, fs::File, io::{Read, Write}, path::PathBuf};
-use walkdir::WalkDir;
-use zip::write::FileOptions;
+mod cli;
+
+use anyhow::Result;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use cli::style::{self, Tone};
+#[cfg(feature = "tui")]
+use cli::review;
+use cli::{
+    batch, build, catalog, convert, diff, extract, i18n, inspect, log, merge, meta, rename, split, sync, update,
+    verify, volumes,
+};
+use manga_compiler::exit;
+use std::{path::PathBuf, process::ExitCode};
 
 #[derive(Parser, Debug)]
-#[command(author, version, about)]
-struct Args {
-    folder: PathBuf,
-    #[arg(short, long)]
-    output: Option<PathBuf>,
+#[command(
+    author,
+    version,
+    about,
+    after_help = exit::HELP,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    build: build::BuildArgs,
+    /// Disable colored output (also disabled by setting NO_COLOR)
+    #[arg(long, global = true)]
+    no_color: bool,
+    /// Append a timestamped record of matched and skipped files and of each page's processing
+    #[arg(long, global = true, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+    /// Language of the messages, instead of the one of the locale in LC_ALL, LC_MESSAGES or LANG
+    #[arg(long, global = true, value_enum)]
+    lang: Option<i18n::Lang>,
+    /// Print the man page, or write pages for every subcommand into DIR
+    #[arg(long, value_name = "DIR", num_args = 0..=1, exclusive = true, hide = true)]
+    generate_manpage: Option<Option<PathBuf>>,
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    let folder = &args.folder;
-
-    let title = folder
-        .file_name()
-        .and_then(|s| s.to_str())
-        .context("Could not determine folder name as title")?;
-
-    let pattern = format!(r"^{}-(\d+)\.(jpg|jpeg|png)$", regex::escape(title));
-    let name_rx = RegexBuilder::new(&pattern)
-        .case_insensitive(true)
-        .build()?;
-
-    let mut page_entries = Vec::new();
-    let mut noise = Vec::new();
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compile a folder of pages into a CBZ archive
+    Build(build::BuildArgs),
+    /// Compile every subfolder of one or more library folders
+    Batch(batch::BatchArgs),
+    /// Compile a series folder of chapter subfolders into volumes as mapped by volumes.toml
+    Volumes(volumes::VolumesArgs),
+    /// Join existing archives into one, e.g. chapters into a volume
+    Merge(merge::MergeArgs),
+    /// Cut a folder or archive of many chapters into chapter archives
+    Split(split::SplitArgs),
+    /// Check archives for corrupt entries and missing pages
+    Verify(verify::VerifyArgs),
+    /// Turn compiled archives into EPUB, KEPUB or PDF books, keeping their metadata
+    Convert(convert::ConvertArgs),
+    /// Unpack an archive into a folder
+    Extract(extract::ExtractArgs),
+    /// List the entries of an archive
+    Inspect(inspect::InspectArgs),
+    /// Show which pages were added, removed, moved or changed between two archives
+    Diff(diff::DiffArgs),
+    /// Show or edit the ComicInfo.xml of an archive
+    Meta(meta::MetaArgs),
+    /// Replace single pages of an archive, leaving every other entry as it was
+    Update(update::UpdateArgs),
+    /// Generate a static OPDS catalog of a folder of compiled archives
+    Catalog(catalog::CatalogArgs),
+    /// Rename the images of a folder to the canonical `<folder>-001.jpg` scheme
+    Rename(rename::RenameArgs),
+    /// Copy compiled books to a mounted Kindle or Kobo, skipping those already on it
+    Sync(sync::SyncArgs),
+    /// Review, reorder and exclude pages interactively before writing the archive
+    #[cfg(feature = "tui")]
+    Review(review::ReviewArgs),
+    /// Print a shell completion script to stdout
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
 
-    for entry in WalkDir::new(folder).min_depth(1).max_depth(1) {
-        let entry = entry?;
-        if !entry.file_type().is_file() {
-            continue;
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    style::init(cli.no_color);
+    i18n::init(cli.lang);
+    match run(cli) {
+        Ok(()) => {
+            log::line("Finished");
+            ExitCode::SUCCESS
         }
-        let fname = entry.file_name().to_string_lossy();
-        if let Some(caps) = name_rx.captures(&fname) {
-            if let Ok(n) = caps[1].parse::<u32>() {
-                page_entries.push((n, entry.path().to_path_buf()));
-            } else {
-                noise.push(fname.into_owned());
-            }
-        } else {
-            noise.push(fname.into_owned());
+        Err(e) => {
+            log::line(format_args!("Error: {:#}", e));
+            eprintln!("{}", style::err(Tone::Error, i18n::t!("Error: {0}", format!("{:#}", e))));
+            ExitCode::from(exit::code_of(&e) as u8)
         }
     }
+}
 
-    if !noise.is_empty() {
-        eprintln!("Warning: ignored files not matching pattern:");
-        for n in &noise {
-            eprintln!("  - {}", n);
-        }
+fn run(cli: Cli) -> Result<()> {
+    if let Some(dir) = cli.generate_manpage {
+        return generate_manpage(dir);
     }
-
-    if page_entries.is_empty() {
-        anyhow::bail!("No valid image files found matching pattern {}-<number>.<ext>", title);
+    if let Some(path) = &cli.log_file {
+        log::open(path)?;
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        log::line(format_args!("Started manga-compiler {}: {}", env!("CARGO_PKG_VERSION"), args.join(" ")));
     }
-
-    page_entries.sort_unstable_by_key(|(n, _)| *n);
-    let nums: Vec<u32> = page_entries.iter().map(|(n, _)| *n).collect();
-    let max_page = *nums.last().unwrap();
-    let num_set: HashSet<u32> = nums.iter().copied().collect();
-    let missing: Vec<u32> = (1..=max_page).filter(|i| !num_set.contains(i)).collect();
-
-    if !missing.is_empty() {
-        eprintln!("Missing page numbers: {:?}", missing);
-        std::process::exit(1);
+    match cli.command {
+        Some(Command::Build(args)) => build::run(&args),
+        Some(Command::Batch(args)) => batch::run(&args),
+        Some(Command::Volumes(args)) => volumes::run(&args),
+        Some(Command::Merge(args)) => merge::run(&args),
+        Some(Command::Split(args)) => split::run(&args),
+        Some(Command::Verify(args)) => verify::run(&args),
+        Some(Command::Convert(args)) => convert::run(&args),
+        Some(Command::Extract(args)) => extract::run(&args),
+        Some(Command::Inspect(args)) => inspect::run(&args),
+        Some(Command::Diff(args)) => diff::run(&args),
+        Some(Command::Meta(args)) => meta::run(&args),
+        Some(Command::Update(args)) => update::run(&args),
+        Some(Command::Catalog(args)) => catalog::run(&args),
+        Some(Command::Rename(args)) => rename::run(&args),
+        Some(Command::Sync(args)) => sync::run(&args),
+        #[cfg(feature = "tui")]
+        Some(Command::Review(args)) => review::run(&args),
+        Some(Command::Completions { shell }) => {
+            clap_complete::generate(shell, &mut Cli::command(), "manga-compiler", &mut std::io::stdout());
+            Ok(())
+        }
+        None => build::run(&cli.build),
     }
+}
 
-    let out_path = args
-        .output
-        .clone()
-        .unwrap_or_else(|| folder.with_extension("cbz"));
-
-    let file = File::create(&out_path).context("Failed to create output file")?;
-    let mut zip = zip::ZipWriter::new(file);
-    let options = FileOptions::default().compression_method(zip::CompressionMethod::Stored);
-
-    for (_num, path) in page_entries {
-        let mut f = File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
-        let mut buffer = Vec::new();
-        f.read_to_end(&mut buffer).with_context(|| format!("Failed to read {}", path.display()))?;
-        let arc_name = path.file_name().unwrap().to_string_lossy();
-        zip.start_file(arc_name, options)?;
-        zip.write_all(&buffer)?;
+fn generate_manpage(dir: Option<PathBuf>) -> Result<()> {
+    let cmd = Cli::command();
+    match dir {
+        Some(dir) => {
+            std::fs::create_dir_all(&dir)?;
+            clap_mangen::generate_to(cmd, &dir)?;
+            println!("Wrote man pages to {}", dir.display());
+        }
+        None => clap_mangen::Man::new(cmd).render(&mut std::io::stdout())?,
     }
-
-    zip.finish().context("Failed to finalize CBZ archive")?;
-    println!("Successfully created {}", out_path.display());
     Ok(())
 }
@@ -0,0 +1,128 @@
+//! Archive entry names: composed so every reader sees the same characters, and spelled in ASCII
+//! for readers that cannot decode UTF-8 names.
+
+/// A combining mark, the letters it composes with and what each composes to.
+const COMPOSITIONS: &[(char, &str, &str)] = &[
+    ('\u{0300}', "AEIOUaeiou", "ÀÈÌÒÙàèìòù"),
+    ('\u{0301}', "AEIOUYaeiouyCcLlNnRrSsZz", "ÁÉÍÓÚÝáéíóúýĆćĹĺŃńŔŕŚśŹź"),
+    ('\u{0302}', "AEIOUaeiouCcGgHhJjSsWwYy", "ÂÊÎÔÛâêîôûĈĉĜĝĤĥĴĵŜŝŴŵŶŷ"),
+    ('\u{0303}', "ANOanoIiUu", "ÃÑÕãñõĨĩŨũ"),
+    ('\u{0304}', "AaEeIiOoUu", "ĀāĒēĪīŌōŪū"),
+    ('\u{0306}', "AaEeGgIiOoUu", "ĂăĔĕĞğĬĭŎŏŬŭ"),
+    ('\u{0307}', "CcEeGgIZz", "ĊċĖėĠġİŻż"),
+    ('\u{0308}', "AEIOUaeiouyY", "ÄËÏÖÜäëïöüÿŸ"),
+    ('\u{030a}', "AaUu", "ÅåŮů"),
+    ('\u{030b}', "OoUu", "ŐőŰű"),
+    ('\u{030c}', "CcDdEeLlNnRrSsTtZz", "ČčĎďĚěĽľŇňŘřŠšŤťŽž"),
+    ('\u{0327}', "CcGgKkLlNnRrSsTt", "ÇçĢģĶķĻļŅņŖŗŞşŢţ"),
+    ('\u{0328}', "AaEeIiUu", "ĄąĘęĮįŲų"),
+    (
+        '\u{3099}',
+        "かきくけこさしすせそたちつてとはひふへほうゝカキクケコサシスセソタチツテトハヒフヘホウワヰヱヲヽ",
+        "がぎぐげござじずぜぞだぢづでどばびぶべぼゔゞガギグゲゴザジズゼゾダヂヅデドバビブベボヴヷヸヹヺヾ",
+    ),
+    ('\u{309a}', "はひふへほハヒフヘホ", "ぱぴぷぺぽパピプペポ"),
+];
+
+/// Hepburn spelling of the hiragana from U+3041 on; katakana follow the same order from U+30A1.
+/// The small tsu is empty, it doubles the next consonant instead.
+const ROMAJI: [&str; 86] = [
+    "a", "a", "i", "i", "u", "u", "e", "e", "o", "o", "ka", "ga", "ki", "gi", "ku", "gu", "ke", "ge", "ko", "go",
+    "sa", "za", "shi", "ji", "su", "zu", "se", "ze", "so", "zo", "ta", "da", "chi", "ji", "", "tsu", "zu", "te",
+    "de", "to", "do", "na", "ni", "nu", "ne", "no", "ha", "ba", "pa", "hi", "bi", "pi", "fu", "bu", "pu", "he",
+    "be", "pe", "ho", "bo", "po", "ma", "mi", "mu", "me", "mo", "ya", "ya", "yu", "yu", "yo", "yo", "ra", "ri",
+    "ru", "re", "ro", "wa", "wa", "wi", "we", "wo", "n", "vu", "ka", "ke",
+];
+
+/// Indices into [`ROMAJI`] of the small kana that modify the syllable before them.
+const SMALL_VOWELS: [usize; 5] = [0, 2, 4, 6, 8];
+const SMALL_Y: [usize; 3] = [66, 68, 70];
+const SMALL_TSU: usize = 34;
+
+/// Composes letters followed by a combining mark, as names from macOS spell them (NFD), into
+/// the single characters other systems write (NFC). Covers Latin letters and kana.
+pub fn compose(name: &str) -> String {
+    let mut composed = String::with_capacity(name.len());
+    for c in name.chars() {
+        match composed.chars().next_back().and_then(|base| composition(base, c)) {
+            Some(letter) => {
+                composed.pop();
+                composed.push(letter);
+            }
+            None => composed.push(c),
+        }
+    }
+    composed
+}
+
+/// Spells `name` in ASCII: accents are dropped, kana become romaji, full-width forms their ASCII
+/// counterparts and anything else `_`.
+pub fn to_ascii(name: &str) -> String {
+    let mut ascii = String::with_capacity(name.len());
+    let mut after_kana = false;
+    let mut double = false;
+    for c in compose(name).chars() {
+        let kana = kana_index(c);
+        match kana {
+            Some(SMALL_TSU) => double = true,
+            Some(i) if after_kana && SMALL_Y.contains(&i) && ascii.ends_with('i') => {
+                ascii.pop();
+                let palatal = ["sh", "ch", "j"].iter().any(|s| ascii.ends_with(s));
+                ascii.push_str(if palatal { &ROMAJI[i][1..] } else { ROMAJI[i] });
+            }
+            Some(i) if after_kana && SMALL_VOWELS.contains(&i) && ascii.ends_with(['a', 'i', 'u', 'e', 'o']) => {
+                ascii.pop();
+                ascii.push_str(ROMAJI[i]);
+            }
+            Some(i) => {
+                let syllable = ROMAJI[i];
+                if std::mem::take(&mut double) {
+                    ascii.push_str(if syllable.starts_with("ch") { "t" } else { &syllable[..1] });
+                }
+                ascii.push_str(syllable);
+            }
+            None => match c {
+                _ if c.is_ascii() => ascii.push(c),
+                '\u{0300}'..='\u{036f}' | 'ヽ' | 'ヾ' | 'ゝ' | 'ゞ' => {}
+                'ー' => ascii.push('-'),
+                'ヷ'..='ヺ' => ascii.push_str(["va", "vi", "ve", "vo"][c as usize - 'ヷ' as usize]),
+                '\u{3000}' => ascii.push(' '),
+                '！'..='～' => ascii.push(char::from_u32(c as u32 - 0xfee0).unwrap_or('_')),
+                'ß' => ascii.push_str("ss"),
+                'æ' => ascii.push_str("ae"),
+                'Æ' => ascii.push_str("AE"),
+                'œ' => ascii.push_str("oe"),
+                'Œ' => ascii.push_str("OE"),
+                'ø' => ascii.push('o'),
+                'Ø' => ascii.push('O'),
+                'đ' => ascii.push('d'),
+                'Đ' => ascii.push('D'),
+                'ł' => ascii.push('l'),
+                'Ł' => ascii.push('L'),
+                'ı' => ascii.push('i'),
+                _ => ascii.push(base_letter(c).filter(char::is_ascii).unwrap_or('_')),
+            },
+        }
+        after_kana = kana.is_some();
+    }
+    ascii
+}
+
+fn composition(base: char, mark: char) -> Option<char> {
+    let (_, bases, composed) = COMPOSITIONS.iter().find(|(m, _, _)| *m == mark)?;
+    let index = bases.chars().position(|b| b == base)?;
+    composed.chars().nth(index)
+}
+
+/// The letter a composed character is built on, `e` for `é`.
+fn base_letter(c: char) -> Option<char> {
+    COMPOSITIONS.iter().find_map(|(_, bases, composed)| bases.chars().nth(composed.chars().position(|x| x == c)?))
+}
+
+fn kana_index(c: char) -> Option<usize> {
+    match c {
+        '\u{3041}'..='\u{3096}' => Some(c as usize - 0x3041),
+        '\u{30a1}'..='\u{30f6}' => Some(c as usize - 0x30a1),
+        _ => None,
+    }
+}
@@ -0,0 +1,123 @@
+//! Async wrappers for tokio, enabled with the `async` feature.
+//!
+//! The pipeline runs on tokio's blocking pool (where `tokio::fs` does its file IO as well), so
+//! large archives never stall executor threads. Progress arrives as a stream of [`Event`]s.
+//!
+//! ```no_run
+//! use manga_compiler::{Compiler, nonblocking::{self, Event}};
+//!
+//! # async fn example() -> Result<(), manga_compiler::CompileError> {
+//! let mut job = nonblocking::spawn(Compiler::new("uploads/Vol 1"));
+//! while let Some(event) = job.next_event().await {
+//!     if let Event::PageFinished { index, .. } = event {
+//!         println!("page {} done", index + 1);
+//!     }
+//! }
+//! let summary = job.finish().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::compiler::{Compiler, compile_source};
+use crate::config::Config;
+use crate::error::{CompileError, Result};
+use crate::progress::{ProgressObserver, Warning};
+use crate::source::MemorySource;
+use crate::summary::Summary;
+use std::{
+    io::Cursor,
+    panic,
+    path::{Path, PathBuf},
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::{JoinError, JoinHandle};
+
+/// An owned [`ProgressObserver`] callback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event {
+    Started { output: PathBuf, entries: usize },
+    PageStarted { index: usize, name: String },
+    PageFinished { index: usize, name: String },
+    BytesWritten(u64),
+    Warning(Warning),
+}
+
+/// A compilation running in the background.
+pub struct Job<T> {
+    events: UnboundedReceiver<Event>,
+    handle: JoinHandle<Result<T>>,
+}
+
+impl<T> Job<T> {
+    /// The next progress event, or `None` once the compilation is over.
+    pub async fn next_event(&mut self) -> Option<Event> {
+        self.events.recv().await
+    }
+
+    /// Waits for the result; events not yet received are dropped.
+    pub async fn finish(self) -> Result<T> {
+        self.handle.await.unwrap_or_else(|e| Err(join_error(e)))
+    }
+}
+
+/// Starts `compiler` on the blocking pool. Must be called from within a tokio runtime.
+pub fn spawn(compiler: Compiler) -> Job<Summary> {
+    let (tx, events) = mpsc::unbounded_channel();
+    let handle = tokio::task::spawn_blocking(move || compiler.run_with(&mut Channel(tx)));
+    Job { events, handle }
+}
+
+/// Discovers and compiles without progress events.
+pub async fn run(compiler: Compiler) -> Result<Summary> {
+    tokio::task::spawn_blocking(move || compiler.run()).await.unwrap_or_else(|e| Err(join_error(e)))
+}
+
+/// Compiles in-memory files, e.g. an upload, into CBZ bytes via [`compile_source`].
+pub fn spawn_source(source: MemorySource, config: Config) -> Job<(Vec<u8>, Summary)> {
+    let (tx, events) = mpsc::unbounded_channel();
+    let handle = tokio::task::spawn_blocking(move || {
+        let (cbz, summary) = compile_source(&source, config, Cursor::new(Vec::new()), &mut Channel(tx))?;
+        Ok((cbz.into_inner(), summary))
+    });
+    Job { events, handle }
+}
+
+struct Channel(UnboundedSender<Event>);
+
+impl Channel {
+    fn send(&self, event: Event) {
+        // Nobody listening is fine; the compilation goes on.
+        let _ = self.0.send(event);
+    }
+}
+
+impl ProgressObserver for Channel {
+    fn started(&mut self, output: &Path, entries: usize) {
+        self.send(Event::Started { output: output.to_path_buf(), entries });
+    }
+
+    fn page_started(&mut self, index: usize, name: &str) {
+        self.send(Event::PageStarted { index, name: name.to_string() });
+    }
+
+    fn page_finished(&mut self, index: usize, name: &str) {
+        self.send(Event::PageFinished { index, name: name.to_string() });
+    }
+
+    fn bytes_written(&mut self, bytes: u64) {
+        self.send(Event::BytesWritten(bytes));
+    }
+
+    fn warning(&mut self, warning: &Warning) {
+        self.send(Event::Warning(warning.clone()));
+    }
+}
+
+/// Re-raises panics of the blocking task; a task cancelled by runtime shutdown becomes an error.
+fn join_error(err: JoinError) -> CompileError {
+    match err.try_into_panic() {
+        Ok(payload) => panic::resume_unwind(payload),
+        Err(_) => CompileError::Cancelled,
+    }
+}
@@ -0,0 +1,79 @@
+//! Text recognition for the invisible text layer of PDF books, so that the dialogue of their
+//! pages can be searched and copied. [`Tesseract`] runs an installed `tesseract`; other engines
+//! plug in through [`Recognizer`].
+
+use std::{
+    io::{self, Write},
+    process::{Command, Stdio},
+    thread,
+};
+
+/// A word found on a page, its box in pixels from the top left corner of the image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Word {
+    pub text: String,
+    pub left: u32,
+    pub top: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// An OCR engine.
+pub trait Recognizer {
+    /// The words on the page `data` named `name`, a JPEG or PNG image.
+    fn words(&self, name: &str, data: &[u8]) -> io::Result<Vec<Word>>;
+}
+
+/// The `tesseract` command line tool, reading each page on stdin and printing its words as TSV.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tesseract {
+    /// Models to use, e.g. `jpn+eng`; tesseract's default without.
+    pub languages: Option<String>,
+}
+
+impl Recognizer for Tesseract {
+    fn words(&self, name: &str, data: &[u8]) -> io::Result<Vec<Word>> {
+        let mut cmd = Command::new("tesseract");
+        cmd.args(["stdin", "stdout"]);
+        if let Some(languages) = &self.languages {
+            cmd.arg("-l").arg(languages);
+        }
+        cmd.arg("tsv").stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("Failed to run tesseract, which OCR needs: {}", e)))?;
+        let stdin = child.stdin.take();
+        let output = thread::scope(|scope| {
+            // Fed from a thread of its own so that a large page cannot fill both pipes.
+            scope.spawn(move || stdin.map(|mut stdin| stdin.write_all(data)));
+            child.wait_with_output()
+        })?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let last = stderr.lines().map(str::trim).rfind(|line| !line.is_empty()).unwrap_or_default();
+            return Err(io::Error::other(format!("tesseract failed on {} with {}: {}", name, output.status, last)));
+        }
+        Ok(parse_tsv(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+/// The words of tesseract's TSV, the rows of level 5 with some text.
+fn parse_tsv(tsv: &str) -> Vec<Word> {
+    let word = |line: &str| {
+        // level page block paragraph line word left top width height confidence text
+        let fields: Vec<&str> = line.splitn(12, '\t').collect();
+        let [level, _, _, _, _, _, left, top, width, height, _, text] = fields.as_slice() else { return None };
+        let text = text.trim();
+        if *level != "5" || text.is_empty() {
+            return None;
+        }
+        Some(Word {
+            text: text.to_string(),
+            left: left.parse().ok()?,
+            top: top.parse().ok()?,
+            width: width.parse().ok()?,
+            height: height.parse().ok()?,
+        })
+    };
+    tsv.lines().skip(1).filter_map(word).collect()
+}
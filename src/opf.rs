@@ -0,0 +1,37 @@
+use crate::comicinfo::{self, ComicInfo, escape};
+use crate::error::{CompileError, Result};
+use crate::paths;
+use std::{fs, path::Path};
+
+pub const SIDECAR: &str = "metadata.opf";
+
+/// An OPF 2.0 package document carrying the same fields as ComicInfo.xml, in the form Calibre
+/// reads: `dc:*` elements plus its `calibre:series` and `calibre:series_index` metas.
+pub fn to_opf(info: &ComicInfo) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"2.0\">\n  \
+         <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\" xmlns:opf=\"http://www.idpf.org/2007/opf\">\n",
+    );
+    if let Some(title) = &info.title {
+        xml.push_str(&format!("    <dc:title>{}</dc:title>\n", escape(title)));
+    }
+    for author in info.writer.as_deref().into_iter().flat_map(comicinfo::authors) {
+        xml.push_str(&format!("    <dc:creator opf:role=\"aut\">{}</dc:creator>\n", escape(author)));
+    }
+    if let Some(series) = &info.series {
+        xml.push_str(&format!("    <meta name=\"calibre:series\" content=\"{}\"/>\n", escape(series)));
+        if let Some(index) = info.volume.map(f64::from).or(info.number) {
+            xml.push_str(&format!("    <meta name=\"calibre:series_index\" content=\"{}\"/>\n", index));
+        }
+    }
+    xml.push_str("  </metadata>\n</package>\n");
+    xml
+}
+
+/// Writes `metadata.opf` into the folder of `archive`, which Calibre picks up when a folder
+/// holds one book.
+pub fn write_sidecar(archive: &Path, info: &ComicInfo) -> Result<()> {
+    let path = archive.parent().unwrap_or(Path::new("")).join(SIDECAR);
+    fs::write(paths::extended(&path), to_opf(info)).map_err(CompileError::write(&path))
+}
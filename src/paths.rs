@@ -0,0 +1,77 @@
+//! Output paths Windows accepts: names taken from titles and folders lose the characters and
+//! device names it reserves, and paths past `MAX_PATH` get the `\\?\` form.
+
+use crate::error::CompileError;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Names Windows keeps for devices, with any extension: `CON.cbz` cannot be created either.
+const RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1",
+    "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Not allowed in Windows file names, besides the separators and control characters.
+const FORBIDDEN: &[char] = &['<', '>', ':', '"', '|', '?', '*'];
+
+/// Longest path the plain Windows API takes for a file in a created folder: `MAX_PATH` less
+/// room for an 8.3 name.
+const MAX_PLAIN: usize = 248;
+
+/// `name` as a file name Windows can create: forbidden characters become `-`, trailing dots
+/// and spaces, which Windows drops, are cut, and a device name gets a `_`. Unchanged elsewhere.
+pub fn file_name(name: &str) -> String {
+    if !cfg!(windows) {
+        return name.to_string();
+    }
+    let replace = |c: char| if FORBIDDEN.contains(&c) || c.is_control() { '-' } else { c };
+    let mut name: String = name.chars().map(replace).collect();
+    name.truncate(name.trim_end_matches(['.', ' ']).len());
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        name.insert(stem.len(), '_');
+    }
+    if name.is_empty() { "_".to_string() } else { name }
+}
+
+/// A `/`-separated output name, e.g. a rendered template, with every part made a [`file_name`].
+pub fn relative(name: &str) -> PathBuf {
+    name.split('/').map(file_name).collect()
+}
+
+/// `path` in the `\\?\` form on Windows once it is too long for the plain API, which fails on
+/// such paths even where long paths are enabled for the system. Unchanged otherwise.
+pub fn extended(path: &Path) -> PathBuf {
+    if !cfg!(windows) || path.as_os_str().len() < MAX_PLAIN {
+        return path.to_path_buf();
+    }
+    // The long form is taken literally, so it needs an absolute path without `.` or `..`.
+    let Ok(absolute) = std::path::absolute(path) else { return path.to_path_buf() };
+    let text = absolute.to_string_lossy().replace('/', "\\");
+    if text.starts_with(r"\\?\") {
+        return absolute;
+    }
+    match text.strip_prefix(r"\\") {
+        Some(share) => PathBuf::from(format!(r"\\?\UNC\{}", share)),
+        None => PathBuf::from(format!(r"\\?\{}", text)),
+    }
+}
+
+/// Writes `path` through `<path>.part`, which `write` creates and fills: it replaces `path` once
+/// `write` succeeds and is removed otherwise, so a failed or interrupted run never leaves a
+/// truncated file under the real name.
+pub fn write_atomic<T, E: From<CompileError>>(path: &Path, write: impl FnOnce(&Path) -> Result<T, E>) -> Result<T, E> {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    let part = PathBuf::from(part);
+    let result = write(&part).and_then(|value| {
+        fs::rename(extended(&part), extended(path)).map_err(CompileError::write(path))?;
+        Ok(value)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(extended(&part));
+    }
+    result
+}
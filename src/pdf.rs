@@ -0,0 +1,337 @@
+//! PDF books of whole-page images, each page as large as its image at 72 dpi so readers fit it
+//! to the screen. JPEGs are embedded as they are, and so is the compressed data of PNGs PDF
+//! can take directly; other PNGs are flattened onto white first. Recognized text goes over each
+//! image invisibly, in a font without glyphs, so that it can be searched and copied.
+
+use crate::comicinfo::ComicInfo;
+use crate::config::Direction;
+use crate::ocr::Word;
+use image::{ImageFormat, Rgb, RgbImage, codecs::png::PngEncoder};
+use std::io::{self, Write};
+
+/// Writes `pages`, each its name in the archive and its contents in reading order, as a PDF
+/// described by `info`, its bookmarks becoming the outline. `words` holds the words recognized on
+/// each page for the text layer, and is empty for none.
+pub fn write<W: Write>(sink: W, pages: &[(String, Vec<u8>)], info: &ComicInfo, words: &[Vec<Word>]) -> io::Result<W> {
+    let bookmarks: Vec<&(usize, String)> = info.bookmarks.iter().filter(|(i, _)| *i < pages.len()).collect();
+    // Catalog, page tree and info come first, then image, contents and page of each page, then
+    // the outline and the font of the text layer.
+    let page_id = |index: usize| 6 + 3 * index;
+    let outline = 4 + 3 * pages.len();
+    let font = if bookmarks.is_empty() { outline } else { outline + bookmarks.len() + 1 };
+    let layer = !words.is_empty();
+    let objects = if layer { font + 4 } else { font };
+    let mut pdf = Writer { out: sink, pos: 0, offsets: vec![0; objects] };
+    pdf.raw(b"%PDF-1.5\n%\xe2\xe3\xcf\xd3\n")?;
+
+    let mut catalog = String::from("<< /Type /Catalog /Pages 2 0 R");
+    if !bookmarks.is_empty() {
+        catalog.push_str(&format!(" /Outlines {} 0 R /PageMode /UseOutlines", outline));
+    }
+    if info.direction == Some(Direction::Rtl) {
+        catalog.push_str(" /ViewerPreferences << /Direction /R2L >>");
+    }
+    catalog.push_str(" >>");
+    pdf.object(1, catalog.as_bytes())?;
+    let kids: Vec<String> = (0..pages.len()).map(|i| format!("{} 0 R", page_id(i))).collect();
+    pdf.object(2, format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()).as_bytes())?;
+    let mut properties = vec![("Producer", text("manga-compiler"))];
+    properties.extend(info.title.as_deref().map(|t| ("Title", text(t))));
+    properties.extend(info.writer.as_deref().map(|w| ("Author", text(w))));
+    properties.extend(info.series.as_deref().map(|s| ("Subject", text(s))));
+    let properties: Vec<String> = properties.iter().map(|(key, value)| format!("/{} {}", key, value)).collect();
+    pdf.object(3, format!("<< {} >>", properties.join(" ")).as_bytes())?;
+
+    for (index, (name, data)) in pages.iter().enumerate() {
+        let image = page_id(index) - 2;
+        let (width, height) = pdf.image(image, name, data)?;
+        let mut contents = format!("q {} 0 0 {} 0 0 cm /Im Do Q", width, height);
+        if let Some(found) = words.get(index).filter(|found| !found.is_empty()) {
+            contents.push_str(&text_layer(found, height));
+        }
+        pdf.stream(image + 1, "", contents.as_bytes())?;
+        let fonts = if layer { format!(" /Font << /F {} 0 R >>", font) } else { String::new() };
+        let page = format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /XObject << /Im {} 0 R >>{} >> \
+             /Contents {} 0 R >>",
+            width,
+            height,
+            image,
+            fonts,
+            image + 1
+        );
+        pdf.object(page_id(index), page.as_bytes())?;
+    }
+
+    if !bookmarks.is_empty() {
+        let last = outline + bookmarks.len();
+        let count = bookmarks.len();
+        let root = format!("<< /Type /Outlines /First {} 0 R /Last {} 0 R /Count {} >>", outline + 1, last, count);
+        pdf.object(outline, root.as_bytes())?;
+        for (id, (index, title)) in (outline + 1..).zip(bookmarks) {
+            let mut item = format!("<< /Title {} /Parent {} 0 R", text(title), outline);
+            item.push_str(&format!(" /Dest [{} 0 R /Fit]", page_id(*index)));
+            if id > outline + 1 {
+                item.push_str(&format!(" /Prev {} 0 R", id - 1));
+            }
+            if id < last {
+                item.push_str(&format!(" /Next {} 0 R", id + 1));
+            }
+            item.push_str(" >>");
+            pdf.object(id, item.as_bytes())?;
+        }
+    }
+    if layer {
+        pdf.glyphless_font(font)?;
+    }
+    pdf.finish()
+}
+
+/// Each word drawn invisibly (render mode 3) over its box on a page `height` high, as large as
+/// the box and stretched to its width. Every character of the font is half as wide as it is high.
+fn text_layer(words: &[Word], height: u32) -> String {
+    let mut layer = String::from("\nBT 3 Tr");
+    for word in words {
+        let units: Vec<u16> = word.text.encode_utf16().collect();
+        let size = word.height.max(1);
+        let natural = units.len() as f64 * 0.5 * f64::from(size);
+        let stretch = 100.0 * f64::from(word.width.max(1)) / natural;
+        let baseline = height.saturating_sub(word.top + word.height);
+        let hex: String = units.iter().map(|u| format!("{:04X}", u)).collect();
+        layer.push_str(&format!(
+            "\n/F {} Tf {:.1} Tz 1 0 0 1 {} {} Tm <{}> Tj",
+            size, stretch, word.left, baseline, hex
+        ));
+    }
+    layer.push_str("\nET");
+    layer
+}
+
+/// A text string as UTF-16 in hex, which needs no escaping and keeps any script.
+fn text(s: &str) -> String {
+    let hex: String = s.encode_utf16().map(|u| format!("{:04X}", u)).collect();
+    format!("<FEFF{}>", hex)
+}
+
+struct Writer<W> {
+    out: W,
+    pos: u64,
+    /// Byte offset of each object by id, for the cross-reference table.
+    offsets: Vec<u64>,
+}
+
+impl<W: Write> Writer<W> {
+    fn raw(&mut self, data: &[u8]) -> io::Result<()> {
+        self.out.write_all(data)?;
+        self.pos += data.len() as u64;
+        Ok(())
+    }
+
+    fn object(&mut self, id: usize, body: &[u8]) -> io::Result<()> {
+        self.offsets[id] = self.pos;
+        self.raw(format!("{} 0 obj\n", id).as_bytes())?;
+        self.raw(body)?;
+        self.raw(b"\nendobj\n")
+    }
+
+    /// A stream object whose dictionary has `entries` besides the length.
+    fn stream(&mut self, id: usize, entries: &str, data: &[u8]) -> io::Result<()> {
+        self.offsets[id] = self.pos;
+        self.raw(format!("{} 0 obj\n<< {} /Length {} >>\nstream\n", id, entries, data.len()).as_bytes())?;
+        self.raw(data)?;
+        self.raw(b"\nendstream\nendobj\n")
+    }
+
+    /// Writes a page image as object `id` and returns its width and height.
+    fn image(&mut self, id: usize, name: &str, data: &[u8]) -> io::Result<(u32, u32)> {
+        let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", name, reason));
+        match image::guess_format(data) {
+            Ok(ImageFormat::Jpeg) => {
+                let (width, height, components, adobe) = jpeg_header(data).ok_or_else(|| invalid("no JPEG frame"))?;
+                let space = match components {
+                    1 => "/DeviceGray",
+                    3 => "/DeviceRGB",
+                    4 => "/DeviceCMYK",
+                    _ => return Err(invalid("unsupported JPEG colour components")),
+                };
+                // Adobe's CMYK JPEGs store the channels inverted.
+                let decode = if components == 4 && adobe { " /Decode [1 0 1 0 1 0 1 0]" } else { "" };
+                let entries = format!(
+                    "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace {} /BitsPerComponent 8 \
+                     /Filter /DCTDecode{}",
+                    width, height, space, decode
+                );
+                self.stream(id, &entries, data)?;
+                Ok((width, height))
+            }
+            Ok(ImageFormat::Png) => {
+                let png = match Png::parse(data) {
+                    Some(png) => png,
+                    None => {
+                        let flattened = flatten(data).map_err(|_| invalid("could not be decoded"))?;
+                        Png::parse(&flattened).ok_or_else(|| invalid("could not be re-encoded"))?
+                    }
+                };
+                let space = match (png.color, &png.palette) {
+                    (0, _) => "/DeviceGray".to_string(),
+                    (2, _) => "/DeviceRGB".to_string(),
+                    (_, Some(palette)) => {
+                        let hex: String = palette.iter().map(|b| format!("{:02X}", b)).collect();
+                        format!("[/Indexed /DeviceRGB {} <{}>]", palette.len() / 3 - 1, hex)
+                    }
+                    _ => return Err(invalid("PNG palette missing")),
+                };
+                let colors = if png.color == 2 { 3 } else { 1 };
+                let entries = format!(
+                    "/Type /XObject /Subtype /Image /Width {w} /Height {h} /ColorSpace {space} /BitsPerComponent {b} \
+                     /Filter /FlateDecode /DecodeParms << /Predictor 15 /Colors {colors} /BitsPerComponent {b} \
+                     /Columns {w} >>",
+                    w = png.width,
+                    h = png.height,
+                    b = png.depth
+                );
+                self.stream(id, &entries, &png.data)?;
+                Ok((png.width, png.height))
+            }
+            _ => Err(invalid("not a JPEG or PNG image")),
+        }
+    }
+
+    /// Writes the font of the text layer as objects `id` to `id + 3`: a composite font whose codes
+    /// are UTF-16 units, each read back as itself, with no glyphs to embed.
+    fn glyphless_font(&mut self, id: usize) -> io::Result<()> {
+        let font = format!(
+            "<< /Type /Font /Subtype /Type0 /BaseFont /GlyphLessFont /Encoding /Identity-H \
+             /DescendantFonts [{} 0 R] /ToUnicode {} 0 R >>",
+            id + 1,
+            id + 3
+        );
+        self.object(id, font.as_bytes())?;
+        let cid_font = format!(
+            "<< /Type /Font /Subtype /CIDFontType2 /BaseFont /GlyphLessFont \
+             /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> \
+             /FontDescriptor {} 0 R /DW 500 /CIDToGIDMap /Identity >>",
+            id + 2
+        );
+        self.object(id + 1, cid_font.as_bytes())?;
+        let descriptor = "<< /Type /FontDescriptor /FontName /GlyphLessFont /Flags 5 /FontBBox [0 0 500 1000] \
+                          /ItalicAngle 0 /Ascent 1000 /Descent 0 /CapHeight 1000 /StemV 80 >>";
+        self.object(id + 2, descriptor.as_bytes())?;
+        // Ranges may only differ in their last byte, and a block holds 100 of them at most.
+        let mut cmap = String::from(
+            "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n\
+             /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+             /CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+             1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+        );
+        let ranges: Vec<u16> = (0..=0xFF).collect();
+        for block in ranges.chunks(100) {
+            cmap.push_str(&format!("{} beginbfrange\n", block.len()));
+            for high in block {
+                cmap.push_str(&format!("<{0:02X}00> <{0:02X}FF> <{0:02X}00>\n", high));
+            }
+            cmap.push_str("endbfrange\n");
+        }
+        cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend");
+        self.stream(id + 3, "", cmap.as_bytes())
+    }
+
+    /// Writes the cross-reference table and trailer.
+    fn finish(mut self) -> io::Result<W> {
+        let start = self.pos;
+        let mut xref = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len());
+        for offset in &self.offsets[1..] {
+            xref.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        xref.push_str(&format!(
+            "trailer\n<< /Size {} /Root 1 0 R /Info 3 0 R >>\nstartxref\n{}\n%%EOF\n",
+            self.offsets.len(),
+            start
+        ));
+        self.raw(xref.as_bytes())?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Width, height and colour components of a JPEG's frame, and whether it has Adobe's marker.
+fn jpeg_header(data: &[u8]) -> Option<(u32, u32, u8, bool)> {
+    let mut adobe = false;
+    let mut i = 2;
+    while i + 4 <= data.len() {
+        if data[i] != 0xFF {
+            return None;
+        }
+        let marker = data[i + 1];
+        let length = usize::from(u16::from_be_bytes([data[i + 2], data[i + 3]]));
+        // APP14, followed by `Adobe`.
+        if marker == 0xEE && data.get(i + 4..i + 9) == Some(b"Adobe") {
+            adobe = true;
+        }
+        // SOF0 to SOF15 but DHT, JPG and DAC.
+        if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+            let frame = data.get(i + 4..i + 10)?;
+            let height = u32::from(u16::from_be_bytes([frame[1], frame[2]]));
+            let width = u32::from(u16::from_be_bytes([frame[3], frame[4]]));
+            return Some((width, height, frame[5], adobe));
+        }
+        i += 2 + length;
+    }
+    None
+}
+
+/// The parts of a PNG a PDF image takes as they are.
+struct Png {
+    width: u32,
+    height: u32,
+    depth: u8,
+    /// 0 gray, 2 RGB or 3 palette.
+    color: u8,
+    palette: Option<Vec<u8>>,
+    /// The zlib stream of all `IDAT` chunks.
+    data: Vec<u8>,
+}
+
+impl Png {
+    /// `None` for PNGs PDF cannot take directly: with alpha or transparency, or interlaced.
+    fn parse(data: &[u8]) -> Option<Png> {
+        let mut png = Png { width: 0, height: 0, depth: 0, color: 0, palette: None, data: Vec::new() };
+        let mut i = 8;
+        while i + 8 <= data.len() {
+            let length = u32::from_be_bytes(data[i..i + 4].try_into().ok()?) as usize;
+            let kind = &data[i + 4..i + 8];
+            let body = data.get(i + 8..i + 8 + length)?;
+            match kind {
+                b"IHDR" if body.len() >= 13 => {
+                    png.width = u32::from_be_bytes(body[0..4].try_into().ok()?);
+                    png.height = u32::from_be_bytes(body[4..8].try_into().ok()?);
+                    (png.depth, png.color) = (body[8], body[9]);
+                    if ![0, 2, 3].contains(&png.color) || body[12] != 0 {
+                        return None;
+                    }
+                }
+                b"PLTE" => png.palette = Some(body.to_vec()),
+                b"tRNS" => return None,
+                b"IDAT" => png.data.extend_from_slice(body),
+                b"IEND" => break,
+                _ => {}
+            }
+            i += 12 + length;
+        }
+        (png.width > 0 && !png.data.is_empty()).then_some(png)
+    }
+}
+
+/// Decodes a PNG and encodes it again without alpha, set against white, and not interlaced.
+fn flatten(data: &[u8]) -> image::ImageResult<Vec<u8>> {
+    let page = image::load_from_memory_with_format(data, ImageFormat::Png)?.to_rgba8();
+    let mut rgb = RgbImage::new(page.width(), page.height());
+    for (out, pixel) in rgb.pixels_mut().zip(page.pixels()) {
+        let [r, g, b, a] = pixel.0.map(u32::from);
+        let blend = |c: u32| ((c * a + 255 * (255 - a)) / 255) as u8;
+        *out = Rgb([blend(r), blend(g), blend(b)]);
+    }
+    let mut png = Vec::new();
+    rgb.write_with_encoder(PngEncoder::new(&mut png))?;
+    Ok(png)
+}
@@ -0,0 +1,55 @@
+use crate::summary::Summary;
+use std::path::Path;
+
+/// Something worth reporting that does not stop the build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Warning {
+    /// File names in the source folder that did not match the page pattern.
+    IgnoredFiles(Vec<String>),
+    /// Gaps in the page numbering tolerated by [`MissingPolicy::Warn`](crate::MissingPolicy).
+    MissingPages(Vec<u32>),
+    /// Copies of pages, e.g. `page-5 (1).jpg`, left out since another copy was kept.
+    DroppedDuplicates(Vec<String>),
+    /// The same for pages named with chapter and page, by chapter.
+    MissingChapterPages(Vec<(String, Vec<u32>)>),
+    /// Pages kept by [`DedupeExact::Warn`](crate::DedupeExact) that repeat an earlier page byte for
+    /// byte, each with that page.
+    IdenticalPages(Vec<(String, String)>),
+    /// Pages stored as they are since preparing them for the device would take more memory than
+    /// `max-memory` allows.
+    OverMemory(Vec<String>),
+    /// Pages stored unfiltered by [`OnFilterError::Keep`](crate::config::OnFilterError), each with
+    /// what went wrong.
+    Unfiltered(Vec<(String, String)>),
+}
+
+/// Receives structured progress while a book is compiled. Every method defaults to doing
+/// nothing, and `()` is the observer that ignores everything.
+///
+/// ```
+/// use manga_compiler::progress::ProgressObserver;
+///
+/// struct Percent(usize);
+///
+/// impl ProgressObserver for Percent {
+///     fn started(&mut self, _output: &std::path::Path, entries: usize) {
+///         self.0 = entries;
+///     }
+///     fn page_finished(&mut self, index: usize, _name: &str) {
+///         println!("{}%", (index + 1) * 100 / self.0);
+///     }
+/// }
+/// ```
+pub trait ProgressObserver {
+    /// Called once before anything is written, with the number of entries to pack.
+    fn started(&mut self, _output: &Path, _entries: usize) {}
+    fn page_started(&mut self, _index: usize, _name: &str) {}
+    fn page_finished(&mut self, _index: usize, _name: &str) {}
+    /// Total archive bytes written so far.
+    fn bytes_written(&mut self, _bytes: u64) {}
+    fn warning(&mut self, _warning: &Warning) {}
+    fn finished(&mut self, _summary: &Summary) {}
+}
+
+impl ProgressObserver for () {}
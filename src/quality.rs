@@ -0,0 +1,220 @@
+//! Scan quality scores for the pages of a book: sharpness, JPEG blocking, resolution and skew,
+//! the checks scan groups otherwise make by eye. Sharpness and resolution are judged against
+//! the book's other pages, since what is normal depends on the source.
+
+use image::{GrayImage, imageops::FilterType};
+use serde::Serialize;
+
+/// Pages listed as the worst of a book.
+pub const WORST: usize = 5;
+
+/// Blocking above this, the 8×8 grid of JPEG being this much more visible than other edges,
+/// counts as artifacts.
+const BLOCKY: f64 = 1.3;
+/// Pages less sharp than this share of the book's median count as blurry.
+const BLURRY: f64 = 0.5;
+/// Pages less tall than this share of the book's median count as low resolution.
+const LOW_RES: f64 = 0.8;
+/// Skew in degrees from which a page counts as crooked.
+const SKEWED: f64 = 0.5;
+/// Largest skew looked for, in degrees.
+const MAX_SKEW: f64 = 5.0;
+/// Steps between neighbouring pixels above this are edges of the drawing, not blocking.
+const EDGE: f64 = 32.0;
+/// Standard deviation of the gray values under which a page is taken as blank and not blurry.
+const BLANK: f64 = 8.0;
+
+/// The scores of one page. `score` runs from 100 for a clean page down to 0.
+#[derive(Serialize, Debug, Clone)]
+pub struct PageQuality {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    /// Variance of the Laplacian of the gray values; higher is sharper.
+    pub sharpness: f64,
+    /// Edge strength across the 8×8 block borders of JPEG against within blocks; 1 is none.
+    pub blockiness: f64,
+    /// Rotation of the content in degrees, clockwise positive.
+    pub skew: f64,
+    pub score: u32,
+    /// What is wrong with the page, e.g. `blurry` or `skewed`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub issues: Vec<&'static str>,
+    #[serde(skip)]
+    blank: bool,
+}
+
+/// The pages of a book in reading order, scored against each other.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct QualityReport {
+    pub pages: Vec<PageQuality>,
+}
+
+impl QualityReport {
+    /// Scores the [`measure`]d `pages` against each other.
+    pub fn new(mut pages: Vec<PageQuality>) -> QualityReport {
+        let sharp: Vec<f64> = pages.iter().filter(|p| !p.blank).map(|p| p.sharpness).collect();
+        let sharp = median(sharp);
+        let height = median(pages.iter().map(|p| f64::from(p.height)).collect());
+        for page in &mut pages {
+            let mut penalty = 0.0;
+            if !page.blank && sharp > 0.0 {
+                let relative = (page.sharpness / sharp).min(1.0);
+                penalty += 40.0 * (1.0 - relative);
+                if relative < BLURRY {
+                    page.issues.push("blurry");
+                }
+            }
+            penalty += (60.0 * (page.blockiness - 1.0)).clamp(0.0, 30.0);
+            if page.blockiness > BLOCKY {
+                page.issues.push("JPEG artifacts");
+            }
+            let relative = (f64::from(page.height) / height).min(1.0);
+            penalty += 20.0 * (1.0 - relative);
+            if relative < LOW_RES {
+                page.issues.push("low resolution");
+            }
+            penalty += (10.0 * page.skew.abs()).min(20.0);
+            if page.skew.abs() >= SKEWED {
+                page.issues.push("skewed");
+            }
+            page.score = (100.0 - penalty).clamp(0.0, 100.0).round() as u32;
+        }
+        QualityReport { pages }
+    }
+
+    /// Up to `count` of the lowest scoring pages with an issue, worst first.
+    pub fn worst(&self, count: usize) -> Vec<&PageQuality> {
+        let mut pages: Vec<&PageQuality> = self.pages.iter().filter(|p| !p.issues.is_empty()).collect();
+        pages.sort_by_key(|p| p.score);
+        pages.truncate(count);
+        pages
+    }
+
+    /// The mean score of the pages.
+    pub fn score(&self) -> u32 {
+        let total: u32 = self.pages.iter().map(|p| p.score).sum();
+        total.checked_div(self.pages.len() as u32).unwrap_or(100)
+    }
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(f64::total_cmp);
+    values[values.len() / 2]
+}
+
+/// Measures the page `data` named `name`, `None` if it cannot be decoded. Its score and issues
+/// are left for [`QualityReport::new`].
+pub fn measure(name: String, data: &[u8]) -> Option<PageQuality> {
+    let gray = image::load_from_memory(data).ok()?.to_luma8();
+    let (width, height) = gray.dimensions();
+    let (sharpness, deviation) = sharpness(&gray);
+    let blank = deviation < BLANK;
+    let skew = if blank { 0.0 } else { skew(&gray) };
+    let blockiness = blockiness(&gray);
+    Some(PageQuality { name, width, height, sharpness, blockiness, skew, score: 100, issues: Vec::new(), blank })
+}
+
+/// Variance of the 4-neighbour Laplacian and standard deviation of the gray values.
+fn sharpness(gray: &GrayImage) -> (f64, f64) {
+    let (width, height) = gray.dimensions();
+    let at = |x: u32, y: u32| f64::from(gray.get_pixel(x, y).0[0]);
+    let (mut sum, mut squares, mut count) = (0.0, 0.0, 0.0);
+    for y in 1..height.saturating_sub(1) {
+        for x in 1..width.saturating_sub(1) {
+            let laplacian = at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1) - 4.0 * at(x, y);
+            sum += laplacian;
+            squares += laplacian * laplacian;
+            count += 1.0;
+        }
+    }
+    let pixels = f64::from(width) * f64::from(height);
+    let mean = gray.pixels().map(|p| f64::from(p.0[0])).sum::<f64>() / pixels.max(1.0);
+    let variance = gray.pixels().map(|p| (f64::from(p.0[0]) - mean).powi(2)).sum::<f64>() / pixels.max(1.0);
+    if count == 0.0 {
+        return (0.0, variance.sqrt());
+    }
+    let mean_laplacian = sum / count;
+    (squares / count - mean_laplacian * mean_laplacian, variance.sqrt())
+}
+
+/// Mean step between neighbouring pixels across the 8×8 block borders over the mean step
+/// within blocks, rows and columns together, leaving out the edges of the drawing.
+fn blockiness(gray: &GrayImage) -> f64 {
+    let (width, height) = gray.dimensions();
+    let at = |x: u32, y: u32| f64::from(gray.get_pixel(x, y).0[0]);
+    let (mut border, mut borders, mut inner, mut inners) = (0.0, 0.0, 0.0, 0.0);
+    let mut step = |offset: u32, difference: f64| {
+        // Edges of the drawing itself would drown the small steps blocking makes.
+        if difference > EDGE {
+            return;
+        }
+        if offset.is_multiple_of(8) {
+            border += difference;
+            borders += 1.0;
+        } else {
+            inner += difference;
+            inners += 1.0;
+        }
+    };
+    for y in 0..height {
+        for x in 1..width {
+            step(x, (at(x, y) - at(x - 1, y)).abs());
+        }
+    }
+    for y in 1..height {
+        for x in 0..width {
+            step(y, (at(x, y) - at(x, y - 1)).abs());
+        }
+    }
+    if borders == 0.0 || inners == 0.0 {
+        return 1.0;
+    }
+    // Evened out by a step of one so that the few small steps of clean line art on white do not count.
+    (border / borders + 1.0) / (inner / inners + 1.0)
+}
+
+/// The rotation at which the dark pixels line up best into rows and columns, as panel borders
+/// and text do on a straight page.
+fn skew(gray: &GrayImage) -> f64 {
+    // A smaller copy is enough for the angle and much quicker to search.
+    let scale = 1000.0 / f64::from(gray.width().max(gray.height()));
+    let small = if scale < 1.0 {
+        let (w, h) = ((f64::from(gray.width()) * scale) as u32, (f64::from(gray.height()) * scale) as u32);
+        image::imageops::resize(gray, w.max(1), h.max(1), FilterType::Triangle)
+    } else {
+        gray.clone()
+    };
+    let dark: Vec<(f64, f64)> = small
+        .enumerate_pixels()
+        .filter(|(_, _, p)| p.0[0] < 128)
+        .map(|(x, y, _)| (f64::from(x), f64::from(y)))
+        .collect();
+    if dark.len() < 100 {
+        return 0.0;
+    }
+    let (width, height) = (small.width() as usize, small.height() as usize);
+    let sharpness = |degrees: f64| {
+        let tan = degrees.to_radians().tan();
+        // Room for lines sheared past either edge, the fine search going a little past the coarse one.
+        let margin = ((width.max(height) as f64) * (MAX_SKEW + 1.0).to_radians().tan()).ceil() as usize + 1;
+        let mut rows = vec![0u32; height + 2 * margin];
+        let mut columns = vec![0u32; width + 2 * margin];
+        for &(x, y) in &dark {
+            rows[((y - x * tan).round() as isize + margin as isize) as usize] += 1;
+            columns[((x + y * tan).round() as isize + margin as isize) as usize] += 1;
+        }
+        rows.iter().chain(&columns).map(|&n| f64::from(n) * f64::from(n)).sum::<f64>()
+    };
+    let best = |from: f64, to: f64, step: f64| {
+        let steps = ((to - from) / step).round() as i32;
+        let angles = (0..=steps).map(|i| from + f64::from(i) * step);
+        angles.map(|a| (a, sharpness(a))).max_by(|a, b| a.1.total_cmp(&b.1)).map(|(a, _)| a)
+    };
+    let coarse = best(-MAX_SKEW, MAX_SKEW, 0.5).unwrap_or(0.0);
+    let fine = best(coarse - 0.5, coarse + 0.5, 0.05).unwrap_or(coarse);
+    (fine * 100.0).round() / 100.0
+}
@@ -0,0 +1,75 @@
+//! Detached signatures of finished archives, made by an installed `minisign` or `gpg`.
+
+use crate::error::{CompileError, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Signer {
+    /// `<archive>.minisig`, checked with `minisign -V -p <public key> -m <archive>`
+    Minisign,
+    /// `<archive>.sig`, checked with `gpg --verify <archive>.sig`
+    Gpg,
+}
+
+impl Signer {
+    fn program(self) -> &'static str {
+        match self {
+            Signer::Minisign => "minisign",
+            Signer::Gpg => "gpg",
+        }
+    }
+
+    pub fn signature_path(self, archive: &Path) -> PathBuf {
+        let mut path = archive.as_os_str().to_owned();
+        path.push(match self {
+            Signer::Minisign => ".minisig",
+            Signer::Gpg => ".sig",
+        });
+        PathBuf::from(path)
+    }
+}
+
+/// Signs `archive` next to it and returns the signature's path. `key` is minisign's secret key
+/// file or gpg's key id, each tool's default key without one. Either may prompt for the key's
+/// password on the terminal.
+pub fn sign(signer: Signer, key: Option<&str>, archive: &Path) -> Result<PathBuf> {
+    let signature = signer.signature_path(archive);
+    let mut cmd = Command::new(signer.program());
+    match signer {
+        Signer::Minisign => {
+            cmd.arg("-S").arg("-m").arg(archive).arg("-x").arg(&signature);
+            if let Some(key) = key {
+                cmd.arg("-s").arg(key);
+            }
+        }
+        Signer::Gpg => {
+            cmd.args(["--yes", "--detach-sign", "--output"]).arg(&signature);
+            if let Some(key) = key {
+                cmd.arg("--local-user").arg(key);
+            }
+            cmd.arg(archive);
+        }
+    }
+    // Like hook output, kept off stdout for `--json`.
+    cmd.stdout(io::stderr());
+    let command = describe(&cmd);
+    let status = cmd.status().map_err(|source| CompileError::SignStart { command: command.clone(), source })?;
+    if !status.success() {
+        return Err(CompileError::Sign { command, status });
+    }
+    Ok(signature)
+}
+
+fn describe(cmd: &Command) -> String {
+    let mut words: Vec<OsString> = vec![cmd.get_program().to_owned()];
+    words.extend(cmd.get_args().map(|a| a.to_owned()));
+    words.iter().map(|w| w.to_string_lossy()).collect::<Vec<_>>().join(" ")
+}
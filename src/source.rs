@@ -0,0 +1,108 @@
+use crate::config::FOLDER_CONFIG;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Where the files of a book come from. The pipeline only lists and reads through this trait,
+/// so it also runs where there is no filesystem.
+pub trait Source {
+    /// Stands in for the folder name: the `{folder}` placeholder and the default title.
+    fn name(&self) -> &str;
+    /// The candidate page files, in any order.
+    fn files(&self) -> io::Result<Vec<PathBuf>>;
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    /// Size in bytes of a file, if known.
+    fn size(&self, _path: &Path) -> Option<u64> {
+        None
+    }
+    /// Modification time of a file, if known.
+    fn modified(&self, _path: &Path) -> Option<SystemTime> {
+        None
+    }
+}
+
+/// The files directly inside a folder, skipping subfolders and the folder config.
+#[derive(Debug, Clone)]
+pub struct DirSource {
+    folder: PathBuf,
+    name: String,
+}
+
+impl DirSource {
+    /// Returns `None` if the folder has no UTF-8 name.
+    pub fn new(folder: impl Into<PathBuf>) -> Option<Self> {
+        let folder = folder.into();
+        let name = folder.file_name()?.to_str()?.to_string();
+        Some(DirSource { folder, name })
+    }
+}
+
+impl Source for DirSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn files(&self) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.folder)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() && entry.file_name() != FOLDER_CONFIG {
+                files.push(entry.path());
+            }
+        }
+        Ok(files)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn size(&self, path: &Path) -> Option<u64> {
+        fs::metadata(path).ok().map(|m| m.len())
+    }
+
+    fn modified(&self, path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+}
+
+/// Files held in memory, e.g. dropped into a browser; paths are the bare file names.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySource {
+    pub name: String,
+    pub files: Vec<(String, Vec<u8>)>,
+}
+
+impl MemorySource {
+    pub fn new(name: impl Into<String>) -> Self {
+        MemorySource { name: name.into(), files: Vec::new() }
+    }
+
+    pub fn add(&mut self, file_name: impl Into<String>, data: Vec<u8>) {
+        self.files.push((file_name.into(), data));
+    }
+}
+
+impl Source for MemorySource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn files(&self) -> io::Result<Vec<PathBuf>> {
+        Ok(self.files.iter().map(|(name, _)| PathBuf::from(name)).collect())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files
+            .iter()
+            .find(|(name, _)| Path::new(name) == path)
+            .map(|(_, data)| data.clone())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} not found", path.display())))
+    }
+
+    fn size(&self, path: &Path) -> Option<u64> {
+        self.files.iter().find(|(name, _)| Path::new(name) == path).map(|(_, data)| data.len() as u64)
+    }
+}
@@ -0,0 +1,178 @@
+use crate::quality::QualityReport;
+use serde::Serialize;
+use std::{path::PathBuf, time::Duration};
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Stats {
+    pub pages: usize,
+    pub skipped: usize,
+    pub warnings: usize,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    /// `bytes_out / bytes_in`, kept in sync by [`Stats::add`] and [`Stats::set_bytes`].
+    pub ratio: f64,
+    pub elapsed_secs: f64,
+}
+
+impl Stats {
+    pub fn set_bytes(&mut self, bytes_in: u64, bytes_out: u64) {
+        self.bytes_in = bytes_in;
+        self.bytes_out = bytes_out;
+        self.ratio = ratio(bytes_in, bytes_out);
+    }
+
+    pub fn set_elapsed(&mut self, elapsed: Duration) {
+        self.elapsed_secs = elapsed.as_secs_f64();
+    }
+
+    pub fn add(&mut self, other: &Stats) {
+        self.pages += other.pages;
+        self.skipped += other.skipped;
+        self.warnings += other.warnings;
+        self.elapsed_secs += other.elapsed_secs;
+        self.set_bytes(self.bytes_in + other.bytes_in, self.bytes_out + other.bytes_out);
+    }
+
+    pub fn line(&self) -> String {
+        format!(
+            "{} pages, {} in, {} out ({:.1}%), {} skipped, {} warnings, {:.2}s",
+            self.pages,
+            human_bytes(self.bytes_in),
+            human_bytes(self.bytes_out),
+            self.ratio * 100.0,
+            self.skipped,
+            self.warnings,
+            self.elapsed_secs
+        )
+    }
+}
+
+/// Result of compiling one book.
+#[derive(Serialize, Debug, Clone)]
+pub struct Summary {
+    pub source: PathBuf,
+    pub output: PathBuf,
+    /// Files in the source folder that did not match the page pattern.
+    pub ignored: Vec<String>,
+    /// Copies of pages left out for another copy, e.g. `page-5 (1).jpg`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub duplicates: Vec<String>,
+    /// Page numbers that were missing but tolerated by [`MissingPolicy::Warn`](crate::MissingPolicy) or `Compact`.
+    pub missing: Vec<u32>,
+    /// Pages added to an archive that already held the others, see
+    /// [`Compiler::append`](crate::Compiler::append).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub appended: Option<usize>,
+    /// Detached signature written next to the archive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<PathBuf>,
+    /// Cover image written next to the archive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cover: Option<PathBuf>,
+    /// What became of an archive that was already at the output path.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub existing: Option<Existing>,
+    /// Scores of the source pages, see [`Config::quality_report`](crate::Config::quality_report).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<QualityReport>,
+    #[serde(flatten)]
+    pub stats: Stats,
+}
+
+/// How an archive already at a book's output path was dealt with.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Existing {
+    Overwritten,
+    /// The book was written next to it under a new name.
+    Renamed,
+    /// The book was not compiled.
+    Skipped,
+}
+
+/// One book of a batch run, successful or not.
+#[derive(Serialize, Debug)]
+pub struct BookResult {
+    pub source: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<Stats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub resumed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub existing: Option<Existing>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<QualityReport>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct BatchSummary {
+    pub books: Vec<BookResult>,
+    pub compiled: usize,
+    pub failed: usize,
+    pub resumed: usize,
+    /// Books left alone since their archive already existed.
+    pub skipped_existing: usize,
+    pub total: Stats,
+}
+
+impl BatchSummary {
+    pub fn push(&mut self, source: PathBuf, result: &anyhow::Result<Summary>) {
+        let book = match result {
+            Ok(summary) => {
+                self.compiled += 1;
+                self.total.add(&summary.stats);
+                BookResult {
+                    source,
+                    output: Some(summary.output.clone()),
+                    stats: Some(summary.stats.clone()),
+                    error: None,
+                    resumed: false,
+                    existing: summary.existing,
+                    quality: summary.quality.clone(),
+                }
+            }
+            Err(e) => {
+                self.failed += 1;
+                let error = Some(format!("{:#}", e));
+                BookResult { source, output: None, stats: None, error, resumed: false, existing: None, quality: None }
+            }
+        };
+        self.books.push(book);
+    }
+
+    pub fn push_resumed(&mut self, source: PathBuf, output: PathBuf) {
+        self.resumed += 1;
+        let (output, existing) = (Some(output), None);
+        let book = BookResult { source, output, stats: None, error: None, resumed: true, existing, quality: None };
+        self.books.push(book);
+    }
+
+    pub fn push_skipped(&mut self, source: PathBuf, output: PathBuf) {
+        self.skipped_existing += 1;
+        let (output, existing) = (Some(output), Some(Existing::Skipped));
+        let book = BookResult { source, output, stats: None, error: None, resumed: false, existing, quality: None };
+        self.books.push(book);
+    }
+}
+
+fn ratio(bytes_in: u64, bytes_out: u64) -> f64 {
+    if bytes_in == 0 { 0.0 } else { bytes_out as f64 / bytes_in as f64 }
+}
+
+pub fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
@@ -0,0 +1,51 @@
+use crate::error::{CompileError, Result};
+use regex::Regex;
+use std::sync::LazyLock;
+
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{(\w+)(?::0?(\d+))?\}").unwrap());
+
+/// Expands `{name}` and `{name:0N}` placeholders. Numeric values are zero-padded to `N` digits
+/// before any decimals, and path separators inside values are replaced so a value can never add
+/// directories.
+pub fn render(template: &str, vars: &[(&str, Option<String>)]) -> Result<String> {
+    let mut out = String::new();
+    let mut last = 0;
+    for caps in PLACEHOLDER.captures_iter(template) {
+        let whole = caps.get(0).unwrap();
+        let name = &caps[1];
+        let value = match vars.iter().find(|(n, _)| *n == name) {
+            Some((_, Some(v))) => v.replace(['/', '\\'], "-"),
+            Some((_, None)) => {
+                return Err(CompileError::Config(format!(
+                    "Output template uses {{{}}} but it is not known for this book",
+                    name
+                )));
+            }
+            None => {
+                let known: Vec<&str> = vars.iter().map(|(n, _)| *n).collect();
+                return Err(CompileError::Config(format!(
+                    "Unknown output template variable {{{}}}, expected one of {:?}",
+                    name, known
+                )));
+            }
+        };
+        out.push_str(&template[last..whole.start()]);
+        match caps.get(2).and_then(|w| w.as_str().parse::<usize>().ok()) {
+            Some(width) if is_number(&value) => {
+                let whole = value.split('.').next().unwrap_or_default();
+                out.push_str(&format!("{:0>width$}{}", whole, &value[whole.len()..]))
+            }
+            _ => out.push_str(&value),
+        }
+        last = whole.end();
+    }
+    out.push_str(&template[last..]);
+    Ok(out)
+}
+
+/// Digits with at most one decimal point, as in `15` or `15.5`.
+fn is_number(value: &str) -> bool {
+    let (whole, decimals) = value.split_once('.').unwrap_or((value, "0"));
+    [whole, decimals].iter().all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+}
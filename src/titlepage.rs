@@ -0,0 +1,172 @@
+//! Generated pages announcing a chapter, for volumes assembled from chapters without one.
+
+use crate::error::{CompileError, Result};
+use ab_glyph::{Font, FontArc, PxScale, ScaleFont, point};
+use font8x8::{BASIC_FONTS, LATIN_FONTS, UnicodeFonts};
+use image::{Rgb, RgbImage};
+use std::{fs, path::Path};
+
+/// Bitmap glyphs are 8 by 8 pixels, drawn at this fraction of the page width per character.
+const COLUMNS: u32 = 24;
+const LINE_HEIGHT: u32 = 12;
+/// Font size as a fraction of the page width.
+const FONT_SIZE: f32 = 1.0 / 14.0;
+
+/// How title pages look.
+#[derive(Clone)]
+pub struct TitleStyle {
+    /// TrueType or OpenType font; `None` draws the built-in bitmap font, which only knows Latin
+    /// letters.
+    pub font: Option<FontArc>,
+    /// The text is black on light backgrounds and white on dark ones.
+    pub background: Rgb<u8>,
+}
+
+impl Default for TitleStyle {
+    fn default() -> Self {
+        TitleStyle { font: None, background: Rgb([255, 255, 255]) }
+    }
+}
+
+impl TitleStyle {
+    pub fn load_font(path: &Path) -> Result<FontArc> {
+        let data = fs::read(path).map_err(CompileError::io(path))?;
+        FontArc::try_from_vec(data)
+            .map_err(|_| CompileError::Config(format!("{} is not a TrueType or OpenType font", path.display())))
+    }
+}
+
+/// `#rrggbb`, `rrggbb`, `white` or `black`.
+pub fn parse_color(s: &str) -> Option<Rgb<u8>> {
+    match s.to_ascii_lowercase().as_str() {
+        "white" => return Some(Rgb([255, 255, 255])),
+        "black" => return Some(Rgb([0, 0, 0])),
+        _ => {}
+    }
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(Rgb([channel(0)?, channel(2)?, channel(4)?]))
+}
+
+/// Draws `text` on a page of the given size, centered and wrapped at word boundaries.
+pub fn render(text: &str, width: u32, height: u32, style: &TitleStyle) -> RgbImage {
+    let mut page = RgbImage::from_pixel(width, height, style.background);
+    let [r, g, b] = style.background.0;
+    let light = 299 * r as u32 + 587 * g as u32 + 114 * b as u32 > 127_500;
+    let ink = if light { Rgb([0, 0, 0]) } else { Rgb([255, 255, 255]) };
+    match &style.font {
+        Some(font) => draw_outlines(&mut page, text, font, ink),
+        None => draw_bitmap(&mut page, text, ink),
+    }
+    page
+}
+
+fn draw_outlines(page: &mut RgbImage, text: &str, font: &FontArc, ink: Rgb<u8>) {
+    let (width, height) = page.dimensions();
+    let scaled = font.as_scaled(PxScale::from(width as f32 * FONT_SIZE));
+    let measure = |line: &str| {
+        let ids: Vec<_> = line.chars().map(|c| scaled.glyph_id(c)).collect();
+        let kerning: f32 = ids.windows(2).map(|w| scaled.kern(w[0], w[1])).sum();
+        ids.iter().map(|&id| scaled.h_advance(id)).sum::<f32>() + kerning
+    };
+    let lines = wrap(text, |line| measure(line) <= width as f32 * 0.85);
+
+    let line_height = scaled.height() + scaled.line_gap();
+    let mut y = (height as f32 - lines.len() as f32 * line_height).max(0.0) / 2.0;
+    for line in &lines {
+        let mut x = (width as f32 - measure(line)).max(0.0) / 2.0;
+        let mut previous = None;
+        for c in line.chars() {
+            let id = scaled.glyph_id(c);
+            if let Some(previous) = previous {
+                x += scaled.kern(previous, id);
+            }
+            let glyph = id.with_scale_and_position(scaled.scale(), point(x, y + scaled.ascent()));
+            if let Some(outline) = font.outline_glyph(glyph) {
+                let bounds = outline.px_bounds();
+                outline.draw(|gx, gy, coverage| {
+                    let (px, py) = (bounds.min.x as i64 + gx as i64, bounds.min.y as i64 + gy as i64);
+                    if (0..width as i64).contains(&px) && (0..height as i64).contains(&py) {
+                        let pixel = page.get_pixel_mut(px as u32, py as u32);
+                        for (channel, target) in pixel.0.iter_mut().zip(ink.0) {
+                            *channel = (*channel as f32 + (target as f32 - *channel as f32) * coverage) as u8;
+                        }
+                    }
+                });
+            }
+            x += scaled.h_advance(id);
+            previous = Some(id);
+        }
+        y += line_height;
+    }
+}
+
+fn draw_bitmap(page: &mut RgbImage, text: &str, ink: Rgb<u8>) {
+    let (width, height) = page.dimensions();
+    let scale = (width / (COLUMNS * 8)).max(1);
+    let columns = (width / (8 * scale)).saturating_sub(2).max(1) as usize;
+    let lines = wrap(text, |line| line.chars().count() <= columns);
+
+    let block = lines.len() as u32 * LINE_HEIGHT * scale;
+    let mut y = height.saturating_sub(block) / 2;
+    for line in &lines {
+        let mut x = width.saturating_sub(line.chars().count() as u32 * 8 * scale) / 2;
+        for c in line.chars() {
+            draw_glyph(page, glyph(c), x, y, scale, ink);
+            x += 8 * scale;
+        }
+        y += LINE_HEIGHT * scale;
+    }
+}
+
+/// Characters the bitmap font lacks are drawn as `?`, dashes as `-`.
+fn glyph(c: char) -> [u8; 8] {
+    let c = if matches!(c, '\u{2012}'..='\u{2015}') { '-' } else { c };
+    BASIC_FONTS.get(c).or_else(|| LATIN_FONTS.get(c)).or_else(|| BASIC_FONTS.get('?')).unwrap_or_default()
+}
+
+/// Each row byte holds the leftmost pixel in its lowest bit.
+fn draw_glyph(page: &mut RgbImage, rows: [u8; 8], x: u32, y: u32, scale: u32, ink: Rgb<u8>) {
+    for (row, bits) in (0..).zip(rows) {
+        for column in (0..8).filter(|c| bits & (1 << c) != 0) {
+            for dy in 0..scale {
+                for dx in 0..scale {
+                    let (px, py) = (x + column * scale + dx, y + row * scale + dy);
+                    if px < page.width() && py < page.height() {
+                        page.put_pixel(px, py, ink);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Breaks `text` into lines that `fits`, splitting words too long for a line of their own.
+fn wrap(text: &str, fits: impl Fn(&str) -> bool) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let joined = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+        if fits(&joined) {
+            line = joined;
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        for c in word.chars() {
+            line.push(c);
+            if !fits(&line) && line.chars().count() > 1 {
+                line.pop();
+                lines.push(std::mem::replace(&mut line, c.to_string()));
+            }
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
@@ -0,0 +1,69 @@
+//! `volumes.toml`: which chapters make up each volume.
+//!
+//! ```toml
+//! vol1 = "1-7"
+//! vol2 = "8-15.5"
+//! vol3 = "16-22, 24"
+//! ```
+
+use crate::exit::{self, Code, WithCode};
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::{collections::BTreeMap, fs, path::Path, sync::LazyLock};
+
+pub const VOLUMES_FILE: &str = "volumes.toml";
+
+static KEY: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d+)\D*$").unwrap());
+
+/// Chapter ranges per volume number.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VolumeMap {
+    /// Inclusive `(first, last)` chapter ranges, by volume.
+    pub volumes: BTreeMap<u32, Vec<(f64, f64)>>,
+}
+
+impl VolumeMap {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))
+            .code(Code::Config)?;
+        Self::parse(&text).with_context(|| format!("Invalid {}", path.display())).code(Code::Config)
+    }
+
+    /// Keys name the volume by their last number (`vol1`, `v02`, `3`); values list chapters
+    /// and inclusive ranges separated by commas.
+    pub fn parse(text: &str) -> Result<Self> {
+        let table: BTreeMap<String, String> = toml::from_str(text)?;
+        let mut volumes = BTreeMap::new();
+        for (key, value) in table {
+            let volume: u32 = match KEY.captures(&key) {
+                Some(caps) => caps[1].parse()?,
+                None => return Err(exit::fail(Code::Config, format!("{:?} does not name a volume number", key))),
+            };
+            let mut ranges = Vec::new();
+            for part in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+                let (first, last) = part.split_once('-').unwrap_or((part, part));
+                let parse = |s: &str| {
+                    s.trim().parse::<f64>().with_context(|| format!("{}: {:?} is not a chapter number", key, s.trim()))
+                };
+                let (first, last) = (parse(first)?, parse(last)?);
+                if first > last {
+                    return Err(exit::fail(Code::Config, format!("{}: range {:?} runs backwards", key, part)));
+                }
+                ranges.push((first, last));
+            }
+            if volumes.insert(volume, ranges).is_some() {
+                return Err(exit::fail(Code::Config, format!("Volume {} is listed twice", volume)));
+            }
+        }
+        Ok(VolumeMap { volumes })
+    }
+
+    /// The first volume whose ranges contain `chapter`.
+    pub fn volume_of(&self, chapter: f64) -> Option<u32> {
+        self.volumes
+            .iter()
+            .find(|(_, ranges)| ranges.iter().any(|&(first, last)| (first..=last).contains(&chapter)))
+            .map(|(&volume, _)| volume)
+    }
+}
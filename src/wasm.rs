@@ -0,0 +1,63 @@
+//! Browser bindings, built with `wasm-pack build --target web`.
+
+use crate::config::{Config, Direction, MissingPolicy};
+use crate::{MemorySource, compile_source};
+use std::io::Cursor;
+use wasm_bindgen::prelude::*;
+
+/// Files dropped into the page plus the settings to compile them with.
+#[wasm_bindgen(js_name = Book)]
+pub struct JsBook {
+    source: MemorySource,
+    config: Config,
+}
+
+#[wasm_bindgen(js_class = Book)]
+impl JsBook {
+    /// `name` stands in for the folder name, both for the page pattern and the default title.
+    #[wasm_bindgen(constructor)]
+    pub fn new(name: String) -> JsBook {
+        JsBook { source: MemorySource::new(name), config: Config::default() }
+    }
+
+    pub fn add(&mut self, file_name: String, data: Vec<u8>) {
+        self.source.add(file_name, data);
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_title(&mut self, title: String) {
+        self.config.title = Some(title);
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_series(&mut self, series: String) {
+        self.config.series = Some(series);
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_volume(&mut self, volume: u32) {
+        self.config.volume = Some(volume);
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_pattern(&mut self, pattern: String) {
+        self.config.pattern = Some(pattern);
+    }
+
+    #[wasm_bindgen(setter = rightToLeft)]
+    pub fn set_right_to_left(&mut self, rtl: bool) {
+        self.config.direction = Some(if rtl { Direction::Rtl } else { Direction::Ltr });
+    }
+
+    #[wasm_bindgen(setter = allowMissing)]
+    pub fn set_allow_missing(&mut self, allow: bool) {
+        self.config.missing = Some(if allow { MissingPolicy::Warn } else { MissingPolicy::Fail });
+    }
+
+    /// Returns the CBZ bytes.
+    pub fn compile(&self) -> Result<Vec<u8>, JsError> {
+        let (cbz, _) = compile_source(&self.source, self.config.clone(), Cursor::new(Vec::new()), &mut ())
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(cbz.into_inner())
+    }
+}
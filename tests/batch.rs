@@ -0,0 +1,63 @@
+//! `batch` verifies every archive it writes against the book that was compiled, whose numbering
+//! may have gaps on purpose.
+
+mod common;
+
+use common::{PNG, temp_root};
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    process::Command,
+};
+use zip::ZipArchive;
+
+/// A library in a fresh temporary folder with one book `vol` of the pages `files`, each different
+/// from the others unless named in `copies`.
+fn library(test: &str, files: &[&str], copies: &[&str]) -> PathBuf {
+    let root = temp_root(test);
+    for (index, file) in files.iter().enumerate() {
+        let path = root.join("lib/vol").join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let unique = if copies.contains(file) { 0 } else { index as u8 + 1 };
+        fs::write(path, [PNG, &[unique]].concat()).unwrap();
+    }
+    root
+}
+
+/// The entries of `lib/vol.cbz` once `batch` has compiled and verified it.
+fn batch(root: &Path, args: &[&str]) -> Vec<String> {
+    let output = Command::new(env!("CARGO_BIN_EXE_manga-compiler"))
+        .current_dir(root)
+        .env("MANGA_COMPILER_CONFIG", root.join("no-config.toml"))
+        .args(["batch", "lib"])
+        .args(args)
+        .output()
+        .unwrap();
+    let entries = File::open(root.join("lib/vol.cbz"))
+        .map(|file| ZipArchive::new(file).unwrap().file_names().map(str::to_string).collect::<Vec<_>>());
+    let _ = fs::remove_dir_all(root);
+    assert!(output.status.success(), "batch failed: {}", String::from_utf8_lossy(&output.stderr));
+    let mut entries = entries.unwrap();
+    entries.sort();
+    entries
+}
+
+#[test]
+fn page_selection() {
+    let root = library("pages", &["vol-1.png", "vol-2.png", "vol-3.png", "vol-4.png"], &[]);
+    assert_eq!(batch(&root, &["--pages", "3-"]), ["vol-3.png", "vol-4.png"]);
+}
+
+#[test]
+fn dropped_duplicates() {
+    let files = ["vol-1.png", "vol-2.png", "vol-3.png", "vol-4.png"];
+    let root = library("dedupe", &files, &["vol-1.png", "vol-3.png"]);
+    assert_eq!(batch(&root, &["--dedupe-exact"]), ["vol-1.png", "vol-2.png", "vol-4.png"]);
+}
+
+#[test]
+fn extras_and_gaps() {
+    let root = library("extras", &["vol-1.png", "vol-3.png", "extras/credits.png"], &[]);
+    let entries = batch(&root, &["--extras-dir", "extras", "--missing", "warn"]);
+    assert_eq!(entries, ["extras/credits.png", "vol-1.png", "vol-3.png"]);
+}
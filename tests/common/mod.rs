@@ -0,0 +1,14 @@
+//! Fixtures shared by the integration tests.
+
+use std::{fs, path::PathBuf};
+
+/// The PNG signature, enough for a file to be taken as a page.
+pub const PNG: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// A fresh, empty temporary folder for `test`.
+pub fn temp_root(test: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("manga-compiler-test-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    root
+}
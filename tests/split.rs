@@ -0,0 +1,73 @@
+//! `split --by-folder` and `--by-bookmark` cut an archive among the pages that remain once
+//! `--pages` and `--dedupe-exact` have left some out.
+
+mod common;
+
+use common::{PNG, temp_root};
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::PathBuf,
+    process::Command,
+};
+use zip::{ZipWriter, write::FileOptions};
+
+/// An archive `Big.cbz` in a fresh temporary folder holding chapters of three, three and two
+/// pages in folders of their own, each bookmarked, with its fourth page a copy of its first.
+fn archive(test: &str) -> PathBuf {
+    let root = temp_root(test);
+    let mut zip = ZipWriter::new(File::create(root.join("Big.cbz")).unwrap());
+    let chapters = [("Ch 1 - Start", 3), ("Ch 2 - Hawk", 3), ("Ch 3 - End", 2)];
+    let mut index = 0;
+    for (folder, pages) in chapters {
+        for page in 1..=pages {
+            zip.start_file(format!("{}/p{}.png", folder, page), FileOptions::default()).unwrap();
+            zip.write_all(&[PNG, &[if index == 3 { 0 } else { index }]].concat()).unwrap();
+            index += 1;
+        }
+    }
+    zip.start_file("ComicInfo.xml", FileOptions::default()).unwrap();
+    zip.write_all(
+        b"<?xml version=\"1.0\"?><ComicInfo><Title>Big</Title><Pages><Page Image=\"0\" Bookmark=\"Start\"/>\
+          <Page Image=\"3\" Bookmark=\"Hawk\"/><Page Image=\"6\" Bookmark=\"End\"/></Pages></ComicInfo>",
+    )
+    .unwrap();
+    zip.finish().unwrap();
+    root
+}
+
+/// The chapters `split --dry-run` prints.
+fn chapters(test: &str, args: &[&str]) -> Vec<String> {
+    let root = archive(test);
+    let output = Command::new(env!("CARGO_BIN_EXE_manga-compiler"))
+        .current_dir(&root)
+        .env("MANGA_COMPILER_CONFIG", root.join("no-config.toml"))
+        .args(["split", "Big.cbz", "--dry-run"])
+        .args(args)
+        .output()
+        .unwrap();
+    let _ = fs::remove_dir_all(&root);
+    assert!(output.status.success(), "split failed: {}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8_lossy(&output.stdout).lines().map(str::to_string).collect()
+}
+
+#[test]
+fn page_selection() {
+    let expected = [
+        "Big Ch. 001: Big-005.png - Big-006.png (2 pages)",
+        "Big Ch. 002: Big-007.png - Big-008.png (2 pages)",
+    ];
+    assert_eq!(chapters("split-folder-pages", &["--by-folder", "--pages", "5-"]), expected);
+    assert_eq!(chapters("split-bookmark-pages", &["--by-bookmark", "--pages", "5-"]), expected);
+}
+
+#[test]
+fn dropped_duplicates() {
+    let expected = [
+        "Big Ch. 001: Big-001.png - Big-003.png (3 pages)",
+        "Big Ch. 002: Big-005.png - Big-006.png (2 pages)",
+        "Big Ch. 003: Big-007.png - Big-008.png (2 pages)",
+    ];
+    assert_eq!(chapters("split-folder-dedupe", &["--by-folder", "--dedupe-exact"]), expected);
+    assert_eq!(chapters("split-bookmark-dedupe", &["--by-bookmark", "--dedupe-exact"]), expected);
+}